@@ -0,0 +1,397 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The native atomCAD document format, which saves a whole workspace: every
+//! molecule (atoms, bond orders, bonding sites and the orientation of each
+//! atom, and the unit cell of periodic molecules) and the state of the
+//! camera.
+//!
+//! Documents can be stored in a compact binary form (CBOR, preceded by a magic
+//! number) or as human-readable JSON.  Both forms carry a format version.
+//! When a document is read it is first decoded into an untyped tree, which is
+//! upgraded one version at a time by `MIGRATIONS` before being converted into
+//! the current schema.  Fields added in later versions have defaults, and
+//! unknown fields are ignored, so small additions need no migration at all.
+
+mod schema;
+
+use glam::{Quat, Vec3};
+use molecule::Molecule;
+use serde_json::Value;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+
+/// The version of the document format written by this version of atomCAD.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// The first bytes of a binary document.
+pub const MAGIC: &[u8; 8] = b"atomCAD\0";
+
+/// The file extension of binary documents.  JSON documents use `.json`.
+pub const EXTENSION: &str = "atomcad";
+
+// Each entry upgrades the untyped tree of a document from version `i + 1` to
+// version `i + 2`.  When the schema changes incompatibly, bump
+// `FORMAT_VERSION` and append a migration here.
+type Migration = fn(&mut Value) -> Result<(), DocumentError>;
+const MIGRATIONS: &[Migration] = &[];
+
+/// The two encodings of a document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Binary,
+    Json,
+}
+
+impl Format {
+    /// Picks the format for a file from its extension: JSON for `.json` and
+    /// binary for anything else.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Binary,
+        }
+    }
+}
+
+/// The camera of a workspace: it orbits `focus` at a distance of `radius`,
+/// looking along its rotated -z axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraState {
+    pub focus: Vec3,
+    pub radius: f32,
+    pub rotation: Quat,
+    pub upside_down: bool,
+}
+
+/// Everything which is saved in an atomCAD document.
+#[derive(Clone, Debug, Default)]
+pub struct Document {
+    pub molecules: Vec<Molecule>,
+    pub camera: Option<CameraState>,
+}
+
+/// An error encountered while reading or writing a document.
+#[derive(Debug)]
+pub enum DocumentError {
+    Io(io::Error),
+    /// The document is malformed.
+    Format(String),
+    /// The document was written by a newer version of atomCAD.
+    UnsupportedVersion(u64),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::Io(err) => write!(f, "{}", err),
+            DocumentError::Format(message) => write!(f, "invalid document: {}", message),
+            DocumentError::UnsupportedVersion(version) => write!(
+                f,
+                "document format version {} is newer than the supported version {}",
+                version, FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DocumentError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DocumentError {
+    fn from(err: io::Error) -> Self {
+        DocumentError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for DocumentError {
+    fn from(err: serde_json::Error) -> Self {
+        match err.classify() {
+            serde_json::error::Category::Io => DocumentError::Io(err.into()),
+            _ => DocumentError::Format(err.to_string()),
+        }
+    }
+}
+
+// Upgrades the untyped tree of a document to `current` using `migrations`.
+fn apply_migrations(
+    value: &mut Value,
+    migrations: &[Migration],
+    current: u64,
+) -> Result<(), DocumentError> {
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| DocumentError::Format("missing format version".to_owned()))?;
+    if version == 0 || version > current {
+        return Err(DocumentError::UnsupportedVersion(version));
+    }
+    for migration in &migrations[(version - 1) as usize..] {
+        migration(value)?;
+    }
+    value["version"] = current.into();
+    Ok(())
+}
+
+impl Document {
+    /// Reads a document in either format, recognising binary documents by
+    /// their magic number.
+    pub fn read(mut reader: impl BufRead) -> Result<Self, DocumentError> {
+        if reader.fill_buf()?.starts_with(MAGIC) {
+            Self::read_binary(reader)
+        } else {
+            Self::read_json(reader)
+        }
+    }
+
+    pub fn read_json(reader: impl Read) -> Result<Self, DocumentError> {
+        Self::from_value(serde_json::from_reader(reader)?)
+    }
+
+    pub fn read_binary(mut reader: impl Read) -> Result<Self, DocumentError> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(DocumentError::Format(
+                "not an atomCAD binary document".to_owned(),
+            ));
+        }
+        let value = ciborium::de::from_reader(reader).map_err(|err| match err {
+            ciborium::de::Error::Io(err) => DocumentError::Io(err),
+            err => DocumentError::Format(err.to_string()),
+        })?;
+        Self::from_value(value)
+    }
+
+    fn from_value(mut value: Value) -> Result<Self, DocumentError> {
+        apply_migrations(&mut value, MIGRATIONS, FORMAT_VERSION)?;
+        let document: schema::Document = serde_json::from_value(value)?;
+        document.into_document()
+    }
+
+    pub fn write(&self, format: Format, writer: impl Write) -> Result<(), DocumentError> {
+        match format {
+            Format::Binary => self.write_binary(writer),
+            Format::Json => self.write_json(writer),
+        }
+    }
+
+    pub fn write_json(&self, mut writer: impl Write) -> Result<(), DocumentError> {
+        serde_json::to_writer_pretty(&mut writer, &schema::Document::from_document(self))?;
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    pub fn write_binary(&self, mut writer: impl Write) -> Result<(), DocumentError> {
+        writer.write_all(MAGIC)?;
+        ciborium::ser::into_writer(&schema::Document::from_document(self), writer).map_err(|err| {
+            match err {
+                ciborium::ser::Error::Io(err) => DocumentError::Io(err),
+                err => DocumentError::Format(err.to_string()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use molecule::cell::UnitCell;
+    use molecule::{fixtures, Particle};
+    use periodic_table::Element;
+    use petgraph::visit::EdgeRef;
+
+    fn ethane() -> Molecule {
+        let (mut molecule, atoms) = fixtures::chain(&[Element::Carbon, Element::Carbon]);
+        let (carbon, other) = (atoms[0], atoms[1]);
+        if let Particle::Atom(atom) = &mut molecule.graph[other].particle {
+            atom.isotope = Some(13);
+        }
+        // Leave a hole in the graph's indices, which must not upset saving
+        let site = molecule.bonding_sites(carbon).next().unwrap();
+        molecule.graph.remove_node(site);
+        molecule
+    }
+
+    fn document() -> Document {
+        Document {
+            molecules: vec![
+                ethane(),
+                Molecule {
+                    cell: UnitCell::from_parameters(
+                        Vec3::new(5.0, 6.0, 7.0),
+                        Vec3::new(90.0, 100.0, 120.0),
+                    ),
+                    ..Molecule::new()
+                },
+            ],
+            camera: Some(CameraState {
+                focus: Vec3::new(1.0, 2.0, 3.0),
+                radius: 6.0,
+                rotation: Quat::from_rotation_y(0.5),
+                upside_down: false,
+            }),
+        }
+    }
+
+    // A description of a molecule which doesn't depend on its graph indices
+    fn summary(molecule: &Molecule) -> Vec<String> {
+        let graph = &molecule.graph;
+        let mut lines: Vec<String> = graph
+            .node_indices()
+            .map(|node| {
+                let particle = match &graph[node].particle {
+                    Particle::Atom(atom) => format!(
+                        "{} {:?} {:?}",
+                        atom.element.symbol(),
+                        atom.isotope,
+                        atom.facing.map(|facing| graph[facing].pos)
+                    ),
+                    Particle::BondingSite => "site".to_owned(),
+                };
+                let mut bonds: Vec<String> = graph
+                    .edges(node)
+                    .map(|edge| format!("{:?}={}", graph[edge.target()].pos, edge.weight()))
+                    .collect();
+                bonds.sort();
+                format!("{} {:?} {:?}", particle, graph[node].pos, bonds)
+            })
+            .collect();
+        lines.sort();
+        lines
+    }
+
+    fn check(copy: &Document, original: &Document) {
+        assert_eq!(copy.camera, original.camera);
+        assert_eq!(copy.molecules.len(), original.molecules.len());
+        for (a, b) in copy.molecules.iter().zip(original.molecules.iter()) {
+            assert_eq!(summary(a), summary(b));
+            assert_eq!(a.cell, b.cell);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let original = document();
+        for format in [Format::Binary, Format::Json] {
+            let mut bytes = Vec::new();
+            original.write(format, &mut bytes).unwrap();
+            assert_eq!(bytes.starts_with(MAGIC), format == Format::Binary);
+            check(&Document::read(bytes.as_slice()).unwrap(), &original);
+        }
+    }
+
+    #[test]
+    fn versions() {
+        let mut json = Vec::new();
+        document().write_json(&mut json).unwrap();
+        let mut value: Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["version"], FORMAT_VERSION);
+        assert_eq!(MIGRATIONS.len() as u64, FORMAT_VERSION - 1);
+
+        // Unknown fields from newer minor revisions are ignored
+        value["future_setting"] = "ignored".into();
+        value["camera"]["projection"] = "orthographic".into();
+        check(&Document::from_value(value.clone()).unwrap(), &document());
+
+        value["version"] = (FORMAT_VERSION + 1).into();
+        assert!(matches!(
+            Document::from_value(value),
+            Err(DocumentError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn migrations() {
+        fn rename_field(value: &mut Value) -> Result<(), DocumentError> {
+            let old = value["old"].take();
+            value["new"] = old;
+            Ok(())
+        }
+        fn double_field(value: &mut Value) -> Result<(), DocumentError> {
+            let new = value["new"].as_u64().unwrap();
+            value["new"] = (new * 2).into();
+            Ok(())
+        }
+        let migrations: &[Migration] = &[rename_field, double_field];
+
+        let mut value = serde_json::json!({ "version": 1, "old": 21 });
+        apply_migrations(&mut value, migrations, 3).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "version": 3, "old": null, "new": 42 })
+        );
+
+        // Only the migrations after a document's version are applied
+        let mut value = serde_json::json!({ "version": 2, "new": 5 });
+        apply_migrations(&mut value, migrations, 3).unwrap();
+        assert_eq!(value["new"], 10);
+    }
+
+    #[test]
+    fn errors() {
+        let err = Document::read("{\"molecules\": []}".as_bytes());
+        assert!(matches!(err, Err(DocumentError::Format(_))));
+        // A binary document holding a number instead of a map
+        let err = Document::read(&b"atomCAD\0\x01"[..]);
+        assert!(matches!(err, Err(DocumentError::Format(_))));
+        assert!(Document::read(&b"atomCAD\0\xa1"[..]).is_err());
+        let json = r#"{"version": 1, "molecules": [{"nodes": [], "bonds": [[0, 1, 1]]}]}"#;
+        assert!(matches!(
+            Document::read(json.as_bytes()),
+            Err(DocumentError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_molecules() {
+        // Two carbon atoms, then two bonding sites
+        let read = |bonds: serde_json::Value| {
+            let atom = serde_json::json!({
+                "pos": [0, 0, 0],
+                "particle": {"kind": "atom", "element": "C"},
+            });
+            let site = serde_json::json!({
+                "pos": [1, 0, 0],
+                "particle": {"kind": "bonding_site"},
+            });
+            let value = serde_json::json!({
+                "version": FORMAT_VERSION,
+                "molecules": [{"nodes": [atom, atom, site, site], "bonds": bonds}],
+            });
+            Document::from_value(value)
+        };
+        let document = read(serde_json::json!([[0, 1, 2], [0, 2, 1], [1, 3, 1]])).unwrap();
+        assert_eq!(document.molecules[0].graph.edge_count(), 3);
+
+        for bonds in [
+            // A bond listed twice, in either direction
+            serde_json::json!([[0, 1, 1], [1, 0, 1], [0, 2, 1], [1, 3, 1]]),
+            // Bond orders out of range
+            serde_json::json!([[0, 1, 0], [0, 2, 1], [1, 3, 1]]),
+            serde_json::json!([[0, 1, 4], [0, 2, 1], [1, 3, 1]]),
+            // A bonding site with no atom
+            serde_json::json!([[0, 1, 1], [0, 2, 1]]),
+            // A bonding site shared by two atoms
+            serde_json::json!([[0, 2, 1], [1, 2, 1], [1, 3, 1]]),
+            // Two bonding sites bonded to each other
+            serde_json::json!([[0, 2, 1], [2, 3, 1]]),
+        ] {
+            assert!(
+                matches!(read(bonds.clone()), Err(DocumentError::Format(_))),
+                "{}",
+                bonds
+            );
+        }
+    }
+}
+
+// End of File
@@ -0,0 +1,266 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// The serialized form of the current version of the document format.  These
+// types are kept separate from the in-memory model so that the model can
+// change without silently changing the format.  Particles are stored in a
+// list and referred to by their position in it, rather than by their index in
+// the molecule graph, which may have holes.
+
+use crate::{CameraState, DocumentError, FORMAT_VERSION};
+use glam::{Mat3, Quat, Vec3};
+use molecule::cell::UnitCell;
+use molecule::{Atom, MolNode, Particle};
+use periodic_table::Element;
+use petgraph::stable_graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Document {
+    version: u64,
+    #[serde(default)]
+    molecules: Vec<Molecule>,
+    #[serde(default)]
+    camera: Option<Camera>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Molecule {
+    nodes: Vec<Node>,
+    // (node, node, bond order), including the bonds to bonding sites
+    bonds: Vec<(usize, usize, u8)>,
+    // The lattice vectors of the unit cell, one after another
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cell: Option<[f32; 9]>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Node {
+    pos: [f32; 3],
+    #[serde(default)]
+    vel: [f32; 3],
+    particle: NodeParticle,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum NodeParticle {
+    Atom {
+        element: String,
+        #[serde(default)]
+        charge: i8,
+        #[serde(default)]
+        facing: Option<usize>,
+        // The mass number, if the atom is a specific isotope
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        isotope: Option<u16>,
+    },
+    BondingSite,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Camera {
+    focus: [f32; 3],
+    radius: f32,
+    // A quaternion, as [x, y, z, w]
+    rotation: [f32; 4],
+    #[serde(default)]
+    upside_down: bool,
+}
+
+fn format_error(message: String) -> DocumentError {
+    DocumentError::Format(message)
+}
+
+impl Molecule {
+    fn from_molecule(molecule: &molecule::Molecule) -> Self {
+        let graph = &molecule.graph;
+        let positions: HashMap<NodeIndex, usize> = graph
+            .node_indices()
+            .enumerate()
+            .map(|(position, node)| (node, position))
+            .collect();
+
+        let nodes = graph
+            .node_indices()
+            .map(|node| {
+                let MolNode { pos, vel, particle } = &graph[node];
+                let particle = match particle {
+                    Particle::Atom(atom) => NodeParticle::Atom {
+                        element: atom.element.symbol().to_owned(),
+                        charge: atom.charge,
+                        facing: atom
+                            .facing
+                            .and_then(|facing| positions.get(&facing).copied()),
+                        isotope: atom.isotope,
+                    },
+                    Particle::BondingSite => NodeParticle::BondingSite,
+                };
+                Node {
+                    pos: pos.to_array(),
+                    vel: vel.to_array(),
+                    particle,
+                }
+            })
+            .collect();
+
+        let bonds = graph
+            .edge_indices()
+            .filter_map(|edge| {
+                let (a, b) = graph.edge_endpoints(edge)?;
+                Some((positions[&a], positions[&b], graph[edge]))
+            })
+            .collect();
+
+        Molecule {
+            nodes,
+            bonds,
+            cell: molecule.cell.map(|cell| cell.lattice().to_cols_array()),
+        }
+    }
+
+    fn into_molecule(self) -> Result<molecule::Molecule, DocumentError> {
+        let mut molecule = molecule::Molecule::new();
+        if let Some(lattice) = self.cell {
+            molecule.cell = Some(
+                UnitCell::new(Mat3::from_cols_array(&lattice))
+                    .ok_or_else(|| format_error("unit cell has no volume".to_owned()))?,
+            );
+        }
+        let count = self.nodes.len();
+        let check = |position: usize| {
+            if position < count {
+                Ok(NodeIndex::new(position))
+            } else {
+                Err(format_error(format!(
+                    "reference to missing node {}",
+                    position
+                )))
+            }
+        };
+
+        // The graph is empty, so nodes are given indices in the same order as
+        // they are stored
+        for node in self.nodes {
+            let particle = match node.particle {
+                NodeParticle::Atom {
+                    element,
+                    charge,
+                    facing,
+                    isotope,
+                } => Particle::Atom(Atom {
+                    element: element
+                        .parse::<Element>()
+                        .map_err(|err| format_error(err.to_string()))?,
+                    charge,
+                    facing: facing.map(check).transpose()?,
+                    isotope,
+                }),
+                NodeParticle::BondingSite => Particle::BondingSite,
+            };
+            molecule.graph.add_node(MolNode {
+                pos: Vec3::from_array(node.pos),
+                vel: Vec3::from_array(node.vel),
+                particle,
+            });
+        }
+
+        for (a, b, order) in self.bonds {
+            let (a, b) = (check(a)?, check(b)?);
+            if a == b {
+                return Err(format_error(format!(
+                    "node {} is bonded to itself",
+                    a.index()
+                )));
+            }
+            if !(1..=3).contains(&order) {
+                return Err(format_error(format!(
+                    "nodes {} and {} have a bond of order {}",
+                    a.index(),
+                    b.index(),
+                    order
+                )));
+            }
+            if molecule.graph.find_edge(a, b).is_some() {
+                return Err(format_error(format!(
+                    "nodes {} and {} are bonded more than once",
+                    a.index(),
+                    b.index()
+                )));
+            }
+            molecule.graph.add_edge(a, b, order);
+        }
+
+        // Each bonding site belongs to exactly one atom
+        let graph = &molecule.graph;
+        for site in graph.node_indices() {
+            if graph[site].particle != Particle::BondingSite {
+                continue;
+            }
+            let mut neighbors = graph.neighbors(site);
+            match (neighbors.next(), neighbors.next()) {
+                (Some(atom), None) if matches!(graph[atom].particle, Particle::Atom(_)) => {}
+                (Some(other), None) => {
+                    return Err(format_error(format!(
+                        "bonding site {} is bonded to bonding site {}",
+                        site.index(),
+                        other.index()
+                    )))
+                }
+                (None, _) => {
+                    return Err(format_error(format!(
+                        "bonding site {} belongs to no atom",
+                        site.index()
+                    )))
+                }
+                (Some(_), Some(_)) => {
+                    return Err(format_error(format!(
+                        "bonding site {} has more than one bond",
+                        site.index()
+                    )))
+                }
+            }
+        }
+
+        Ok(molecule)
+    }
+}
+
+impl Document {
+    pub(crate) fn from_document(document: &crate::Document) -> Self {
+        Document {
+            version: FORMAT_VERSION,
+            molecules: document
+                .molecules
+                .iter()
+                .map(Molecule::from_molecule)
+                .collect(),
+            camera: document.camera.map(|camera| Camera {
+                focus: camera.focus.to_array(),
+                radius: camera.radius,
+                rotation: camera.rotation.to_array(),
+                upside_down: camera.upside_down,
+            }),
+        }
+    }
+
+    pub(crate) fn into_document(self) -> Result<crate::Document, DocumentError> {
+        Ok(crate::Document {
+            molecules: self
+                .molecules
+                .into_iter()
+                .map(Molecule::into_molecule)
+                .collect::<Result<_, _>>()?,
+            camera: self.camera.map(|camera| CameraState {
+                focus: Vec3::from_array(camera.focus),
+                radius: camera.radius,
+                rotation: Quat::from_array(camera.rotation),
+                upside_down: camera.upside_down,
+            }),
+        })
+    }
+}
+
+// End of File
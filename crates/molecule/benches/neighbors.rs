@@ -0,0 +1,111 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Times the force field on ever larger molecules, to show that it takes
+//! time proportional to the number of particles.  Run with
+//! `cargo bench -p atomcad-molecule`; the time per particle should stay
+//! roughly flat as the particle count grows.
+//!
+//! Building the neighbour list is timed separately from updating it while it
+//! is still fresh, which is what most relaxation steps do.
+
+use atomcad_molecule::forcefield::neighbors::NeighborList;
+use atomcad_molecule::forcefield::uff::Uff;
+use atomcad_molecule::forcefield::{ForceField, Topology};
+use atomcad_molecule::{vsepr, Molecule};
+use glam::Vec3;
+use periodic_table::Element;
+use petgraph::stable_graph::NodeIndex;
+use std::time::{Duration, Instant};
+
+const CHAIN_LENGTH: usize = 20;
+
+// Alkane chains (with a bonding site for each hydrogen) on a square grid, so
+// that there are bonds, angles and torsions as well as nonbonded pairs
+fn alkane_grid(side: usize) -> Molecule {
+    let mut molecule = Molecule::new();
+    let directions = vsepr::bond_directions(Element::Carbon, &[]);
+    for y in 0..side {
+        for z in 0..side {
+            let start = Vec3::new(0.0, y as f32, z as f32) * 5.0;
+            let mut carbon =
+                molecule.add_atom(Element::Carbon, start, Vec3::Z, None, &directions, false);
+            // Grow the chain along +x
+            for _ in 1..CHAIN_LENGTH {
+                let position = molecule.graph[carbon].pos;
+                let site = molecule
+                    .bonding_sites(carbon)
+                    .max_by(|&a, &b| {
+                        let along = |site: NodeIndex| (molecule.graph[site].pos - position).x;
+                        along(a).total_cmp(&along(b))
+                    })
+                    .unwrap();
+                carbon = molecule.bond_atom_at_site(site, Element::Carbon).unwrap();
+            }
+        }
+    }
+    molecule
+}
+
+// The fastest of a few runs of `f`
+fn time(mut f: impl FnMut()) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>18} {:>18} {:>18} {:>18}",
+        "particles", "build list", "update list", "parameterize", "relax step"
+    );
+    for side in [4, 6, 8, 11, 16] {
+        let mut molecule = alkane_grid(side);
+        let topology = Topology::new(&molecule);
+        let positions = topology.positions(&molecule);
+        let count = positions.len();
+
+        let force_field = Uff::default();
+        let mut list = NeighborList::new(force_field.cutoff, force_field.skin);
+        let excluded = |a, b| topology.is_excluded(a, b);
+        let build = time(|| list.rebuild(&positions, excluded));
+        // The particles have not moved, so the list is kept
+        let update = time(|| assert!(!list.update(&positions, excluded)));
+
+        // A freshly parameterized potential builds its neighbour list on the
+        // first evaluation, so this includes the build
+        let parameterize = time(|| {
+            let mut forces = vec![Vec3::ZERO; count];
+            force_field
+                .parameterize(&topology)
+                .evaluate(&positions, &mut forces);
+        });
+
+        // After the first step the parameters are reused, and the neighbour
+        // list only rebuilt once the particles have moved far enough
+        let mut parameterized = None;
+        molecule.relax_step(&force_field, &mut parameterized);
+        let relax = time(|| molecule.relax_step(&force_field, &mut parameterized));
+
+        let column = |duration: Duration| {
+            let ms = duration.as_secs_f64() * 1e3;
+            format!("{:.2}ms {:.2}us", ms, ms * 1e3 / count as f64)
+        };
+        println!(
+            "{:>10} {:>18} {:>18} {:>18} {:>18}",
+            count,
+            column(build),
+            column(update),
+            column(parameterize),
+            column(relax),
+        );
+    }
+}
+
+// End of File
@@ -0,0 +1,387 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Periodic boundary conditions.  A molecule with a unit cell is one copy of
+//! an infinite crystal, repeated along each of the three (not necessarily
+//! perpendicular) lattice vectors of the cell.  Particles interact with the
+//! nearest copy of each other particle (the minimum image convention), and
+//! are wrapped back into the cell whenever they leave it.
+//!
+//! Positions within the cell are described by fractional coordinates, which
+//! run from 0 to 1 along each lattice vector.
+
+use crate::Molecule;
+use glam::{Mat3, Vec3};
+use petgraph::stable_graph::NodeIndex;
+
+/// A triclinic unit cell, with one corner at the origin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UnitCell {
+    // The lattice vectors, as columns, and the inverse matrix which converts
+    // positions to fractional coordinates
+    lattice: Mat3,
+    inverse: Mat3,
+}
+
+impl UnitCell {
+    /// The cell spanned by the columns of `lattice`, or `None` if they do not
+    /// span a volume.
+    pub fn new(lattice: Mat3) -> Option<Self> {
+        let volume = lattice.determinant().abs();
+        if !volume.is_finite() || volume < 1e-6 {
+            return None;
+        }
+        Some(Self {
+            lattice,
+            inverse: lattice.inverse(),
+        })
+    }
+
+    /// The cell with the given lattice vector lengths (in angstroms) and the
+    /// angles between them (in degrees, with alpha between b and c, beta
+    /// between a and c, and gamma between a and b), in the usual orientation:
+    /// a along x, and b in the xy plane.
+    pub fn from_parameters(lengths: Vec3, angles: Vec3) -> Option<Self> {
+        let (cos_alpha, cos_beta) = (angles.x.to_radians().cos(), angles.y.to_radians().cos());
+        let (sin_gamma, cos_gamma) = angles.z.to_radians().sin_cos();
+        let a = Vec3::X * lengths.x;
+        let b = Vec3::new(cos_gamma, sin_gamma, 0.0) * lengths.y;
+        let cx = cos_beta;
+        let cy = (cos_alpha - cos_beta * cos_gamma) / sin_gamma;
+        let cz = (1.0 - cx * cx - cy * cy).max(0.0).sqrt();
+        let c = Vec3::new(cx, cy, cz) * lengths.z;
+        Self::new(Mat3::from_cols(a, b, c))
+    }
+
+    /// This cell with its lattice vector lengths and angles changed, keeping
+    /// its orientation: a stays along the same direction, and b in the same
+    /// plane on the same side of a.  Left-handed lattices stay left-handed.
+    pub fn with_parameters(&self, lengths: Vec3, angles: Vec3) -> Option<Self> {
+        let standard = Self::from_parameters(lengths, angles)?;
+        let (a, b) = (self.lattice.x_axis, self.lattice.y_axis);
+        let x = a.normalize();
+        let y = (b - x * x.dot(b)).normalize();
+        let z = x.cross(y) * self.lattice.determinant().signum();
+        Self::new(Mat3::from_cols(x, y, z) * standard.lattice)
+    }
+
+    /// A cube with sides of `length` angstroms.
+    pub fn cubic(length: f32) -> Option<Self> {
+        Self::new(Mat3::from_diagonal(Vec3::splat(length)))
+    }
+
+    /// The lattice vectors, as the columns of a matrix.
+    pub fn lattice(&self) -> Mat3 {
+        self.lattice
+    }
+
+    /// The lengths of the lattice vectors.
+    pub fn lengths(&self) -> Vec3 {
+        Vec3::new(
+            self.lattice.x_axis.length(),
+            self.lattice.y_axis.length(),
+            self.lattice.z_axis.length(),
+        )
+    }
+
+    /// The angles alpha, beta and gamma between the lattice vectors, in
+    /// degrees.
+    pub fn angles(&self) -> Vec3 {
+        let (a, b, c) = (
+            self.lattice.x_axis,
+            self.lattice.y_axis,
+            self.lattice.z_axis,
+        );
+        Vec3::new(b.angle_between(c), a.angle_between(c), a.angle_between(b)) * 180.0
+            / std::f32::consts::PI
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.lattice.determinant().abs()
+    }
+
+    /// The distance between each pair of opposite faces of the cell.  Spheres
+    /// up to half the smallest of these across fit inside the cell.
+    pub fn widths(&self) -> Vec3 {
+        let (a, b, c) = (
+            self.lattice.x_axis,
+            self.lattice.y_axis,
+            self.lattice.z_axis,
+        );
+        let volume = self.volume();
+        Vec3::new(
+            volume / b.cross(c).length(),
+            volume / a.cross(c).length(),
+            volume / a.cross(b).length(),
+        )
+    }
+
+    /// Whether the cell is at least twice `range` wide in every direction, so
+    /// that no particle is within `range` of two copies of another.  The
+    /// minimum image convention misses interactions in narrower cells.
+    pub fn is_wide_enough(&self, range: f32) -> bool {
+        self.widths().min_element() >= 2.0 * range
+    }
+
+    pub fn to_fractional(&self, position: Vec3) -> Vec3 {
+        self.inverse * position
+    }
+
+    pub fn to_cartesian(&self, fractional: Vec3) -> Vec3 {
+        self.lattice * fractional
+    }
+
+    /// The copy of `position` inside the cell.
+    pub fn wrap(&self, position: Vec3) -> Vec3 {
+        let fractional = self.to_fractional(position);
+        self.to_cartesian(fractional - fractional.floor())
+    }
+
+    /// The shortest of the offsets between copies of two particles `offset`
+    /// apart.  The copy is found in fractional coordinates, which only
+    /// guarantees it is the nearest when it is less than half the smallest
+    /// width of the cell away.
+    pub fn minimum_image(&self, offset: Vec3) -> Vec3 {
+        let fractional = self.to_fractional(offset);
+        self.to_cartesian(fractional - fractional.round())
+    }
+
+    /// The twelve edges of the cell, as pairs of corners.
+    pub fn edges(&self) -> [(Vec3, Vec3); 12] {
+        let (a, b, c) = (
+            self.lattice.x_axis,
+            self.lattice.y_axis,
+            self.lattice.z_axis,
+        );
+        let mut edges = [(Vec3::ZERO, Vec3::ZERO); 12];
+        for (index, (along, across)) in [(a, [b, c]), (b, [a, c]), (c, [a, b])]
+            .into_iter()
+            .enumerate()
+        {
+            for (corner, start) in [Vec3::ZERO, across[0], across[1], across[0] + across[1]]
+                .into_iter()
+                .enumerate()
+            {
+                edges[4 * index + corner] = (start, start + along);
+            }
+        }
+        edges
+    }
+}
+
+impl Molecule {
+    /// The offset from particle `a` to particle `b`, or to the nearest copy of
+    /// `b` if the molecule has a unit cell.
+    pub fn offset(&self, a: NodeIndex, b: NodeIndex) -> Vec3 {
+        let offset = self.graph[b].pos - self.graph[a].pos;
+        self.cell.map_or(offset, |cell| cell.minimum_image(offset))
+    }
+
+    /// Moves every atom that has left the molecule's unit cell back into it,
+    /// taking its bonding sites along.  Does nothing if the molecule has no
+    /// cell.
+    pub fn wrap_into_cell(&mut self) {
+        let Some(cell) = self.cell else {
+            return;
+        };
+        let atoms: Vec<NodeIndex> = self.atoms().map(|(node, _)| node).collect();
+        for atom in atoms {
+            let pos = self.graph[atom].pos;
+            let shift = cell.wrap(pos) - pos;
+            if shift == Vec3::ZERO {
+                continue;
+            }
+            self.graph[atom].pos += shift;
+            let sites: Vec<NodeIndex> = self.bonding_sites(atom).collect();
+            for site in sites {
+                self.graph[site].pos += shift;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vsepr;
+    use periodic_table::Element;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    #[test]
+    fn parameters() {
+        let cell =
+            UnitCell::from_parameters(Vec3::new(5.0, 6.0, 7.0), Vec3::new(80.0, 95.0, 110.0))
+                .unwrap();
+        assert!(close(cell.lengths(), Vec3::new(5.0, 6.0, 7.0)));
+        assert!(cell.angles().distance(Vec3::new(80.0, 95.0, 110.0)) < 1e-3);
+        assert!(close(cell.lattice().x_axis, Vec3::new(5.0, 0.0, 0.0)));
+
+        assert!(UnitCell::cubic(0.0).is_none());
+        assert!(UnitCell::new(Mat3::from_cols(Vec3::X, Vec3::Y, Vec3::X)).is_none());
+        assert_eq!(UnitCell::cubic(3.0).unwrap().widths(), Vec3::splat(3.0));
+    }
+
+    #[test]
+    fn reparameterize() {
+        let lengths = Vec3::new(5.0, 6.0, 7.0);
+        let angles = Vec3::new(80.0, 95.0, 110.0);
+        let standard = UnitCell::from_parameters(lengths, angles).unwrap();
+        let rotation = Mat3::from_axis_angle(Vec3::new(1.0, -2.0, 0.5).normalize(), 1.2);
+        let mirror = Mat3::from_diagonal(Vec3::new(1.0, 1.0, -1.0));
+        for turn in [rotation, rotation * mirror] {
+            let cell = UnitCell::new(turn * standard.lattice()).unwrap();
+
+            // Changing nothing leaves the cell as it was
+            let same = cell.with_parameters(lengths, angles).unwrap();
+            assert!(close(same.lattice().x_axis, cell.lattice().x_axis));
+            assert!(close(same.lattice().y_axis, cell.lattice().y_axis));
+            assert!(close(same.lattice().z_axis, cell.lattice().z_axis));
+
+            // A changed cell has the new shape, turned the same way
+            let (new_lengths, new_angles) =
+                (Vec3::new(8.0, 6.0, 9.0), Vec3::new(90.0, 100.0, 120.0));
+            let changed = cell.with_parameters(new_lengths, new_angles).unwrap();
+            assert!(close(changed.lengths(), new_lengths));
+            assert!(changed.angles().distance(new_angles) < 1e-3);
+            let expected = turn
+                * UnitCell::from_parameters(new_lengths, new_angles)
+                    .unwrap()
+                    .lattice();
+            assert!(close(changed.lattice().x_axis, expected.x_axis));
+            assert!(close(changed.lattice().y_axis, expected.y_axis));
+            assert!(close(changed.lattice().z_axis, expected.z_axis));
+        }
+    }
+
+    #[test]
+    fn wide_enough() {
+        // A sheared cell is narrower than its sides are long
+        let cell =
+            UnitCell::from_parameters(Vec3::splat(10.0), Vec3::new(90.0, 90.0, 60.0)).unwrap();
+        assert!(cell.is_wide_enough(4.0));
+        assert!(!cell.is_wide_enough(4.5));
+        assert!(UnitCell::cubic(10.0).unwrap().is_wide_enough(5.0));
+    }
+
+    #[test]
+    fn wrap_and_minimum_image() {
+        let cell =
+            UnitCell::from_parameters(Vec3::splat(10.0), Vec3::new(90.0, 90.0, 120.0)).unwrap();
+        let b = cell.lattice().y_axis;
+
+        // Particles outside the cell come back in by whole lattice vectors
+        let wrapped = cell.wrap(Vec3::new(-1.0, 0.5, 23.0));
+        let fractional = cell.to_fractional(wrapped);
+        assert!(fractional.cmpge(Vec3::ZERO).all() && fractional.cmplt(Vec3::ONE).all());
+        assert!(close(wrapped, Vec3::new(9.0, 0.5, 3.0)));
+
+        // Particles just either side of a face of the cell are close together
+        let offset = cell.minimum_image(b * 0.98 - b * 0.01);
+        assert!(close(offset, -b * 0.03));
+        assert!(close(
+            cell.minimum_image(Vec3::new(1.0, 2.0, 3.0)),
+            Vec3::new(1.0, 2.0, 3.0)
+        ));
+    }
+
+    #[test]
+    fn edges() {
+        let cell = UnitCell::cubic(2.0).unwrap();
+        let edges = cell.edges();
+        assert!(edges
+            .iter()
+            .all(|(a, b)| (a.distance(*b) - 2.0).abs() < 1e-6));
+        // Each corner is the end of three edges
+        let corner = Vec3::splat(2.0);
+        let count = edges
+            .iter()
+            .filter(|(a, b)| *a == corner || *b == corner)
+            .count();
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn wrap_into_cell() {
+        let mut molecule = Molecule::new();
+        let carbon = molecule.add_atom(
+            Element::Carbon,
+            Vec3::new(11.0, -0.5, 5.0),
+            Vec3::Z,
+            None,
+            &vsepr::bond_directions(Element::Carbon, &[]),
+            false,
+        );
+        let site = molecule.bonding_sites(carbon).next().unwrap();
+        let bond = molecule.graph[site].pos - molecule.graph[carbon].pos;
+
+        // Without a cell nothing moves
+        molecule.wrap_into_cell();
+        assert_eq!(molecule.graph[carbon].pos, Vec3::new(11.0, -0.5, 5.0));
+
+        molecule.cell = UnitCell::cubic(10.0);
+        molecule.wrap_into_cell();
+        assert!(close(molecule.graph[carbon].pos, Vec3::new(1.0, 9.5, 5.0)));
+        // The bonding site moved along with its atom
+        assert!(close(
+            molecule.graph[site].pos - molecule.graph[carbon].pos,
+            bond
+        ));
+    }
+
+    #[test]
+    fn unbonding_across_a_face() {
+        // Ethane, straddling a face of the cell, which wrapping splits
+        let periodic_ethane = || {
+            let mut molecule = Molecule::new();
+            let carbon = molecule.add_atom(
+                Element::Carbon,
+                Vec3::new(-0.5, 5.0, 5.0),
+                Vec3::X,
+                None,
+                &vsepr::bond_directions(Element::Carbon, &[]),
+                false,
+            );
+            // The site pointing along +x, out of the cell
+            let site = molecule
+                .bonding_sites(carbon)
+                .find(|&site| molecule.graph[site].pos.x > 0.0)
+                .unwrap();
+            let other = molecule.bond_atom_at_site(site, Element::Carbon).unwrap();
+            molecule.cell = UnitCell::cubic(10.0);
+            molecule.wrap_into_cell();
+            let offset = molecule.offset(carbon, other);
+            assert!(
+                molecule.graph[other]
+                    .pos
+                    .distance(molecule.graph[carbon].pos)
+                    > 5.0
+            );
+            (molecule, carbon, other, offset)
+        };
+        // The bonding site left in place of the bond points towards the
+        // nearest copy of the other carbon, not across the cell
+        let points_along = |molecule: &Molecule, atom: NodeIndex, offset: Vec3| {
+            molecule.bonding_sites(atom).any(|site| {
+                close(
+                    molecule.offset(atom, site),
+                    offset.normalize() * molecule.offset(atom, site).length(),
+                )
+            })
+        };
+
+        let (mut molecule, carbon, other, offset) = periodic_ethane();
+        molecule.remove_bond(carbon, other);
+        assert!(points_along(&molecule, carbon, offset));
+        assert!(points_along(&molecule, other, -offset));
+
+        let (mut molecule, carbon, other, offset) = periodic_ethane();
+        molecule.remove_atom(other);
+        assert!(points_along(&molecule, carbon, offset));
+    }
+}
+
+// End of File
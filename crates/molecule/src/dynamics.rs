@@ -0,0 +1,423 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Molecular dynamics: integrating Newton's equations of motion for the
+//! particles of a molecule with the velocity Verlet method, optionally coupled
+//! to a heat bath by a thermostat.
+//!
+//! Times are in femtoseconds, so velocities (kept in `MolNode::vel`) are in
+//! angstroms per femtosecond.  Particles weigh their atom's mass; bonding
+//! sites weigh as much as the hydrogen atoms they stand in for.
+
+use crate::forcefield::{Energy, ForceField, Potential, Topology};
+use crate::{Molecule, Particle};
+use glam::Vec3;
+use periodic_table::Element;
+use petgraph::stable_graph::NodeIndex;
+
+/// Boltzmann's constant, in kcal/(mol K).
+pub const BOLTZMANN: f32 = 0.001_987_204_1;
+
+// The acceleration, in angstroms per femtosecond squared, of one dalton under
+// a force of one kcal/mol/angstrom.
+const ACCELERATION: f32 = 4.184e-4;
+
+/// How the molecule exchanges heat with its surroundings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Thermostat {
+    /// None: the total energy is conserved.
+    None,
+    /// Rescales the velocities each step so that the temperature relaxes
+    /// towards the target over `coupling_time` femtoseconds.
+    Berendsen { coupling_time: f32 },
+    /// Applies friction and random kicks to every particle, with `friction`
+    /// in inverse femtoseconds.
+    Langevin { friction: f32 },
+}
+
+/// The settings of a molecular dynamics run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DynamicsOptions {
+    /// In femtoseconds.
+    pub timestep: f32,
+    /// The temperature of the thermostat, and that velocities are drawn at by
+    /// `Dynamics::thermalize`, in kelvin.
+    pub temperature: f32,
+    pub thermostat: Thermostat,
+}
+
+impl Default for DynamicsOptions {
+    fn default() -> Self {
+        Self {
+            timestep: 0.5,
+            temperature: 300.0,
+            thermostat: Thermostat::Berendsen {
+                coupling_time: 100.0,
+            },
+        }
+    }
+}
+
+/// The energy of a molecule during a dynamics run, in kcal/mol.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DynamicsReport {
+    pub kinetic: f32,
+    pub potential: Energy,
+    /// The instantaneous temperature, in kelvin.
+    pub temperature: f32,
+}
+
+impl DynamicsReport {
+    pub fn total(&self) -> f32 {
+        self.kinetic + self.potential.total()
+    }
+}
+
+// A small, fast pseudo-random number generator (SplitMix64), so that runs can
+// be repeated from a seed.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in (0, 1]
+    fn uniform(&mut self) -> f32 {
+        ((self.next() >> 40) as f32 + 1.0) / (1u64 << 24) as f32
+    }
+
+    // A standard normal deviate, by the Box-Muller transform
+    fn normal(&mut self) -> f32 {
+        let (u, v) = (self.uniform(), self.uniform());
+        (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos()
+    }
+
+    fn normal_vec3(&mut self) -> Vec3 {
+        Vec3::new(self.normal(), self.normal(), self.normal())
+    }
+}
+
+/// A molecular dynamics run on a copy of a molecule's particles.  The copy is
+/// written back with `write_to`; if the molecule is edited in the meantime
+/// (which `follows` tells), the run has to be started again.
+pub struct Dynamics {
+    pub options: DynamicsOptions,
+    topology: Topology,
+    potential: Box<dyn Potential>,
+    masses: Vec<f32>,
+    positions: Vec<Vec3>,
+    velocities: Vec<Vec3>,
+    forces: Vec<Vec3>,
+    energy: Energy,
+    random: Random,
+}
+
+// The mass of the particle at `node`
+fn mass(molecule: &Molecule, node: NodeIndex) -> f32 {
+    match &molecule.graph[node].particle {
+        Particle::Atom(atom) => atom.mass(),
+        Particle::BondingSite => Element::Hydrogen.atomic_mass(),
+    }
+}
+
+impl Dynamics {
+    /// Starts a run from the positions and velocities of `molecule`.  `seed`
+    /// seeds the random numbers used by the Langevin thermostat and by
+    /// `thermalize`.
+    pub fn new(
+        molecule: &Molecule,
+        force_field: &dyn ForceField,
+        options: DynamicsOptions,
+        seed: u64,
+    ) -> Self {
+        let topology = Topology::new(molecule);
+        let potential = force_field.parameterize(&topology);
+        let masses = topology
+            .nodes
+            .iter()
+            .map(|&node| mass(molecule, node))
+            .collect();
+        let positions = topology.positions(molecule);
+        let velocities = topology
+            .nodes
+            .iter()
+            .map(|&node| molecule.graph[node].vel)
+            .collect();
+        let mut forces = vec![Vec3::ZERO; positions.len()];
+        let energy = potential.evaluate(&positions, &mut forces);
+
+        Dynamics {
+            options,
+            topology,
+            potential,
+            masses,
+            positions,
+            velocities,
+            forces,
+            energy,
+            random: Random(seed),
+        }
+    }
+
+    /// Copies the positions and velocities of the particles back into
+    /// `molecule`.
+    pub fn write_to(&self, molecule: &mut Molecule) {
+        self.topology.set_positions(molecule, &self.positions);
+        for (&node, &velocity) in self.topology.nodes.iter().zip(&self.velocities) {
+            molecule.graph[node].vel = velocity;
+        }
+    }
+
+    /// Whether `molecule` is as the run last left it: with the same particles,
+    /// bonds and unit cell as when the run started, and every particle where
+    /// `write_to` put it.  If not, the molecule has been edited since.
+    pub fn follows(&self, molecule: &Molecule) -> bool {
+        self.topology.describes(molecule)
+            && self
+                .topology
+                .nodes
+                .iter()
+                .zip(self.positions.iter().zip(&self.masses))
+                .all(|(&node, (&position, &particle_mass))| {
+                    molecule.graph[node].pos == position && mass(molecule, node) == particle_mass
+                })
+    }
+
+    fn kinetic_energy(&self) -> f32 {
+        self.masses
+            .iter()
+            .zip(&self.velocities)
+            .map(|(mass, velocity)| 0.5 * mass * velocity.length_squared())
+            .sum::<f32>()
+            / ACCELERATION
+    }
+
+    fn temperature_of(&self, kinetic_energy: f32) -> f32 {
+        if self.masses.is_empty() {
+            return 0.0;
+        }
+        2.0 * kinetic_energy / (3.0 * self.masses.len() as f32 * BOLTZMANN)
+    }
+
+    /// The current energy and temperature.
+    pub fn report(&self) -> DynamicsReport {
+        let kinetic = self.kinetic_energy();
+        DynamicsReport {
+            kinetic,
+            potential: self.energy,
+            temperature: self.temperature_of(kinetic),
+        }
+    }
+
+    /// Draws new velocities from the Maxwell-Boltzmann distribution at the
+    /// target temperature, without any overall motion of the molecule, and
+    /// scales them to exactly that temperature.
+    pub fn thermalize(&mut self) {
+        for (velocity, mass) in self.velocities.iter_mut().zip(&self.masses) {
+            *velocity = self.random.normal_vec3()
+                * (BOLTZMANN * self.options.temperature * ACCELERATION / mass).sqrt();
+        }
+
+        let total_mass: f32 = self.masses.iter().sum();
+        if total_mass > 0.0 {
+            let momentum: Vec3 = self
+                .velocities
+                .iter()
+                .zip(&self.masses)
+                .map(|(velocity, mass)| *velocity * *mass)
+                .sum();
+            for velocity in self.velocities.iter_mut() {
+                *velocity -= momentum / total_mass;
+            }
+        }
+
+        let temperature = self.temperature_of(self.kinetic_energy());
+        if temperature > 0.0 {
+            let scale = (self.options.temperature / temperature).sqrt();
+            for velocity in self.velocities.iter_mut() {
+                *velocity *= scale;
+            }
+        }
+    }
+
+    /// Advances the run by one timestep.
+    pub fn step(&mut self) -> DynamicsReport {
+        let dt = self.options.timestep;
+
+        for ((velocity, position), (force, mass)) in self
+            .velocities
+            .iter_mut()
+            .zip(self.positions.iter_mut())
+            .zip(self.forces.iter().zip(&self.masses))
+        {
+            *velocity += *force * (0.5 * dt * ACCELERATION / mass);
+            *position += *velocity * dt;
+        }
+        // Particles leaving a periodic cell come back in at the other side
+        self.topology.wrap(&mut self.positions);
+
+        self.forces.fill(Vec3::ZERO);
+        self.energy = self.potential.evaluate(&self.positions, &mut self.forces);
+
+        for ((velocity, force), mass) in self
+            .velocities
+            .iter_mut()
+            .zip(&self.forces)
+            .zip(&self.masses)
+        {
+            *velocity += *force * (0.5 * dt * ACCELERATION / mass);
+        }
+
+        match self.options.thermostat {
+            Thermostat::None => {}
+            Thermostat::Berendsen { coupling_time } => {
+                let temperature = self.temperature_of(self.kinetic_energy());
+                if temperature > 0.0 {
+                    let ratio = self.options.temperature / temperature - 1.0;
+                    // Limit the rescaling, so that a hot start is not quenched
+                    // all at once
+                    let scale = (1.0 + dt / coupling_time * ratio)
+                        .max(0.0)
+                        .sqrt()
+                        .clamp(0.8, 1.25);
+                    for velocity in self.velocities.iter_mut() {
+                        *velocity *= scale;
+                    }
+                }
+            }
+            Thermostat::Langevin { friction } => {
+                // The exact solution of the Ornstein-Uhlenbeck process over
+                // one timestep
+                let damping = (-friction * dt).exp();
+                let noise = (1.0 - damping * damping).sqrt();
+                let thermal = BOLTZMANN * self.options.temperature * ACCELERATION;
+                for (velocity, mass) in self.velocities.iter_mut().zip(&self.masses) {
+                    *velocity = *velocity * damping
+                        + self.random.normal_vec3() * (noise * (thermal / mass).sqrt());
+                }
+            }
+        }
+
+        self.report()
+    }
+
+    /// Advances the run by `steps` timesteps.
+    pub fn run(&mut self, steps: usize) -> DynamicsReport {
+        for _ in 0..steps {
+            self.step();
+        }
+        self.report()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::forcefield::uff::Uff;
+    use crate::minimize::{minimize, MinimizeOptions};
+
+    // Ethane, minimized so that the run starts near equilibrium
+    fn ethane() -> Molecule {
+        let (mut molecule, _) = fixtures::chain(&[Element::Carbon, Element::Carbon]);
+        minimize(&mut molecule, &Uff::default(), &MinimizeOptions::default());
+        molecule
+    }
+
+    fn average_temperature(dynamics: &mut Dynamics, steps: usize) -> f32 {
+        (0..steps).map(|_| dynamics.step().temperature).sum::<f32>() / steps as f32
+    }
+
+    #[test]
+    fn thermalize() {
+        let molecule = ethane();
+        let mut dynamics = Dynamics::new(&molecule, &Uff::default(), DynamicsOptions::default(), 1);
+        assert_eq!(dynamics.report().kinetic, 0.0);
+        dynamics.thermalize();
+        let report = dynamics.report();
+        assert!((report.temperature - 300.0).abs() < 0.1, "{:?}", report);
+
+        let momentum: Vec3 = dynamics
+            .velocities
+            .iter()
+            .zip(&dynamics.masses)
+            .map(|(velocity, mass)| *velocity * *mass)
+            .sum();
+        assert!(momentum.length() < 1e-4);
+    }
+
+    #[test]
+    fn conserves_energy() {
+        let molecule = ethane();
+        let options = DynamicsOptions {
+            timestep: 0.25,
+            thermostat: Thermostat::None,
+            ..DynamicsOptions::default()
+        };
+        let mut dynamics = Dynamics::new(&molecule, &Uff::default(), options, 2);
+        dynamics.thermalize();
+        let start = dynamics.report();
+
+        let mut drift: f32 = 0.0;
+        for _ in 0..2000 {
+            let report = dynamics.step();
+            drift = drift.max((report.total() - start.total()).abs());
+        }
+        // The energy sloshes between kinetic and potential, but the total
+        // stays put
+        assert!(drift < 0.05 * start.kinetic, "{} of {:?}", drift, start);
+
+        // The run is written back into the molecule
+        let mut moved = molecule.clone();
+        dynamics.write_to(&mut moved);
+        let node = dynamics.topology.nodes[0];
+        assert_eq!(moved.graph[node].pos, dynamics.positions[0]);
+        assert_eq!(moved.graph[node].vel, dynamics.velocities[0]);
+        assert!(dynamics.follows(&moved));
+        assert!(!dynamics.follows(&molecule));
+
+        // Until the molecule is edited
+        moved.move_atom(node, Vec3::ZERO);
+        assert!(!dynamics.follows(&moved));
+    }
+
+    #[test]
+    fn berendsen_cools_to_the_target() {
+        let molecule = ethane();
+        let options = DynamicsOptions {
+            temperature: 600.0,
+            thermostat: Thermostat::Berendsen {
+                coupling_time: 20.0,
+            },
+            ..DynamicsOptions::default()
+        };
+        let mut dynamics = Dynamics::new(&molecule, &Uff::default(), options, 3);
+        dynamics.thermalize();
+        dynamics.options.temperature = 200.0;
+        dynamics.run(2000);
+        let temperature = average_temperature(&mut dynamics, 2000);
+        assert!((temperature - 200.0).abs() < 30.0, "{}", temperature);
+    }
+
+    #[test]
+    fn langevin_heats_to_the_target() {
+        let molecule = ethane();
+        let options = DynamicsOptions {
+            temperature: 300.0,
+            thermostat: Thermostat::Langevin { friction: 0.01 },
+            ..DynamicsOptions::default()
+        };
+        let mut dynamics = Dynamics::new(&molecule, &Uff::default(), options, 4);
+        dynamics.run(2000);
+        let temperature = average_temperature(&mut dynamics, 10000);
+        assert!((temperature - 300.0).abs() < 45.0, "{}", temperature);
+    }
+}
+
+// End of File
@@ -0,0 +1,949 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Undoable edits of a molecule.  Every change the user makes to a molecule is
+//! described by an `Edit` and performed through a `History`, which records it
+//! so that it can be undone and redone.
+//!
+//! The history records the nodes and bonds each edit added, removed or
+//! changed, as they were before and after the edit, and undoes or redoes the
+//! edit by putting just those back.  The rest of the molecule keeps whatever
+//! motion (from relaxing or dynamics) it has had since.  Nodes are put back
+//! at their old indices, rather than by applying an inverse edit: the
+//! molecule graph reuses the indices of removed nodes, so an inverse edit
+//! could not guarantee that re-added nodes get their old indices back, and
+//! the edits further along the history would then refer to the wrong nodes.
+
+use crate::cell::UnitCell;
+use crate::{BondOrder, MolGraph, MolNode, Molecule, Particle};
+use glam::Vec3;
+use periodic_table::Element;
+use petgraph::stable_graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::{btree_map::Entry, BTreeMap, HashSet, VecDeque};
+use std::fmt;
+
+/// A single change to a molecule.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit {
+    /// Replaces a bonding site with a new atom, as
+    /// `Molecule::bond_atom_at_site` does.
+    AddAtom { site: NodeIndex, element: Element },
+    /// Removes an atom along with its bonding sites, as
+    /// `Molecule::remove_atom` does.
+    DeleteAtom { atom: NodeIndex },
+    /// Removes the bond between two atoms, as `Molecule::remove_bond` does.
+    DeleteBond { a: NodeIndex, b: NodeIndex },
+    /// Moves an atom, and its bonding sites, to a new position.
+    MoveAtom { atom: NodeIndex, position: Vec3 },
+    /// Changes the order of the bond between two atoms, as
+    /// `Molecule::set_bond_order` does.
+    SetBondOrder {
+        a: NodeIndex,
+        b: NodeIndex,
+        order: BondOrder,
+    },
+    /// Gives the molecule a new unit cell, or makes it no longer periodic,
+    /// moving every particle by `offset` to where it belongs in the new cell.
+    SetCell {
+        cell: Option<UnitCell>,
+        offset: Vec3,
+    },
+    /// Several edits which are undone and redone together, applied in order.
+    /// An edit in the batch must not refer to a node removed by an earlier
+    /// one, as the node's index may have been reused.
+    Batch(Vec<Edit>),
+}
+
+/// The reason an edit could not be applied.  A failed edit leaves the molecule
+/// unchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditError {
+    NotAnAtom(NodeIndex),
+    NotABondingSite(NodeIndex),
+    NotBonded(NodeIndex, NodeIndex),
+    InvalidBondOrder(BondOrder),
+    /// The atom has no bonding site left to raise the order of a bond with.
+    NoFreeBondingSite(NodeIndex),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::NotAnAtom(node) => write!(f, "node {} is not an atom", node.index()),
+            EditError::NotABondingSite(node) => {
+                write!(f, "node {} is not a bonding site", node.index())
+            }
+            EditError::NotBonded(a, b) => {
+                write!(f, "atoms {} and {} are not bonded", a.index(), b.index())
+            }
+            EditError::InvalidBondOrder(order) => write!(f, "invalid bond order {}", order),
+            EditError::NoFreeBondingSite(node) => {
+                write!(f, "atom {} has no free bonding site", node.index())
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+impl Edit {
+    /// Applies the edit to a molecule, without recording it.
+    pub fn apply(&self, molecule: &mut Molecule) -> Result<(), EditError> {
+        match self {
+            Edit::AddAtom { site, element } => molecule
+                .bond_atom_at_site(*site, *element)
+                .map(|_| ())
+                .ok_or(EditError::NotABondingSite(*site)),
+            Edit::DeleteAtom { atom } => molecule
+                .remove_atom(*atom)
+                .map(|_| ())
+                .ok_or(EditError::NotAnAtom(*atom)),
+            Edit::DeleteBond { a, b } => molecule
+                .remove_bond(*a, *b)
+                .map(|_| ())
+                .ok_or(EditError::NotBonded(*a, *b)),
+            Edit::MoveAtom { atom, position } => molecule
+                .move_atom(*atom, *position)
+                .then_some(())
+                .ok_or(EditError::NotAnAtom(*atom)),
+            Edit::SetBondOrder { a, b, order } => {
+                if molecule.set_bond_order(*a, *b, *order).is_some() {
+                    return Ok(());
+                }
+                // Work out why the order could not be set
+                if !(1..=3).contains(order) {
+                    return Err(EditError::InvalidBondOrder(*order));
+                }
+                for node in [*a, *b] {
+                    match molecule.graph.node_weight(node) {
+                        Some(weight) if matches!(weight.particle, Particle::Atom(_)) => {}
+                        _ => return Err(EditError::NotAnAtom(node)),
+                    }
+                }
+                let edge = molecule
+                    .graph
+                    .find_edge(*a, *b)
+                    .ok_or(EditError::NotBonded(*a, *b))?;
+                let needed = order.saturating_sub(molecule.graph[edge]) as usize;
+                Err([*a, *b]
+                    .into_iter()
+                    .find(|&node| molecule.bonding_sites(node).count() < needed)
+                    .map_or(EditError::NotBonded(*a, *b), EditError::NoFreeBondingSite))
+            }
+            Edit::SetCell { cell, offset } => {
+                for node in molecule.graph.node_weights_mut() {
+                    node.pos += *offset;
+                }
+                molecule.cell = *cell;
+                Ok(())
+            }
+            // The batch is recorded as it goes, so that it can be undone if
+            // an edit part way through fails
+            Edit::Batch(_) => Change::record(self, molecule).map(|_| ()),
+        }
+    }
+
+    // The nodes whose bonds or contents the edit may change, other than the
+    // nodes it adds.  Removing an atom also clears the `facing` of the atoms
+    // facing it.
+    fn touched(&self, molecule: &Molecule) -> Vec<NodeIndex> {
+        let named = match self {
+            Edit::AddAtom { site: node, .. } | Edit::MoveAtom { atom: node, .. } => vec![*node],
+            Edit::DeleteAtom { atom } => molecule
+                .graph
+                .node_indices()
+                .filter(|&node| match &molecule.graph[node].particle {
+                    Particle::Atom(other) => other.facing == Some(*atom),
+                    Particle::BondingSite => false,
+                })
+                .chain([*atom])
+                .collect(),
+            Edit::DeleteBond { a, b } | Edit::SetBondOrder { a, b, .. } => vec![*a, *b],
+            Edit::SetCell { .. } => return molecule.graph.node_indices().collect(),
+            Edit::Batch(_) => unreachable!("batches are recorded edit by edit"),
+        };
+        // An edited atom's bonding sites may be replaced, and the atom at the
+        // other end of each of its bonds may be given new ones
+        let mut touched: Vec<_> = named
+            .iter()
+            .filter(|&&node| molecule.graph.contains_node(node))
+            .flat_map(|&node| molecule.graph.neighbors(node))
+            .chain(named.iter().copied())
+            .collect();
+        touched.sort();
+        touched.dedup();
+        touched
+    }
+}
+
+/// How many edits a `History` keeps by default.
+pub const DEFAULT_HISTORY_LIMIT: usize = 1000;
+
+type Bonds = BTreeMap<(NodeIndex, NodeIndex), BondOrder>;
+
+// The bonds of the given nodes, keyed by their ends, lower index first
+fn bonds(graph: &MolGraph, nodes: impl IntoIterator<Item = NodeIndex>) -> Bonds {
+    nodes
+        .into_iter()
+        .filter(|&node| graph.contains_node(node))
+        .flat_map(|node| graph.edges(node))
+        .map(|edge| {
+            let (a, b) = (edge.source(), edge.target());
+            ((a.min(b), a.max(b)), *edge.weight())
+        })
+        .collect()
+}
+
+// Adds a node at an index which is free.  The graph hands out the most
+// recently freed index first, or else a new one at the end, so placeholder
+// nodes are added until the index comes up, and then removed again.
+fn add_node_at(graph: &mut MolGraph, index: NodeIndex, weight: &MolNode) {
+    let mut placeholders = Vec::new();
+    loop {
+        let node = graph.add_node(weight.clone());
+        if node == index {
+            break;
+        }
+        placeholders.push(node);
+    }
+    for node in placeholders.into_iter().rev() {
+        graph.remove_node(node);
+    }
+}
+
+// The nodes and bonds that an edit added, removed or changed, each as it was
+// before and after the edit (`None` where it did not exist), and the unit cell
+// before and after.
+#[derive(Clone, Debug)]
+struct Change {
+    nodes: Vec<(NodeIndex, Option<MolNode>, Option<MolNode>)>,
+    bonds: Vec<BondChange>,
+    cells: (Option<UnitCell>, Option<UnitCell>),
+}
+
+type BondChange = ((NodeIndex, NodeIndex), Option<BondOrder>, Option<BondOrder>);
+
+impl Change {
+    // Applies an edit, and records what it changed.  Only the nodes the edit
+    // touches, their neighbours and the nodes it adds are compared, so that
+    // an edit takes time in proportion to its size rather than the
+    // molecule's.  A batch which fails part way through is undone.
+    fn record(edit: &Edit, molecule: &mut Molecule) -> Result<Self, EditError> {
+        if let Edit::Batch(edits) = edit {
+            let mut changes: Vec<Change> = Vec::with_capacity(edits.len());
+            for edit in edits {
+                match Change::record(edit, molecule) {
+                    Ok(change) => changes.push(change),
+                    Err(err) => {
+                        for change in changes.iter().rev() {
+                            change.apply(molecule, false);
+                        }
+                        return Err(err);
+                    }
+                }
+            }
+            return Ok(Change::merge(changes, molecule.cell));
+        }
+
+        let touched = edit.touched(molecule);
+        let graph = &molecule.graph;
+        let mut old_nodes: BTreeMap<NodeIndex, Option<MolNode>> = touched
+            .iter()
+            .filter(|&&node| graph.contains_node(node))
+            .flat_map(|&node| graph.neighbors(node))
+            .chain(touched.iter().copied())
+            .map(|node| (node, graph.node_weight(node).cloned()))
+            .collect();
+        let old_bonds = bonds(graph, touched.iter().copied());
+        let old_cell = molecule.cell;
+
+        edit.apply(molecule)?;
+
+        // Any other node next to a touched node is new, as are the nodes next
+        // to it in turn: the bonds of the untouched nodes are as they were
+        let graph = &molecule.graph;
+        let mut added = Vec::new();
+        let mut unvisited = touched.clone();
+        while let Some(node) = unvisited.pop() {
+            if !graph.contains_node(node) {
+                continue;
+            }
+            for neighbor in graph.neighbors(node) {
+                if let Entry::Vacant(entry) = old_nodes.entry(neighbor) {
+                    entry.insert(None);
+                    added.push(neighbor);
+                    unvisited.push(neighbor);
+                }
+            }
+        }
+        let new_bonds = bonds(graph, touched.into_iter().chain(added));
+
+        let nodes = old_nodes
+            .into_iter()
+            .map(|(node, old)| (node, old, graph.node_weight(node).cloned()))
+            .filter(|(_, old, new)| old != new)
+            .collect();
+        let pairs: Vec<_> = old_bonds
+            .keys()
+            .chain(
+                new_bonds
+                    .keys()
+                    .filter(|pair| !old_bonds.contains_key(pair)),
+            )
+            .copied()
+            .collect();
+        let bonds = pairs
+            .into_iter()
+            .map(|pair| {
+                (
+                    pair,
+                    old_bonds.get(&pair).copied(),
+                    new_bonds.get(&pair).copied(),
+                )
+            })
+            .filter(|(_, old, new)| old != new)
+            .collect();
+        Ok(Change {
+            nodes,
+            bonds,
+            cells: (old_cell, molecule.cell),
+        })
+    }
+
+    // Combines the changes made one after another into one, which takes each
+    // node and bond from how it was before the first change to how it was
+    // after the last.  `cell` is the unit cell after the changes.
+    fn merge(changes: Vec<Change>, cell: Option<UnitCell>) -> Self {
+        let old_cell = changes.first().map_or(cell, |change| change.cells.0);
+        let mut nodes = BTreeMap::new();
+        let mut bonds = BTreeMap::new();
+        for change in changes {
+            for (node, old, new) in change.nodes {
+                nodes.entry(node).or_insert((old, None)).1 = new;
+            }
+            for (pair, old, new) in change.bonds {
+                bonds.entry(pair).or_insert((old, None)).1 = new;
+            }
+        }
+        Change {
+            nodes: nodes
+                .into_iter()
+                .filter(|(_, (old, new))| old != new)
+                .map(|(node, (old, new))| (node, old, new))
+                .collect(),
+            bonds: bonds
+                .into_iter()
+                .filter(|(_, (old, new))| old != new)
+                .map(|(pair, (old, new))| (pair, old, new))
+                .collect(),
+            cells: (old_cell, cell),
+        }
+    }
+
+    // Puts the changed nodes, bonds and cell back as they were after the edit
+    // (`forward`) or before it.  Returns `false`, leaving the molecule alone,
+    // if the molecule does not have them as they were on the other side of
+    // the edit, or lacks a node that a bond of the edit is to.
+    fn apply(&self, molecule: &mut Molecule, forward: bool) -> bool {
+        // The states to go from and to
+        fn sides<S>(forward: bool, old: S, new: S) -> (S, S) {
+            if forward {
+                (old, new)
+            } else {
+                (new, old)
+            }
+        }
+        let graph = &mut molecule.graph;
+
+        let changed: HashSet<NodeIndex> = self.nodes.iter().map(|(node, _, _)| *node).collect();
+        let nodes_match = self.nodes.iter().all(|(node, old, new)| {
+            let (from, _) = sides(forward, old, new);
+            graph.contains_node(*node) == from.is_some()
+        });
+        let bonds_match = self.bonds.iter().all(|&((a, b), old, new)| {
+            let (from, _) = sides(forward, old, new);
+            let present = [a, b].iter().all(|&node| graph.contains_node(node));
+            let order = present
+                .then(|| graph.find_edge(a, b).map(|edge| graph[edge]))
+                .flatten();
+            order == from
+                && [a, b]
+                    .iter()
+                    .all(|&node| changed.contains(&node) || graph.contains_node(node))
+        });
+        let (from_cell, to_cell) = sides(forward, self.cells.0, self.cells.1);
+        if !nodes_match || !bonds_match || molecule.cell != from_cell {
+            return false;
+        }
+        molecule.cell = to_cell;
+
+        for &((a, b), _, _) in self.bonds.iter() {
+            if let Some(edge) = graph.find_edge(a, b) {
+                graph.remove_edge(edge);
+            }
+        }
+        for (node, old, new) in self.nodes.iter() {
+            match sides(forward, old, new) {
+                (_, None) => {
+                    graph.remove_node(*node);
+                }
+                (Some(_), Some(weight)) => graph[*node] = weight.clone(),
+                (None, Some(weight)) => add_node_at(graph, *node, weight),
+            }
+        }
+        for &((a, b), old, new) in self.bonds.iter() {
+            if let (_, Some(order)) = sides(forward, old, new) {
+                graph.add_edge(a, b, order);
+            }
+        }
+        true
+    }
+}
+
+/// The undo and redo stacks of a molecule's edits.  Each edit is recorded
+/// along with a `target`, which identifies the molecule it was applied to when
+/// one history covers several molecules; the application uses the molecule's
+/// entity.  It is up to the caller to pass the target's molecule back to
+/// `undo` and `redo`, which can be found with `undo_target` and `redo_target`.
+///
+/// Only the most recent edits are kept, up to a limit; the oldest are
+/// forgotten first.
+#[derive(Clone, Debug)]
+pub struct History<T = ()> {
+    undo: VecDeque<(T, Change)>,
+    redo: Vec<(T, Change)>,
+    limit: usize,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self::with_limit(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl<T> History<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A history which keeps no more than `limit` edits.
+    pub fn with_limit(limit: usize) -> Self {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Applies an edit to the target's molecule and records it.  Making a new
+    /// edit discards any edits which were undone.
+    pub fn perform(
+        &mut self,
+        target: T,
+        molecule: &mut Molecule,
+        edit: Edit,
+    ) -> Result<(), EditError> {
+        let change = Change::record(&edit, molecule)?;
+        self.undo.push_back((target, change));
+        if self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+        Ok(())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// The target of the edit which `undo` would undo.
+    pub fn undo_target(&self) -> Option<&T> {
+        self.undo.back().map(|(target, _)| target)
+    }
+
+    /// The target of the edit which `redo` would redo.
+    pub fn redo_target(&self) -> Option<&T> {
+        self.redo.last().map(|(target, _)| target)
+    }
+
+    /// Undoes the most recent edit, which must have been made to `molecule`.
+    /// Returns the edit's target, or `None` if there is nothing to undo.  If
+    /// the molecule no longer has what the edit made, the whole history is
+    /// forgotten, as it no longer applies.
+    pub fn undo(&mut self, molecule: &mut Molecule) -> Option<&T> {
+        let (target, change) = self.undo.pop_back()?;
+        if !change.apply(molecule, false) {
+            self.clear();
+            return None;
+        }
+        self.redo.push((target, change));
+        self.redo.last().map(|(target, _)| target)
+    }
+
+    /// Redoes the most recently undone edit, which must have been made to
+    /// `molecule`.  Returns the edit's target, or `None` if there is nothing
+    /// to redo.  If the molecule no longer has what the edit found, the
+    /// whole history is forgotten.
+    pub fn redo(&mut self, molecule: &mut Molecule) -> Option<&T> {
+        let (target, change) = self.redo.pop()?;
+        if !change.apply(molecule, true) {
+            self.clear();
+            return None;
+        }
+        self.undo.push_back((target, change));
+        self.undo.back().map(|(target, _)| target)
+    }
+
+    /// Forgets every edit, e.g. when the molecules they refer to are replaced.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use petgraph::visit::EdgeRef;
+
+    fn lone_carbon() -> (Molecule, NodeIndex) {
+        let (molecule, atoms) = fixtures::chain(&[Element::Carbon]);
+        (molecule, atoms[0])
+    }
+
+    // The molecule's nodes, in index order, for comparing molecules
+    fn summary(molecule: &Molecule) -> Vec<String> {
+        let graph = &molecule.graph;
+        graph
+            .node_indices()
+            .map(|node| {
+                let mut neighbors: Vec<_> = graph
+                    .edges(node)
+                    .map(|edge| (edge.target().index(), *edge.weight()))
+                    .collect();
+                neighbors.sort();
+                format!(
+                    "{} {:?} {:?} {:?}",
+                    node.index(),
+                    match &graph[node].particle {
+                        Particle::Atom(atom) => Some(atom.element),
+                        Particle::BondingSite => None,
+                    },
+                    graph[node].pos,
+                    neighbors
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let (mut molecule, carbon) = lone_carbon();
+        let mut history = History::new();
+        let mut states = vec![summary(&molecule)];
+
+        let site = molecule.bonding_sites(carbon).next().unwrap();
+        let edit = Edit::AddAtom {
+            site,
+            element: Element::Carbon,
+        };
+        history.perform((), &mut molecule, edit).unwrap();
+        states.push(summary(&molecule));
+        let (other, _) = molecule.bonds(carbon).next().unwrap();
+
+        let edit = Edit::SetBondOrder {
+            a: carbon,
+            b: other,
+            order: 2,
+        };
+        history.perform((), &mut molecule, edit).unwrap();
+        assert_eq!(molecule.bonds(carbon).next(), Some((other, 2)));
+        states.push(summary(&molecule));
+
+        let position = molecule.graph[other].pos + Vec3::X;
+        let edit = Edit::MoveAtom {
+            atom: other,
+            position,
+        };
+        history.perform((), &mut molecule, edit).unwrap();
+        assert_eq!(molecule.graph[other].pos, position);
+        states.push(summary(&molecule));
+
+        let edit = Edit::DeleteAtom { atom: other };
+        history.perform((), &mut molecule, edit).unwrap();
+        assert_eq!(molecule.atoms().count(), 1);
+        states.push(summary(&molecule));
+
+        // Undo everything, then redo everything, checking each state
+        for state in states.iter().rev().skip(1) {
+            assert!(history.undo(&mut molecule).is_some());
+            assert_eq!(&summary(&molecule), state);
+        }
+        assert!(!history.can_undo());
+        assert_eq!(history.undo(&mut molecule), None);
+
+        for state in states.iter().skip(1) {
+            assert!(history.redo(&mut molecule).is_some());
+            assert_eq!(&summary(&molecule), state);
+        }
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn undo_keeps_motion() {
+        let (mut molecule, carbon) = lone_carbon();
+        let mut history = History::new();
+        let site = molecule.bonding_sites(carbon).next().unwrap();
+        let edit = Edit::AddAtom {
+            site,
+            element: Element::Carbon,
+        };
+        history.perform((), &mut molecule, edit).unwrap();
+
+        // Everything drifts, as it would while relaxing
+        for node in molecule.graph.node_weights_mut() {
+            node.pos += Vec3::X;
+        }
+        let before = summary(&molecule);
+
+        // Only what the edit changed is put back
+        assert!(history.undo(&mut molecule).is_some());
+        assert_eq!(molecule.graph[carbon].pos, Vec3::X);
+        assert_eq!(molecule.bonding_sites(carbon).count(), 4);
+        assert!(molecule
+            .bonding_sites(carbon)
+            .filter(|&other| other != site)
+            .all(|other| molecule.graph[other].pos.x > 0.5));
+
+        assert!(history.redo(&mut molecule).is_some());
+        assert_eq!(summary(&molecule)[0], before[0]);
+        assert_eq!(molecule.graph.node_count(), before.len());
+
+        // An edit whose atoms have gone in the meantime can't be undone
+        let (other, _) = molecule.bonds(carbon).next().unwrap();
+        molecule.remove_atom(other);
+        assert_eq!(history.undo(&mut molecule), None);
+        assert!(!history.can_undo() && !history.can_redo());
+    }
+
+    #[test]
+    fn unit_cells() {
+        let (mut molecule, carbon) = lone_carbon();
+        let mut history = History::new();
+        let before = summary(&molecule);
+
+        let cell = UnitCell::cubic(20.0);
+        let edit = Edit::SetCell {
+            cell,
+            offset: Vec3::splat(10.0),
+        };
+        history.perform((), &mut molecule, edit).unwrap();
+        assert_eq!(molecule.cell, cell);
+        assert_eq!(molecule.graph[carbon].pos, Vec3::splat(10.0));
+        let periodic = summary(&molecule);
+
+        // The cell is undone and redone along with the move into it
+        assert!(history.undo(&mut molecule).is_some());
+        assert_eq!(molecule.cell, None);
+        assert_eq!(summary(&molecule), before);
+        assert!(history.redo(&mut molecule).is_some());
+        assert_eq!(molecule.cell, cell);
+        assert_eq!(summary(&molecule), periodic);
+    }
+
+    #[test]
+    fn limit() {
+        let (mut molecule, carbon) = lone_carbon();
+        let mut history = History::with_limit(3);
+        for step in 0..5 {
+            let edit = Edit::MoveAtom {
+                atom: carbon,
+                position: Vec3::X * step as f32,
+            };
+            history.perform((), &mut molecule, edit).unwrap();
+        }
+        // Only the last three moves can be undone
+        for _ in 0..3 {
+            assert!(history.undo(&mut molecule).is_some());
+        }
+        assert!(!history.can_undo());
+        assert_eq!(molecule.graph[carbon].pos, Vec3::X);
+    }
+
+    #[test]
+    fn delete_restores_bonding_sites() {
+        let (mut molecule, carbon) = lone_carbon();
+        let mut history = History::new();
+        let site = molecule.bonding_sites(carbon).next().unwrap();
+        let site_pos = molecule.graph[site].pos;
+
+        let edit = Edit::AddAtom {
+            site,
+            element: Element::Carbon,
+        };
+        history.perform((), &mut molecule, edit).unwrap();
+        let (other, _) = molecule.bonds(carbon).next().unwrap();
+        assert_eq!(molecule.bonding_sites(carbon).count(), 3);
+        assert_eq!(molecule.bonding_sites(other).count(), 3);
+
+        // Breaking the bond leaves each atom with a site where it was
+        let edit = Edit::DeleteBond {
+            a: carbon,
+            b: other,
+        };
+        history.perform((), &mut molecule, edit).unwrap();
+        assert_eq!(molecule.bonds(carbon).count(), 0);
+        for atom in [carbon, other] {
+            assert_eq!(molecule.bonding_sites(atom).count(), 4);
+        }
+        let toward = |molecule: &Molecule, atom: NodeIndex, target: Vec3| {
+            let position = molecule.graph[atom].pos;
+            let direction = (target - position).normalize();
+            molecule
+                .bonding_sites(atom)
+                .any(|site| (molecule.graph[site].pos - position).dot(direction) > 0.999)
+        };
+        assert!(toward(&molecule, carbon, molecule.graph[other].pos));
+        assert!(toward(&molecule, other, molecule.graph[carbon].pos));
+        history.undo(&mut molecule);
+
+        // Deleting the atom puts back the site it was added at
+        let edit = Edit::DeleteAtom { atom: other };
+        history.perform((), &mut molecule, edit).unwrap();
+        assert_eq!(molecule.graph.node_count(), 5);
+        assert_eq!(molecule.bonding_sites(carbon).count(), 4);
+        assert!(toward(&molecule, carbon, site_pos));
+        assert!(history.undo(&mut molecule).is_some());
+        assert_eq!(molecule.atoms().count(), 2);
+    }
+
+    #[test]
+    fn bond_orders() {
+        let (mut molecule, carbon) = lone_carbon();
+        let mut history = History::new();
+        let site = molecule.bonding_sites(carbon).next().unwrap();
+        let edit = Edit::AddAtom {
+            site,
+            element: Element::Carbon,
+        };
+        history.perform((), &mut molecule, edit).unwrap();
+        let (other, _) = molecule.bonds(carbon).next().unwrap();
+
+        // The angles between the bond and the atom's bonding sites
+        let angles = |molecule: &Molecule, atom: NodeIndex, bonded: NodeIndex| {
+            let position = molecule.graph[atom].pos;
+            let bond = molecule.graph[bonded].pos - position;
+            molecule
+                .bonding_sites(atom)
+                .map(|site| {
+                    (molecule.graph[site].pos - position)
+                        .angle_between(bond)
+                        .to_degrees()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Ethane, ethylene, then acetylene
+        for (order, sites, angle) in [(1, 3, 109.47), (2, 2, 120.0), (3, 1, 180.0), (1, 3, 109.47)]
+        {
+            let edit = Edit::SetBondOrder {
+                a: carbon,
+                b: other,
+                order,
+            };
+            history.perform((), &mut molecule, edit).unwrap();
+            assert_eq!(molecule.bonds(carbon).next(), Some((other, order)));
+            for (atom, bonded) in [(carbon, other), (other, carbon)] {
+                let angles = angles(&molecule, atom, bonded);
+                assert_eq!(angles.len(), sites);
+                for found in angles {
+                    assert!((found - angle).abs() < 1.0, "{} != {}", found, angle);
+                }
+            }
+        }
+
+        // Oxygen forms at most two bonds
+        let site = molecule.bonding_sites(carbon).next().unwrap();
+        let edit = Edit::AddAtom {
+            site,
+            element: Element::Oxygen,
+        };
+        history.perform((), &mut molecule, edit).unwrap();
+        let (oxygen, _) = molecule
+            .bonds(carbon)
+            .find(|&(atom, _)| atom != other)
+            .unwrap();
+        let edit = Edit::SetBondOrder {
+            a: carbon,
+            b: oxygen,
+            order: 2,
+        };
+        history.perform((), &mut molecule, edit).unwrap();
+        assert_eq!(molecule.bonding_sites(oxygen).count(), 0);
+        assert_eq!(molecule.bonding_sites(carbon).count(), 1);
+        let edit = Edit::SetBondOrder {
+            a: carbon,
+            b: oxygen,
+            order: 3,
+        };
+        assert_eq!(
+            history.perform((), &mut molecule, edit),
+            Err(EditError::NoFreeBondingSite(oxygen))
+        );
+    }
+
+    #[test]
+    fn batch() {
+        let (mut molecule, carbon) = lone_carbon();
+        let mut history = History::new();
+        let sites: Vec<_> = molecule.bonding_sites(carbon).collect();
+        let before = summary(&molecule);
+
+        let edit = Edit::Batch(
+            sites
+                .iter()
+                .map(|&site| Edit::AddAtom {
+                    site,
+                    element: Element::Hydrogen,
+                })
+                .collect(),
+        );
+        history.perform((), &mut molecule, edit).unwrap();
+        assert_eq!(molecule.bonds(carbon).count(), 4);
+        assert_eq!(molecule.graph.node_count(), 5);
+
+        history.undo(&mut molecule);
+        assert_eq!(summary(&molecule), before);
+
+        // A batch which fails part way through changes nothing
+        let edit = Edit::Batch(vec![
+            Edit::DeleteAtom { atom: carbon },
+            Edit::DeleteAtom { atom: carbon },
+        ]);
+        assert_eq!(
+            history.perform((), &mut molecule, edit),
+            Err(EditError::NotAnAtom(carbon))
+        );
+        assert_eq!(summary(&molecule), before);
+    }
+
+    #[test]
+    fn new_edit_discards_redo() {
+        let (mut molecule, carbon) = lone_carbon();
+        let mut history = History::new();
+        let site = molecule.bonding_sites(carbon).next().unwrap();
+
+        let edit = Edit::AddAtom {
+            site,
+            element: Element::Oxygen,
+        };
+        history.perform(1, &mut molecule, edit).unwrap();
+        assert_eq!(history.undo(&mut molecule), Some(&1));
+        assert!(history.can_redo());
+
+        let edit = Edit::MoveAtom {
+            atom: carbon,
+            position: Vec3::Y,
+        };
+        history.perform(2, &mut molecule, edit).unwrap();
+        assert!(!history.can_redo());
+        assert_eq!(history.undo_target(), Some(&2));
+    }
+
+    #[test]
+    fn failed_edits() {
+        let (mut molecule, carbon) = lone_carbon();
+        let mut history = History::new();
+        let site = molecule.bonding_sites(carbon).next().unwrap();
+        let before = summary(&molecule);
+
+        let edits = [
+            (
+                Edit::AddAtom {
+                    site: carbon,
+                    element: Element::Carbon,
+                },
+                EditError::NotABondingSite(carbon),
+            ),
+            (Edit::DeleteAtom { atom: site }, EditError::NotAnAtom(site)),
+            (
+                Edit::MoveAtom {
+                    atom: site,
+                    position: Vec3::ZERO,
+                },
+                EditError::NotAnAtom(site),
+            ),
+            (
+                Edit::SetBondOrder {
+                    a: carbon,
+                    b: site,
+                    order: 2,
+                },
+                EditError::NotAnAtom(site),
+            ),
+            (
+                Edit::SetBondOrder {
+                    a: carbon,
+                    b: carbon,
+                    order: 4,
+                },
+                EditError::InvalidBondOrder(4),
+            ),
+        ];
+        for (edit, error) in edits {
+            assert_eq!(history.perform((), &mut molecule, edit), Err(error));
+        }
+        assert_eq!(summary(&molecule), before);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn local_changes() {
+        let (mut molecule, atoms) = fixtures::chain(&[Element::Carbon; 12]);
+        let mut history = History::new();
+        let before = summary(&molecule);
+
+        // Removing an atom from the middle of the chain changes no more than
+        // the atom, its two sites, the new sites of the atoms either side of
+        // it and the atom which faced it
+        let edit = Edit::DeleteAtom { atom: atoms[6] };
+        history.perform((), &mut molecule, edit).unwrap();
+        let (_, change) = history.undo.back().unwrap();
+        assert!(change.nodes.len() <= 6);
+        assert!(change.bonds.len() <= 6);
+        let after = summary(&molecule);
+
+        let edit = Edit::Batch(vec![
+            Edit::MoveAtom {
+                atom: atoms[0],
+                position: -Vec3::X,
+            },
+            Edit::SetBondOrder {
+                a: atoms[10],
+                b: atoms[11],
+                order: 3,
+            },
+        ]);
+        history.perform((), &mut molecule, edit).unwrap();
+        let (_, change) = history.undo.back().unwrap();
+        assert!(change.nodes.len() < 16);
+        let batched = summary(&molecule);
+
+        history.undo(&mut molecule);
+        assert_eq!(summary(&molecule), after);
+        history.undo(&mut molecule);
+        assert_eq!(summary(&molecule), before);
+        history.redo(&mut molecule);
+        history.redo(&mut molecule);
+        assert_eq!(summary(&molecule), batched);
+    }
+}
+
+// End of File
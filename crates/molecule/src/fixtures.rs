@@ -0,0 +1,44 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Small molecules for tests, both in this crate and in the crates built on
+//! it.  This is not part of the crate's API.
+
+use crate::{vsepr, Molecule};
+use glam::Vec3;
+use periodic_table::Element;
+use petgraph::stable_graph::NodeIndex;
+
+/// A chain of atoms of the given elements, each single bonded to the one
+/// before it through one of that atom's bonding sites, along with the atoms
+/// in order.  The first atom is at the origin, and every valence left over is
+/// a bonding site, so e.g. `[Carbon, Carbon]` is ethane with each hydrogen
+/// left as a site.
+pub fn chain(elements: &[Element]) -> (Molecule, Vec<NodeIndex>) {
+    let mut molecule = Molecule::new();
+    let mut atoms = Vec::with_capacity(elements.len());
+    for &element in elements {
+        let atom = match atoms.last() {
+            None => molecule.add_atom(
+                element,
+                Vec3::ZERO,
+                Vec3::Z,
+                None,
+                &vsepr::bond_directions(element, &[]),
+                false,
+            ),
+            Some(&previous) => {
+                let site = molecule
+                    .bonding_sites(previous)
+                    .next()
+                    .expect("no bonding site left to continue the chain");
+                molecule.bond_atom_at_site(site, element).unwrap()
+            }
+        };
+        atoms.push(atom);
+    }
+    (molecule, atoms)
+}
+
+// End of File
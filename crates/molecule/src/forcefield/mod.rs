@@ -0,0 +1,339 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Molecular mechanics: the potential energy of a molecule, and the forces on
+//! its particles.  A `Topology` lists the particles of a molecule and the
+//! bonds, angles and torsions between them; a `ForceField` assigns parameters
+//! to those interactions, giving a `Potential` which can be evaluated for any
+//! positions of the particles.
+//!
+//! Energies are in kcal/mol, distances in angstroms, and forces in
+//! kcal/mol/angstrom.  Bonding sites are treated as hydrogen atoms, as they
+//! stand in for whatever will eventually be bonded there.
+//!
+//! In a molecule with a unit cell, particles interact with the nearest
+//! periodic copy of each other (see `crate::cell`).
+
+pub mod neighbors;
+pub mod uff;
+
+use crate::cell::UnitCell;
+use crate::vsepr::{self, Hybridization};
+use crate::{BondOrder, Molecule, Particle};
+use glam::Vec3;
+use periodic_table::Element;
+use petgraph::stable_graph::NodeIndex;
+use std::collections::{HashMap, HashSet};
+use std::ops::AddAssign;
+
+/// What a force field needs to know about a particle to assign it parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtomType {
+    pub element: Element,
+    /// The formal charge of the atom, in units of the elementary charge.
+    pub charge: i8,
+    /// `None` for elements that VSEPR theory does not apply to.
+    pub hybridization: Option<Hybridization>,
+}
+
+/// A bond between two particles, given as indices into the topology.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bond {
+    pub a: usize,
+    pub b: usize,
+    pub order: BondOrder,
+}
+
+/// The particles of a molecule in a fixed order, and the interactions between
+/// them that a force field assigns parameters to.  Particles are referred to
+/// by their index in `nodes`.
+#[derive(Clone, Debug, Default)]
+pub struct Topology {
+    /// The node of the molecule graph of each particle.
+    pub nodes: Vec<NodeIndex>,
+    pub atom_types: Vec<AtomType>,
+    pub bonds: Vec<Bond>,
+    /// Pairs of bonds sharing an atom, with the shared atom in the middle.
+    pub angles: Vec<[usize; 3]>,
+    /// Chains of three bonds, which turn about the middle bond.
+    pub torsions: Vec<[usize; 4]>,
+    /// The unit cell of the molecule, if it is periodic.
+    pub cell: Option<UnitCell>,
+    // The particle whose position decides which copy of the cell each
+    // particle is wrapped into: itself for atoms, and its atom for bonding
+    // sites, so that they stay together
+    anchors: Vec<usize>,
+    // The neighbours of each particle
+    neighbors: Vec<Vec<usize>>,
+    // Pairs of particles separated by one or two bonds, which only interact
+    // through their bonded terms.  The lower index comes first.
+    excluded: HashSet<(usize, usize)>,
+}
+
+// The type of the particle at `node`
+fn atom_type(molecule: &Molecule, node: NodeIndex) -> AtomType {
+    match &molecule.graph[node].particle {
+        Particle::Atom(atom) => {
+            let orders: Vec<BondOrder> = molecule.bonds(node).map(|(_, o)| o).collect();
+            AtomType {
+                element: atom.element,
+                charge: atom.charge,
+                hybridization: vsepr::charged_domains(atom.element, atom.charge, &orders)
+                    .hybridization(),
+            }
+        }
+        Particle::BondingSite => AtomType {
+            element: Element::Hydrogen,
+            charge: 0,
+            hybridization: Some(Hybridization::S),
+        },
+    }
+}
+
+impl Topology {
+    pub fn new(molecule: &Molecule) -> Self {
+        let graph = &molecule.graph;
+        let nodes: Vec<NodeIndex> = graph.node_indices().collect();
+        let index: HashMap<NodeIndex, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, &node)| (node, index))
+            .collect();
+
+        let atom_types = nodes
+            .iter()
+            .map(|&node| atom_type(molecule, node))
+            .collect();
+
+        let bonds: Vec<Bond> = graph
+            .edge_indices()
+            .filter_map(|edge| {
+                let (a, b) = graph.edge_endpoints(edge)?;
+                Some(Bond {
+                    a: index[&a],
+                    b: index[&b],
+                    order: graph[edge],
+                })
+            })
+            .collect();
+
+        let mut neighbors = vec![Vec::new(); nodes.len()];
+        for bond in bonds.iter() {
+            neighbors[bond.a].push(bond.b);
+            neighbors[bond.b].push(bond.a);
+        }
+
+        let mut angles = Vec::new();
+        for (center, around) in neighbors.iter().enumerate() {
+            for (i, &a) in around.iter().enumerate() {
+                for &c in &around[i + 1..] {
+                    angles.push([a, center, c]);
+                }
+            }
+        }
+
+        let mut torsions = Vec::new();
+        for bond in bonds.iter() {
+            for &a in neighbors[bond.a].iter().filter(|&&a| a != bond.b) {
+                for &d in neighbors[bond.b].iter().filter(|&&d| d != bond.a) {
+                    // Skip three-membered rings, which have no torsion
+                    if a != d {
+                        torsions.push([a, bond.a, bond.b, d]);
+                    }
+                }
+            }
+        }
+
+        let anchors = nodes
+            .iter()
+            .enumerate()
+            .map(|(particle, &node)| match graph[node].particle {
+                Particle::Atom(_) => particle,
+                Particle::BondingSite => neighbors[particle].first().copied().unwrap_or(particle),
+            })
+            .collect();
+
+        let pair = |a: usize, b: usize| (a.min(b), a.max(b));
+        let excluded = bonds
+            .iter()
+            .map(|bond| pair(bond.a, bond.b))
+            .chain(angles.iter().map(|&[a, _, c]| pair(a, c)))
+            .collect();
+
+        Topology {
+            nodes,
+            atom_types,
+            bonds,
+            angles,
+            torsions,
+            cell: molecule.cell,
+            anchors,
+            neighbors,
+            excluded,
+        }
+    }
+
+    /// The number of particles.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Whether `molecule` still has the particles, atom types, bonds and unit
+    /// cell that this topology was built from, so that potentials
+    /// parameterized from it still apply to the molecule.
+    pub fn describes(&self, molecule: &Molecule) -> bool {
+        let graph = &molecule.graph;
+        self.cell == molecule.cell
+            && graph.edge_count() == self.bonds.len()
+            && graph.node_indices().eq(self.nodes.iter().copied())
+            && self
+                .nodes
+                .iter()
+                .zip(&self.atom_types)
+                .all(|(&node, &expected)| atom_type(molecule, node) == expected)
+            && graph.edge_indices().zip(&self.bonds).all(|(edge, bond)| {
+                graph.edge_endpoints(edge) == Some((self.nodes[bond.a], self.nodes[bond.b]))
+                    && graph[edge] == bond.order
+            })
+    }
+
+    /// The particles bonded to `particle`.
+    pub fn neighbors(&self, particle: usize) -> &[usize] {
+        &self.neighbors[particle]
+    }
+
+    /// Whether two particles are separated by one or two bonds, in which case
+    /// their nonbonded interactions are left out.
+    pub fn is_excluded(&self, a: usize, b: usize) -> bool {
+        a == b || self.excluded.contains(&(a.min(b), a.max(b)))
+    }
+
+    /// The positions of the particles of `molecule`, in the order of the
+    /// topology.
+    pub fn positions(&self, molecule: &Molecule) -> Vec<Vec3> {
+        self.nodes
+            .iter()
+            .map(|&node| molecule.graph[node].pos)
+            .collect()
+    }
+
+    /// Wraps `positions`, which are in the order of the topology, back into
+    /// the unit cell.  Bonding sites move along with their atoms.
+    pub fn wrap(&self, positions: &mut [Vec3]) {
+        let Some(cell) = &self.cell else {
+            return;
+        };
+        let shifts: Vec<Vec3> = self
+            .anchors
+            .iter()
+            .map(|&anchor| cell.wrap(positions[anchor]) - positions[anchor])
+            .collect();
+        for (position, shift) in positions.iter_mut().zip(shifts) {
+            *position += shift;
+        }
+    }
+
+    /// Moves the particles of `molecule` to `positions`, which are in the
+    /// order of the topology.
+    pub fn set_positions(&self, molecule: &mut Molecule, positions: &[Vec3]) {
+        for (&node, &position) in self.nodes.iter().zip(positions) {
+            molecule.graph[node].pos = position;
+        }
+    }
+}
+
+/// The potential energy of a molecule, broken down by the kind of interaction
+/// it comes from.  In kcal/mol.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Energy {
+    pub stretch: f32,
+    pub bend: f32,
+    pub torsion: f32,
+    pub van_der_waals: f32,
+    pub electrostatic: f32,
+}
+
+impl Energy {
+    pub fn total(&self) -> f32 {
+        self.stretch + self.bend + self.torsion + self.van_der_waals + self.electrostatic
+    }
+}
+
+impl AddAssign for Energy {
+    fn add_assign(&mut self, other: Self) {
+        self.stretch += other.stretch;
+        self.bend += other.bend;
+        self.torsion += other.torsion;
+        self.van_der_waals += other.van_der_waals;
+        self.electrostatic += other.electrostatic;
+    }
+}
+
+/// A force field with its parameters assigned to the interactions of a
+/// particular topology.
+pub trait Potential: Send + Sync {
+    /// The potential energy of the particles at `positions`, which are in the
+    /// order of the topology.  The force on each particle is added to the
+    /// corresponding element of `forces`.
+    fn evaluate(&self, positions: &[Vec3], forces: &mut [Vec3]) -> Energy;
+}
+
+/// A molecular mechanics force field.
+pub trait ForceField {
+    /// Assigns parameters to the interactions in `topology`.
+    fn parameterize(&self, topology: &Topology) -> Box<dyn Potential>;
+
+    /// How far apart particles can be and still interact, in angstroms.
+    /// Periodic cells must be at least twice this wide (see
+    /// `UnitCell::is_wide_enough`) for every interaction to be counted.
+    fn range(&self) -> f32;
+}
+
+/// A force field parameterized for a molecule, along with the topology it was
+/// parameterized from.  It can be evaluated again and again for as long as
+/// the topology describes the molecule, which spares the potential from being
+/// set up afresh (and its neighbour list from being rebuilt) every time.
+pub struct Parameterized {
+    pub topology: Topology,
+    pub potential: Box<dyn Potential>,
+}
+
+impl Parameterized {
+    pub fn new(molecule: &Molecule, force_field: &dyn ForceField) -> Self {
+        let topology = Topology::new(molecule);
+        let potential = force_field.parameterize(&topology);
+        Self {
+            topology,
+            potential,
+        }
+    }
+
+    /// Parameterizes `molecule` afresh if its topology has changed.  Returns
+    /// whether it had.
+    pub fn update(&mut self, molecule: &Molecule, force_field: &dyn ForceField) -> bool {
+        let changed = !self.topology.describes(molecule);
+        if changed {
+            *self = Self::new(molecule, force_field);
+        }
+        changed
+    }
+}
+
+impl Molecule {
+    /// The potential energy of the molecule in the given force field.
+    pub fn potential_energy(&self, force_field: &dyn ForceField) -> Energy {
+        let topology = Topology::new(self);
+        let positions = topology.positions(self);
+        let mut forces = vec![Vec3::ZERO; positions.len()];
+        force_field
+            .parameterize(&topology)
+            .evaluate(&positions, &mut forces)
+    }
+}
+
+// End of File
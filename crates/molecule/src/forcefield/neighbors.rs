@@ -0,0 +1,238 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Verlet neighbour lists, which keep nonbonded interactions from costing
+//! time proportional to the square of the number of particles.
+//!
+//! The list holds every pair of particles within the cutoff plus a skin
+//! distance of each other.  Until some particle has moved half the skin, no
+//! pair outside the list can have come within the cutoff, so the list is only
+//! rebuilt then.  Building it sorts the particles into a grid of cells as wide
+//! as the cutoff plus skin, and only compares particles in neighbouring cells,
+//! so both building and using the list take linear time.
+//!
+//! In a periodic unit cell, the grid divides the cell itself, and wraps
+//! around at its faces.  Particles are paired with the nearest copy of each
+//! other; the cutoff plus skin should be less than half the narrowest width of
+//! the cell, so that no particle is in range of two copies of another.
+
+use crate::cell::UnitCell;
+use glam::{IVec3, Vec3};
+use std::collections::HashMap;
+
+/// The pairs of particles close enough to interact, given as indices into the
+/// positions the list is built from, with the lower index first.
+#[derive(Clone, Debug)]
+pub struct NeighborList {
+    /// The distance beyond which particles do not interact, in angstroms.
+    pub cutoff: f32,
+    /// How much further apart than the cutoff pairs in the list may be, in
+    /// angstroms.
+    pub skin: f32,
+    /// The periodic cell the particles are in, if any.
+    pub cell: Option<UnitCell>,
+    pairs: Vec<(usize, usize)>,
+    // The positions the list was last built from
+    reference: Vec<Vec3>,
+}
+
+impl NeighborList {
+    /// An empty list, which is built by the first call to `update`.
+    pub fn new(cutoff: f32, skin: f32) -> Self {
+        Self {
+            cutoff,
+            skin,
+            cell: None,
+            pairs: Vec::new(),
+            reference: Vec::new(),
+        }
+    }
+
+    pub fn pairs(&self) -> &[(usize, usize)] {
+        &self.pairs
+    }
+
+    /// Whether any particle has moved far enough since the list was built
+    /// that it may be missing a pair within the cutoff.
+    pub fn is_stale(&self, positions: &[Vec3]) -> bool {
+        let limit = 0.25 * self.skin * self.skin;
+        positions.len() != self.reference.len()
+            || positions
+                .iter()
+                .zip(&self.reference)
+                .any(|(position, reference)| position.distance_squared(*reference) > limit)
+    }
+
+    /// Rebuilds the list if it is stale, leaving out the pairs for which
+    /// `excluded` is true.  Returns whether it was rebuilt.
+    pub fn update(&mut self, positions: &[Vec3], excluded: impl Fn(usize, usize) -> bool) -> bool {
+        let stale = self.is_stale(positions);
+        if stale {
+            self.rebuild(positions, excluded);
+        }
+        stale
+    }
+
+    /// Lists every pair of particles within the cutoff plus skin of each
+    /// other, leaving out the pairs for which `excluded` is true.
+    pub fn rebuild(&mut self, positions: &[Vec3], excluded: impl Fn(usize, usize) -> bool) {
+        let range = self.cutoff + self.skin;
+        let cell = self.cell;
+        let in_range = |a: usize, b: usize| {
+            let offset = positions[b] - positions[a];
+            let offset = cell.map_or(offset, |cell| cell.minimum_image(offset));
+            offset.length_squared() <= range * range && !excluded(a, b)
+        };
+        self.pairs.clear();
+
+        // The number of grid cells along each lattice vector of a periodic
+        // cell.  With fewer than three, some neighbouring grid cells would be
+        // the same one, so every pair is compared instead.
+        let bins = cell.map(|cell| (cell.widths() / range).floor().as_ivec3());
+        if bins.is_some_and(|bins| bins.min_element() < 3) {
+            for a in 0..positions.len() {
+                for b in a + 1..positions.len() {
+                    if in_range(a, b) {
+                        self.pairs.push((a, b));
+                    }
+                }
+            }
+        } else {
+            let cell_of = |pos: Vec3| match (cell, bins) {
+                (Some(cell), Some(bins)) => {
+                    let fractional = cell.to_fractional(pos);
+                    ((fractional - fractional.floor()) * bins.as_vec3())
+                        .as_ivec3()
+                        .min(bins - IVec3::ONE)
+                }
+                _ => (pos / range).floor().as_ivec3(),
+            };
+            // Grid cells past the faces of a periodic cell wrap around
+            let wrap = |grid_cell: IVec3| match bins {
+                Some(bins) => IVec3::new(
+                    grid_cell.x.rem_euclid(bins.x),
+                    grid_cell.y.rem_euclid(bins.y),
+                    grid_cell.z.rem_euclid(bins.z),
+                ),
+                None => grid_cell,
+            };
+
+            let mut cells = HashMap::<IVec3, Vec<usize>>::new();
+            for (index, &pos) in positions.iter().enumerate() {
+                cells.entry(cell_of(pos)).or_default().push(index);
+            }
+
+            for (index, &pos) in positions.iter().enumerate() {
+                let grid_cell = cell_of(pos);
+                for x in -1..=1 {
+                    for y in -1..=1 {
+                        for z in -1..=1 {
+                            let neighbor = wrap(grid_cell + IVec3::new(x, y, z));
+                            let Some(others) = cells.get(&neighbor) else {
+                                continue;
+                            };
+                            // Only list each pair once
+                            for &other in others {
+                                if other > index && in_range(index, other) {
+                                    self.pairs.push((index, other));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        self.reference.clear();
+        self.reference.extend_from_slice(positions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A jumble of particles, from a simple linear congruential generator
+    fn jumble(count: usize, size: f32) -> Vec<Vec3> {
+        let mut state: u32 = 12345;
+        let mut next = || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1 << 24) as f32 * size
+        };
+        (0..count)
+            .map(|_| Vec3::new(next(), next(), next()))
+            .collect()
+    }
+
+    #[test]
+    fn finds_every_pair_in_range() {
+        let positions = jumble(300, 20.0);
+        let mut list = NeighborList::new(4.0, 1.0);
+        assert!(list.update(&positions, |a, b| a + 1 == b));
+
+        let mut expected = Vec::new();
+        for a in 0..positions.len() {
+            for b in a + 1..positions.len() {
+                if a + 1 != b && positions[a].distance(positions[b]) <= 5.0 {
+                    expected.push((a, b));
+                }
+            }
+        }
+        let mut pairs = list.pairs().to_vec();
+        pairs.sort();
+        assert_eq!(pairs, expected);
+        assert!(!pairs.is_empty());
+    }
+
+    #[test]
+    fn periodic() {
+        // Large enough for a periodic grid, and too small for one
+        for size in [20.0, 12.0] {
+            let cell =
+                UnitCell::from_parameters(Vec3::splat(size), Vec3::new(80.0, 90.0, 100.0)).unwrap();
+            let positions: Vec<Vec3> = jumble(200, 1.0)
+                .into_iter()
+                .map(|fractional| cell.to_cartesian(fractional))
+                .collect();
+            let mut list = NeighborList::new(4.0, 1.0);
+            list.cell = Some(cell);
+            list.rebuild(&positions, |_, _| false);
+
+            let mut expected = Vec::new();
+            for a in 0..positions.len() {
+                for b in a + 1..positions.len() {
+                    if cell.minimum_image(positions[b] - positions[a]).length() <= 5.0 {
+                        expected.push((a, b));
+                    }
+                }
+            }
+            let mut pairs = list.pairs().to_vec();
+            pairs.sort();
+            assert_eq!(pairs, expected);
+            // Some pairs are only in range across a face of the cell
+            assert!(pairs
+                .iter()
+                .any(|&(a, b)| positions[a].distance(positions[b]) > 5.0));
+        }
+    }
+
+    #[test]
+    fn rebuilds_after_moving_half_the_skin() {
+        let mut positions = jumble(50, 10.0);
+        let mut list = NeighborList::new(4.0, 1.0);
+        assert!(list.update(&positions, |_, _| false));
+        assert!(!list.update(&positions, |_, _| false));
+
+        positions[7].x += 0.4;
+        assert!(!list.update(&positions, |_, _| false));
+        positions[7].x += 0.2;
+        assert!(list.update(&positions, |_, _| false));
+
+        // A particle was added
+        positions.push(Vec3::ZERO);
+        assert!(list.update(&positions, |_, _| false));
+    }
+}
+
+// End of File
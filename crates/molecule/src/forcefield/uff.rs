@@ -0,0 +1,714 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The Universal Force Field (Rappé et al., J. Am. Chem. Soc. 1992, 114,
+//! 10024), which derives the parameters of every interaction from a handful of
+//! per-atom parameters keyed on element and hybridization.
+//!
+//! Bonds are harmonic, angles use UFF's cosine Fourier expansion, torsions
+//! depend on the hybridization of the atoms of the middle bond, and van der
+//! Waals interactions are Lennard-Jones 12-6.  The inversion term is left out.
+//! UFF itself has no charges; here atoms carry their formal charge plus a
+//! partial charge from the difference in electronegativity across each of
+//! their bonds, which interact through Coulomb's law.
+//!
+//! Nonbonded interactions are cut off at a fixed distance, and found with a
+//! neighbour list which is kept between evaluations of the same potential.
+
+use super::neighbors::NeighborList;
+use super::{Energy, ForceField, Potential, Topology};
+use crate::cell::UnitCell;
+use crate::vsepr::Hybridization;
+use crate::BondOrder;
+use glam::Vec3;
+use periodic_table::Element;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+// Coulomb's constant, in kcal angstrom / (mol e^2).
+const COULOMB: f32 = 332.0637;
+
+// The partial charge, in units of the elementary charge, moved across a bond
+// per unit difference in Pauling electronegativity.
+const BOND_INCREMENT: f32 = 0.25;
+
+// UFF's constant for bond stretching and angle bending force constants, in
+// kcal angstrom / (mol e^2).
+const FORCE_CONSTANT: f32 = 664.12;
+
+/// The per-atom parameters of UFF.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtomParameters {
+    /// The valence bond radius, in angstroms.
+    pub bond_radius: f32,
+    /// The natural angle between bonds to the atom, in degrees.
+    pub angle: f32,
+    /// The van der Waals distance, in angstroms.
+    pub vdw_distance: f32,
+    /// The van der Waals well depth, in kcal/mol.
+    pub vdw_depth: f32,
+    /// The effective charge, which sets the stiffness of bonds and angles.
+    pub effective_charge: f32,
+    /// The torsional barrier about a bond between sp3 atoms, in kcal/mol.
+    pub sp3_torsion: f32,
+    /// The torsional constant about a bond between sp2 atoms, in kcal/mol.
+    pub sp2_torsion: f32,
+    /// The GMP electronegativity, in eV.
+    pub electronegativity: f32,
+}
+
+// Keeps the table below to one line per atom type
+#[allow(clippy::too_many_arguments)]
+const fn params(
+    bond_radius: f32,
+    angle: f32,
+    vdw_distance: f32,
+    vdw_depth: f32,
+    effective_charge: f32,
+    sp3_torsion: f32,
+    sp2_torsion: f32,
+    electronegativity: f32,
+) -> AtomParameters {
+    AtomParameters {
+        bond_radius,
+        angle,
+        vdw_distance,
+        vdw_depth,
+        effective_charge,
+        sp3_torsion,
+        sp2_torsion,
+        electronegativity,
+    }
+}
+
+/// The UFF parameters of an atom of `element` with the given hybridization.
+/// Elements without UFF parameters here get rough estimates from the periodic
+/// table.
+pub fn atom_parameters(element: Element, hybridization: Option<Hybridization>) -> AtomParameters {
+    use Element::*;
+    use Hybridization::*;
+    match (element, hybridization) {
+        (Hydrogen, _) => params(0.354, 180.0, 2.886, 0.044, 0.712, 0.0, 0.0, 4.528),
+        (Boron, Some(Sp2)) => params(0.828, 120.0, 4.083, 0.180, 1.755, 0.0, 2.0, 5.110),
+        (Boron, _) => params(0.838, 109.47, 4.083, 0.180, 1.755, 0.0, 2.0, 5.110),
+        (Carbon, Some(Sp)) => params(0.706, 180.0, 3.851, 0.105, 1.912, 0.0, 2.0, 5.343),
+        (Carbon, Some(Sp2)) => params(0.732, 120.0, 3.851, 0.105, 1.912, 0.0, 2.0, 5.343),
+        (Carbon, _) => params(0.757, 109.47, 3.851, 0.105, 1.912, 2.119, 2.0, 5.343),
+        (Nitrogen, Some(Sp)) => params(0.656, 180.0, 3.660, 0.069, 2.544, 0.0, 2.0, 6.899),
+        (Nitrogen, Some(Sp2)) => params(0.685, 111.2, 3.660, 0.069, 2.544, 0.0, 2.0, 6.899),
+        (Nitrogen, _) => params(0.700, 106.7, 3.660, 0.069, 2.544, 0.450, 2.0, 6.899),
+        (Oxygen, Some(Sp)) => params(0.639, 180.0, 3.500, 0.060, 2.300, 0.0, 2.0, 8.741),
+        (Oxygen, Some(Sp2)) => params(0.634, 120.0, 3.500, 0.060, 2.300, 0.0, 2.0, 8.741),
+        (Oxygen, _) => params(0.658, 104.51, 3.500, 0.060, 2.300, 0.018, 2.0, 8.741),
+        (Fluorine, _) => params(0.668, 180.0, 3.364, 0.050, 1.735, 0.0, 2.0, 10.874),
+        (Silicon, _) => params(1.117, 109.47, 4.295, 0.402, 2.323, 1.225, 1.25, 4.168),
+        (Phosphorus, _) => params(1.101, 93.8, 4.147, 0.305, 2.863, 2.400, 1.25, 5.463),
+        (Sulfur, Some(Sp2)) => params(0.854, 120.0, 4.035, 0.274, 2.703, 0.0, 1.25, 6.928),
+        (Sulfur, _) => params(1.064, 92.1, 4.035, 0.274, 2.703, 0.484, 1.25, 6.928),
+        (Chlorine, _) => params(1.044, 180.0, 3.947, 0.227, 2.348, 0.0, 1.25, 8.564),
+        (Germanium, _) => params(1.197, 109.47, 4.280, 0.379, 2.442, 0.701, 0.7, 4.6),
+        (Bromine, _) => params(1.192, 180.0, 4.189, 0.251, 2.519, 0.0, 0.7, 7.790),
+        (Iodine, _) => params(1.382, 180.0, 4.500, 0.339, 2.650, 0.0, 0.2, 6.822),
+        (_, hybridization) => {
+            let angle = match hybridization {
+                Some(S | Sp) => 180.0,
+                Some(Sp2) => 120.0,
+                Some(Sp3) => 109.47,
+                Some(Sp3d | Sp3d2) | None => 90.0,
+            };
+            params(
+                element.covalent_radius(),
+                angle,
+                2.0 * element.van_der_waals_radius().unwrap_or(2.0),
+                0.1,
+                2.0,
+                0.0,
+                0.0,
+                5.0,
+            )
+        }
+    }
+}
+
+/// The natural length of a bond of the given order between two atoms, in
+/// angstroms: the sum of their bond radii, shortened for higher bond orders
+/// and corrected for their difference in electronegativity.
+pub fn rest_length(a: &AtomParameters, b: &AtomParameters, order: BondOrder) -> f32 {
+    let (ra, rb) = (a.bond_radius, b.bond_radius);
+    let (xa, xb) = (a.electronegativity, b.electronegativity);
+    let bond_order = -0.1332 * (ra + rb) * f32::from(order.max(1)).ln();
+    let electronegativity = ra * rb * (xa.sqrt() - xb.sqrt()).powi(2) / (xa * ra + xb * rb);
+    ra + rb + bond_order - electronegativity
+}
+
+/// The Universal Force Field.
+#[derive(Clone, Copy, Debug)]
+pub struct Uff {
+    /// The relative permittivity that electrostatic interactions are screened
+    /// by.
+    pub dielectric: f32,
+    /// The distance beyond which particles do not interact, in angstroms.
+    pub cutoff: f32,
+    /// The skin distance of the neighbour list, in angstroms.
+    pub skin: f32,
+}
+
+impl Default for Uff {
+    fn default() -> Self {
+        Self {
+            dielectric: 1.0,
+            cutoff: 10.0,
+            skin: 1.0,
+        }
+    }
+}
+
+struct Stretch {
+    a: usize,
+    b: usize,
+    k: f32,
+    rest_length: f32,
+}
+
+// E = k (c0 + c1 cos(theta) + c2 cos(2 theta)), or k (1 + cos(theta)) for
+// linear angles.
+struct Bend {
+    a: usize,
+    center: usize,
+    c: usize,
+    k: f32,
+    c0: f32,
+    c1: f32,
+    c2: f32,
+    linear: bool,
+}
+
+// E = barrier / 2 (1 - cos(n phi0) cos(n phi))
+struct Torsion {
+    atoms: [usize; 4],
+    barrier: f32,
+    periodicity: f32,
+    phase: f32,
+}
+
+struct UffPotential {
+    stretches: Vec<Stretch>,
+    bends: Vec<Bend>,
+    torsions: Vec<Torsion>,
+    // The van der Waals distance and well depth, and the charge, of each
+    // particle
+    vdw: Vec<(f32, f32)>,
+    charges: Vec<f32>,
+    excluded: HashSet<(usize, usize)>,
+    dielectric: f32,
+    cutoff: f32,
+    cell: Option<UnitCell>,
+    // Rebuilt by `evaluate` as the particles move
+    neighbors: Mutex<NeighborList>,
+}
+
+impl ForceField for Uff {
+    fn parameterize(&self, topology: &Topology) -> Box<dyn Potential> {
+        let parameters: Vec<AtomParameters> = topology
+            .atom_types
+            .iter()
+            .map(|atom| atom_parameters(atom.element, atom.hybridization))
+            .collect();
+
+        // Both are keyed by the pair of atoms, lower index first
+        let mut rest_lengths = HashMap::new();
+        let mut bond_orders = HashMap::new();
+        let stretches = topology
+            .bonds
+            .iter()
+            .map(|bond| {
+                let (pa, pb) = (&parameters[bond.a], &parameters[bond.b]);
+                let rest_length = rest_length(pa, pb, bond.order);
+                let pair = (bond.a.min(bond.b), bond.a.max(bond.b));
+                rest_lengths.insert(pair, rest_length);
+                bond_orders.insert(pair, bond.order);
+                Stretch {
+                    a: bond.a,
+                    b: bond.b,
+                    k: FORCE_CONSTANT * pa.effective_charge * pb.effective_charge
+                        / rest_length.powi(3),
+                    rest_length,
+                }
+            })
+            .collect();
+        let rest_length = |a: usize, b: usize| rest_lengths[&(a.min(b), a.max(b))];
+
+        let bends = topology
+            .angles
+            .iter()
+            .map(|&[a, center, c]| {
+                let theta = parameters[center].angle.to_radians();
+                let cos = theta.cos();
+                let (rab, rcb) = (rest_length(a, center), rest_length(c, center));
+                let rac2 = rab * rab + rcb * rcb - 2.0 * rab * rcb * cos;
+                let k = FORCE_CONSTANT
+                    * parameters[a].effective_charge
+                    * parameters[c].effective_charge
+                    / rac2.sqrt().powi(5)
+                    * (3.0 * rab * rcb * (1.0 - cos * cos) - rac2 * cos);
+                let linear = parameters[center].angle > 179.0;
+                let c2 = if linear {
+                    0.0
+                } else {
+                    1.0 / (4.0 * theta.sin().powi(2))
+                };
+                Bend {
+                    a,
+                    center,
+                    c,
+                    k,
+                    c0: c2 * (2.0 * cos * cos + 1.0),
+                    c1: -4.0 * c2 * cos,
+                    c2,
+                    linear,
+                }
+            })
+            .collect();
+
+        let torsions = topology
+            .torsions
+            .iter()
+            .filter_map(|&[a, b, c, d]| {
+                use Hybridization::*;
+                let order = bond_orders.get(&(b.min(c), b.max(c))).copied().unwrap_or(1);
+                let (pb, pc) = (&parameters[b], &parameters[c]);
+                let hybridizations = (
+                    topology.atom_types[b].hybridization,
+                    topology.atom_types[c].hybridization,
+                );
+                let (barrier, periodicity, phase): (f32, f32, f32) = match hybridizations {
+                    (Some(Sp3), Some(Sp3)) => {
+                        ((pb.sp3_torsion * pc.sp3_torsion).sqrt(), 3.0, 180.0)
+                    }
+                    (Some(Sp2), Some(Sp2)) => {
+                        let bond_order = 1.0 + 4.18 * f32::from(order.max(1)).ln();
+                        (
+                            5.0 * (pb.sp2_torsion * pc.sp2_torsion).sqrt() * bond_order,
+                            2.0,
+                            180.0,
+                        )
+                    }
+                    (Some(Sp2), Some(Sp3)) | (Some(Sp3), Some(Sp2)) => (1.0, 6.0, 0.0),
+                    _ => return None,
+                };
+                // The barrier is shared between every torsion about the bond
+                let count = (topology.neighbors(b).len() - 1) * (topology.neighbors(c).len() - 1);
+                Some(Torsion {
+                    atoms: [a, b, c, d],
+                    barrier: barrier / count as f32,
+                    periodicity,
+                    phase: (periodicity * phase.to_radians()).cos(),
+                })
+            })
+            .filter(|torsion| torsion.barrier > 0.0)
+            .collect();
+
+        // Formal charges, plus the partial charges of polar bonds
+        let mut charges: Vec<f32> = topology
+            .atom_types
+            .iter()
+            .map(|atom| f32::from(atom.charge))
+            .collect();
+        for bond in topology.bonds.iter() {
+            let electronegativity =
+                |index: usize| topology.atom_types[index].element.electronegativity();
+            if let (Some(a), Some(b)) = (electronegativity(bond.a), electronegativity(bond.b)) {
+                let shift = BOND_INCREMENT * (b - a);
+                charges[bond.a] += shift;
+                charges[bond.b] -= shift;
+            }
+        }
+
+        let mut neighbors = NeighborList::new(self.cutoff, self.skin);
+        neighbors.cell = topology.cell;
+
+        Box::new(UffPotential {
+            stretches,
+            bends,
+            torsions,
+            vdw: parameters
+                .iter()
+                .map(|atom| (atom.vdw_distance, atom.vdw_depth))
+                .collect(),
+            charges,
+            excluded: topology.excluded.clone(),
+            dielectric: self.dielectric,
+            cutoff: self.cutoff,
+            cell: topology.cell,
+            neighbors: Mutex::new(neighbors),
+        })
+    }
+
+    // Pairs up to the skin beyond the cutoff are in the neighbour list
+    fn range(&self) -> f32 {
+        self.cutoff + self.skin
+    }
+}
+
+impl Potential for UffPotential {
+    fn evaluate(&self, positions: &[Vec3], forces: &mut [Vec3]) -> Energy {
+        let mut energy = Energy::default();
+
+        for stretch in self.stretches.iter() {
+            let offset = self.offset(positions, stretch.a, stretch.b);
+            let length = offset.length();
+            let stretched = length - stretch.rest_length;
+            energy.stretch += 0.5 * stretch.k * stretched * stretched;
+            let force = offset.normalize_or_zero() * (stretch.k * stretched);
+            forces[stretch.a] += force;
+            forces[stretch.b] -= force;
+        }
+
+        for bend in self.bends.iter() {
+            let u = self.offset(positions, bend.center, bend.a);
+            let v = self.offset(positions, bend.center, bend.c);
+            let (Some(u_dir), Some(v_dir)) = (u.try_normalize(), v.try_normalize()) else {
+                continue;
+            };
+            let cos = u_dir.dot(v_dir).clamp(-1.0, 1.0);
+            // The derivative of the energy with respect to cos(theta)
+            let derivative = if bend.linear {
+                energy.bend += bend.k * (1.0 + cos);
+                bend.k
+            } else {
+                let cos2 = 2.0 * cos * cos - 1.0;
+                energy.bend += bend.k * (bend.c0 + bend.c1 * cos + bend.c2 * cos2);
+                bend.k * (bend.c1 + 4.0 * bend.c2 * cos)
+            };
+            let force_a = -derivative * (v_dir - cos * u_dir) / u.length();
+            let force_c = -derivative * (u_dir - cos * v_dir) / v.length();
+            forces[bend.a] += force_a;
+            forces[bend.c] += force_c;
+            forces[bend.center] -= force_a + force_c;
+        }
+
+        for torsion in self.torsions.iter() {
+            let [a, b, c, d] = torsion.atoms;
+            let b1 = self.offset(positions, a, b);
+            let b2 = self.offset(positions, b, c);
+            let b3 = self.offset(positions, c, d);
+            let m = b1.cross(b2);
+            let n = b2.cross(b3);
+            let (m2, n2, b2_length) = (m.length_squared(), n.length_squared(), b2.length());
+            if m2 < 1e-8 || n2 < 1e-8 || b2_length < 1e-4 {
+                continue;
+            }
+            let phi = (b2_length * b1.dot(n)).atan2(m.dot(n));
+            let periodicity = torsion.periodicity;
+            energy.torsion +=
+                0.5 * torsion.barrier * (1.0 - torsion.phase * (periodicity * phi).cos());
+            let derivative =
+                0.5 * torsion.barrier * torsion.phase * periodicity * (periodicity * phi).sin();
+
+            let grad_a = -b2_length / m2 * m;
+            let grad_d = b2_length / n2 * n;
+            let (along_1, along_3) = (
+                b1.dot(b2) / (b2_length * b2_length),
+                b3.dot(b2) / (b2_length * b2_length),
+            );
+            let grad_b = along_3 * grad_d - (1.0 + along_1) * grad_a;
+            let grad_c = along_1 * grad_a - (1.0 + along_3) * grad_d;
+            forces[a] -= derivative * grad_a;
+            forces[b] -= derivative * grad_b;
+            forces[c] -= derivative * grad_c;
+            forces[d] -= derivative * grad_d;
+        }
+
+        let mut neighbors = self.neighbors.lock().unwrap();
+        neighbors.update(positions, |a, b| self.excluded.contains(&(a, b)));
+        for &(a, b) in neighbors.pairs() {
+            energy += self.nonbonded(a, b, positions, forces);
+        }
+
+        energy
+    }
+}
+
+impl UffPotential {
+    // The offset from particle `a` to particle `b`, or to its nearest copy in
+    // a periodic cell
+    fn offset(&self, positions: &[Vec3], a: usize, b: usize) -> Vec3 {
+        let offset = positions[b] - positions[a];
+        self.cell.map_or(offset, |cell| cell.minimum_image(offset))
+    }
+
+    // The van der Waals and electrostatic interaction of a pair of particles
+    fn nonbonded(&self, a: usize, b: usize, positions: &[Vec3], forces: &mut [Vec3]) -> Energy {
+        let offset = self.offset(positions, a, b);
+        let distance2 = offset.length_squared();
+        if distance2 < 1e-8 || distance2 > self.cutoff * self.cutoff {
+            return Energy::default();
+        }
+        let distance = distance2.sqrt();
+
+        let ((xa, da), (xb, db)) = (self.vdw[a], self.vdw[b]);
+        let (x, depth) = ((xa * xb).sqrt(), (da * db).sqrt());
+        let ratio6 = (x * x / distance2).powi(3);
+        let van_der_waals = depth * (ratio6 * ratio6 - 2.0 * ratio6);
+        // -dE/dr times r
+        let mut virial = 12.0 * depth * (ratio6 * ratio6 - ratio6);
+
+        let qq = self.charges[a] * self.charges[b];
+        let electrostatic = COULOMB * qq / (self.dielectric * distance);
+        virial += electrostatic;
+
+        let force = offset * (virial / distance2);
+        forces[a] -= force;
+        forces[b] += force;
+        Energy {
+            van_der_waals,
+            electrostatic,
+            ..Energy::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forcefield::Parameterized;
+    use crate::{fixtures, vsepr, Molecule, Particle};
+    use petgraph::stable_graph::NodeIndex;
+
+    // Ethane, with every hydrogen left as a bonding site
+    fn ethane() -> Molecule {
+        fixtures::chain(&[Element::Carbon, Element::Carbon]).0
+    }
+
+    #[test]
+    fn bond_lengths() {
+        let carbon = |hybridization| atom_parameters(Element::Carbon, Some(hybridization));
+        let hydrogen = atom_parameters(Element::Hydrogen, Some(Hybridization::S));
+        let single = rest_length(&carbon(Hybridization::Sp3), &carbon(Hybridization::Sp3), 1);
+        let double = rest_length(&carbon(Hybridization::Sp2), &carbon(Hybridization::Sp2), 2);
+        let triple = rest_length(&carbon(Hybridization::Sp), &carbon(Hybridization::Sp), 3);
+        let ch = rest_length(&carbon(Hybridization::Sp3), &hydrogen, 1);
+        assert!((single - 1.514).abs() < 0.01, "{}", single);
+        assert!((double - 1.329).abs() < 0.01, "{}", double);
+        assert!((triple - 1.20).abs() < 0.02, "{}", triple);
+        assert!((ch - 1.11).abs() < 0.01, "{}", ch);
+    }
+
+    #[test]
+    fn atom_types() {
+        let mut molecule = ethane();
+        let topology = Topology::new(&molecule);
+        let carbons: Vec<_> = topology
+            .atom_types
+            .iter()
+            .filter(|atom| atom.element == Element::Carbon)
+            .collect();
+        assert_eq!(carbons.len(), 2);
+        assert!(carbons
+            .iter()
+            .all(|atom| atom.hybridization == Some(Hybridization::Sp3)));
+        // Bonding sites count as hydrogen
+        assert_eq!(topology.len(), 8);
+        assert_eq!(topology.bonds.len(), 7);
+        assert_eq!(topology.angles.len(), 12);
+        assert_eq!(topology.torsions.len(), 9);
+
+        // Making the bond double makes both carbons sp2
+        let carbons: Vec<_> = molecule.atoms().map(|(node, _)| node).collect();
+        molecule.set_bond_order(carbons[0], carbons[1], 2).unwrap();
+        let topology = Topology::new(&molecule);
+        assert!(topology
+            .atom_types
+            .iter()
+            .filter(|atom| atom.element == Element::Carbon)
+            .all(|atom| atom.hybridization == Some(Hybridization::Sp2)));
+    }
+
+    #[test]
+    fn forces_are_the_gradient() {
+        let (molecule, _) = fixtures::chain(&[Element::Oxygen, Element::Carbon, Element::Nitrogen]);
+
+        // Move every particle off its ideal position, so that every term
+        // contributes a force
+        let topology = Topology::new(&molecule);
+        let mut positions = topology.positions(&molecule);
+        for (index, position) in positions.iter_mut().enumerate() {
+            let i = index as f32;
+            *position += 0.15 * Vec3::new((i * 1.3).sin(), (i * 2.1).cos(), (i * 0.7).sin());
+        }
+
+        let potential = Uff::default().parameterize(&topology);
+        let mut forces = vec![Vec3::ZERO; positions.len()];
+        let energy = potential.evaluate(&positions, &mut forces);
+        assert!(energy.stretch > 0.0 && energy.bend > 0.0 && energy.torsion > 0.0);
+        assert!(energy.van_der_waals != 0.0 && energy.electrostatic != 0.0);
+
+        let total = |positions: &[Vec3]| {
+            let mut scratch = vec![Vec3::ZERO; positions.len()];
+            potential.evaluate(positions, &mut scratch).total()
+        };
+        let step = 1e-3;
+        for index in 0..positions.len() {
+            for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+                let mut forward = positions.clone();
+                forward[index] += axis * step;
+                let mut backward = positions.clone();
+                backward[index] -= axis * step;
+                let numerical = -(total(&forward) - total(&backward)) / (2.0 * step);
+                let analytical = forces[index].dot(axis);
+                assert!(
+                    (numerical - analytical).abs() < 0.05 + 0.02 * analytical.abs(),
+                    "particle {} along {}: {} vs {}",
+                    index,
+                    axis,
+                    numerical,
+                    analytical
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn relaxing_ethane() {
+        let mut molecule = ethane();
+        let mut parameterized = None;
+        for _ in 0..2000 {
+            molecule.relax_step(&Uff::default(), &mut parameterized);
+        }
+        let carbons: Vec<_> = molecule.atoms().map(|(node, _)| node).collect();
+        let length = molecule.graph[carbons[0]]
+            .pos
+            .distance(molecule.graph[carbons[1]].pos);
+        assert!((length - 1.514).abs() < 0.02, "{}", length);
+        for site in molecule.bonding_sites(carbons[0]) {
+            let length = molecule.graph[carbons[0]]
+                .pos
+                .distance(molecule.graph[site].pos);
+            assert!((length - 1.11).abs() < 0.02, "{}", length);
+        }
+    }
+
+    #[test]
+    fn reparameterizing_after_edits() {
+        let mut molecule = ethane();
+        let force_field = Uff::default();
+        let mut parameterized = Parameterized::new(&molecule, &force_field);
+
+        // Moving particles leaves the parameters as they were
+        let carbons: Vec<_> = molecule.atoms().map(|(node, _)| node).collect();
+        molecule.move_atom(carbons[0], Vec3::X);
+        assert!(!parameterized.update(&molecule, &force_field));
+
+        // Unlike changing the bonds, charges or unit cell
+        molecule.set_bond_order(carbons[0], carbons[1], 2);
+        assert!(parameterized.update(&molecule, &force_field));
+        assert!(!parameterized.update(&molecule, &force_field));
+        if let Particle::Atom(atom) = &mut molecule.graph[carbons[1]].particle {
+            atom.charge = -1;
+        }
+        assert!(parameterized.update(&molecule, &force_field));
+        molecule.cell = UnitCell::cubic(30.0);
+        assert!(parameterized.update(&molecule, &force_field));
+        assert!(parameterized.topology.describes(&molecule));
+    }
+
+    #[test]
+    fn staggered_ethane_is_lower_in_energy() {
+        let molecule = ethane();
+        let topology = Topology::new(&molecule);
+        let potential = Uff::default().parameterize(&topology);
+        let positions = topology.positions(&molecule);
+
+        // Turn one methyl group about the C-C bond
+        let energy_at = |angle: f32| {
+            let carbons: Vec<usize> = (0..topology.len())
+                .filter(|&i| topology.atom_types[i].element == Element::Carbon)
+                .collect();
+            let (a, b) = (carbons[0], carbons[1]);
+            let axis = (positions[b] - positions[a]).normalize();
+            let rotation = glam::Quat::from_axis_angle(axis, angle);
+            let mut turned = positions.clone();
+            for &site in topology.neighbors(b).iter().filter(|&&site| site != a) {
+                turned[site] = positions[b] + rotation * (positions[site] - positions[b]);
+            }
+            let mut forces = vec![Vec3::ZERO; turned.len()];
+            potential.evaluate(&turned, &mut forces).torsion
+        };
+
+        let energies: Vec<f32> = (0..12)
+            .map(|step| energy_at(step as f32 * std::f32::consts::PI / 6.0))
+            .collect();
+        let (lowest, highest) = energies
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), &e| (lo.min(e), hi.max(e)));
+        // The barrier is that of UFF's sp3 carbon
+        assert!((highest - lowest - 2.119).abs() < 0.01, "{:?}", energies);
+        // The bonding sites are laid out eclipsed, so a sixth of a turn
+        // staggers them
+        assert!((energies[0] - highest).abs() < 0.01, "{:?}", energies);
+        assert!((energies[2] - lowest).abs() < 0.01, "{:?}", energies);
+    }
+
+    #[test]
+    fn periodic() {
+        let mut molecule = ethane();
+        let energy = molecule.potential_energy(&Uff::default());
+
+        // Centred on a corner of a cell, so that wrapping it into the cell
+        // sends its carbons to opposite sides.  They still interact with the
+        // nearest copy of each other.
+        let carbons: Vec<NodeIndex> = molecule.atoms().map(|(node, _)| node).collect();
+        let center = (molecule.graph[carbons[0]].pos + molecule.graph[carbons[1]].pos) / 2.0;
+        for node in molecule.graph.node_weights_mut() {
+            node.pos -= center;
+        }
+        molecule.cell = UnitCell::from_parameters(Vec3::splat(30.0), Vec3::new(70.0, 90.0, 90.0));
+        molecule.wrap_into_cell();
+        assert!(
+            molecule.graph[carbons[0]]
+                .pos
+                .distance(molecule.graph[carbons[1]].pos)
+                > 10.0
+        );
+        let wrapped = molecule.potential_energy(&Uff::default());
+        assert!((wrapped.total() - energy.total()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn cells_too_narrow_for_the_cutoff() {
+        // The conventional cell of diamond is far narrower than the cutoff,
+        // so its atoms would miss most copies of each other
+        let uff = Uff::default();
+        assert!(!UnitCell::cubic(3.567).unwrap().is_wide_enough(uff.range()));
+        assert!(!UnitCell::cubic(21.0).unwrap().is_wide_enough(uff.range()));
+        assert!(UnitCell::cubic(22.0).unwrap().is_wide_enough(uff.range()));
+    }
+
+    #[test]
+    fn cutoff() {
+        // Two methane carbons, a given distance apart
+        let energy_apart = |distance: f32| {
+            let mut molecule = Molecule::new();
+            for x in [0.0, distance] {
+                molecule.add_atom(
+                    Element::Carbon,
+                    Vec3::new(x, 0.0, 0.0),
+                    Vec3::Z,
+                    None,
+                    &vsepr::bond_directions(Element::Carbon, &[]),
+                    false,
+                );
+            }
+            molecule.potential_energy(&Uff::default())
+        };
+
+        let near = energy_apart(5.0);
+        assert!(near.van_der_waals != 0.0);
+        let far = energy_apart(Uff::default().cutoff + 2.0);
+        assert_eq!(far.van_der_waals, 0.0);
+        assert_eq!(far.electrostatic, 0.0);
+    }
+}
+
+// End of File
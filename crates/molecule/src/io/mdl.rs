@@ -0,0 +1,795 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! MDL molfiles and structure-data (SDF) files.  A molfile holds a single
+//! connection table, in either the fixed-column V2000 format or the
+//! free-format V3000 format.  An SDF file is a sequence of records, each a
+//! molfile followed by named data items and a `$$$$` line.
+//!
+//! Bond orders, formal charges, isotopes and 3D coordinates are kept.
+//! Aromatic bonds (type 4) are read as single bonds, since bond orders are
+//! integers.  Molfiles usually leave out hydrogens, so imported atoms are
+//! given bonding sites wherever their valence is not satisfied.
+
+use super::{columns, ReadError};
+use crate::{Atom, BondOrder, MolNode, Molecule, Particle};
+use glam::Vec3;
+use periodic_table::Element;
+use petgraph::stable_graph::NodeIndex;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+/// The version of the connection table in a molfile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    /// The original fixed-column format, limited to 999 atoms and bonds.
+    V2000,
+    /// The extended format, with no limit on the number of atoms.
+    V3000,
+}
+
+/// A molfile, or one record of an SDF file.
+#[derive(Clone, Debug, Default)]
+pub struct Record {
+    pub name: String,
+    pub comment: String,
+    pub molecule: Molecule,
+    /// The data items following the connection table, as (field name, value)
+    /// pairs.  The lines of a multi-line value are joined with newlines.
+    pub data: Vec<(String, String)>,
+}
+
+// Reads lines while keeping count of the current line number
+struct LineReader<R> {
+    lines: io::Lines<R>,
+    number: usize,
+}
+
+impl<R: BufRead> LineReader<R> {
+    fn next(&mut self) -> Result<Option<String>, ReadError> {
+        self.number += 1;
+        Ok(self.lines.next().transpose()?)
+    }
+
+    fn expect(&mut self, what: &str) -> Result<String, ReadError> {
+        self.next()?
+            .ok_or_else(|| ReadError::parse(self.number, format!("missing {}", what)))
+    }
+}
+
+/// An iterator over the records of an SDF file.  Iteration stops after the
+/// first error.
+pub struct Records<R> {
+    lines: LineReader<R>,
+    done: bool,
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = Result<Record, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = read_record(&mut self.lines).transpose();
+        if !matches!(record, Some(Ok(_))) {
+            self.done = true;
+        }
+        record
+    }
+}
+
+/// Iterates over the records of an SDF file.
+pub fn records<R: BufRead>(reader: R) -> Records<R> {
+    Records {
+        lines: LineReader {
+            lines: reader.lines(),
+            number: 0,
+        },
+        done: false,
+    }
+}
+
+/// Reads a molfile, or the first record of an SDF file.
+pub fn read(reader: impl BufRead) -> Result<Record, ReadError> {
+    records(reader)
+        .next()
+        .unwrap_or_else(|| Err(ReadError::parse(1, "empty file")))
+}
+
+fn parse_field<T: FromStr>(
+    line: &str,
+    first: usize,
+    last: usize,
+    number: usize,
+    what: &str,
+) -> Result<T, ReadError> {
+    let field = columns(line, first, last).trim();
+    field
+        .parse()
+        .map_err(|_| ReadError::parse(number, format!("invalid {} {:?}", what, field)))
+}
+
+fn add_atom(molecule: &mut Molecule, atom: Atom, pos: Vec3) -> NodeIndex {
+    molecule.graph.add_node(MolNode {
+        pos,
+        vel: Vec3::ZERO,
+        particle: Particle::Atom(atom),
+    })
+}
+
+fn atom_mut(molecule: &mut Molecule, node: NodeIndex) -> &mut Atom {
+    match &mut molecule.graph[node].particle {
+        Particle::Atom(atom) => atom,
+        Particle::BondingSite => unreachable!("molfiles only contain atoms"),
+    }
+}
+
+// Checks that an isotope of `element` could have the given mass number.  No
+// nucleus has fewer nucleons than protons, and an isotope of mass number zero
+// would weigh nothing.
+fn check_mass_number(element: Element, mass_number: u16, number: usize) -> Result<u16, ReadError> {
+    if mass_number < u16::from(element.atomic_number()) {
+        return Err(ReadError::parse(
+            number,
+            format!(
+                "impossible mass number {} for {}",
+                mass_number,
+                element.symbol()
+            ),
+        ));
+    }
+    Ok(mass_number)
+}
+
+// Reads the (atom, value) pairs of a properties block line such as "M  CHG",
+// which holds a count followed by pairs of atom numbers and values.
+fn atom_values<T: FromStr>(
+    line: &str,
+    number: usize,
+    atoms: &[NodeIndex],
+) -> Result<Vec<(NodeIndex, T)>, ReadError> {
+    let kind = columns(line, 1, 6);
+    let fields: Vec<&str> = line[6..].split_whitespace().collect();
+    let mut values = Vec::new();
+    for pair in fields.get(1..).unwrap_or_default().chunks(2) {
+        let (Some(Ok(serial)), Some(Ok(value))) = (
+            pair.first().map(|field| field.parse::<usize>()),
+            pair.get(1).map(|field| field.parse::<T>()),
+        ) else {
+            return Err(ReadError::parse(number, format!("malformed {} line", kind)));
+        };
+        let atom = serial
+            .checked_sub(1)
+            .and_then(|index| atoms.get(index))
+            .ok_or_else(|| ReadError::parse(number, format!("{} of an unknown atom", kind)))?;
+        values.push((*atom, value));
+    }
+    Ok(values)
+}
+
+fn add_bond(
+    molecule: &mut Molecule,
+    a: Option<NodeIndex>,
+    b: Option<NodeIndex>,
+    kind: u8,
+    number: usize,
+) -> Result<(), ReadError> {
+    let order = match kind {
+        1..=3 => kind,
+        4 => 1,
+        _ => {
+            return Err(ReadError::parse(
+                number,
+                format!("unsupported bond type {}", kind),
+            ))
+        }
+    };
+    let (Some(a), Some(b)) = (a, b) else {
+        return Err(ReadError::parse(number, "bond to an unknown atom"));
+    };
+    if a == b {
+        return Err(ReadError::parse(number, "atom is bonded to itself"));
+    }
+    molecule.add_bond(a, b, order);
+    Ok(())
+}
+
+fn read_record<R: BufRead>(lines: &mut LineReader<R>) -> Result<Option<Record>, ReadError> {
+    let Some(name) = lines.next()? else {
+        return Ok(None);
+    };
+    if lines.next()?.is_none() {
+        // Allow a blank line at the end of the file
+        if name.trim().is_empty() {
+            return Ok(None);
+        }
+        return Err(ReadError::parse(lines.number, "missing header"));
+    }
+    let comment = lines.expect("comment line")?;
+    let counts = lines.expect("counts line")?;
+
+    let mut molecule = Molecule::new();
+    let atoms = match columns(&counts, 35, 39).trim() {
+        "" | "V2000" => read_v2000(lines, &counts, &mut molecule)?,
+        "V3000" => read_v3000(lines, &mut molecule)?,
+        version => {
+            return Err(ReadError::parse(
+                lines.number,
+                format!("unsupported molfile version {:?}", version),
+            ))
+        }
+    };
+    for atom in atoms {
+        molecule.add_bonding_sites(atom);
+    }
+
+    Ok(Some(Record {
+        name: name.trim_end().to_owned(),
+        comment: comment.trim_end().to_owned(),
+        molecule,
+        data: read_data(lines)?,
+    }))
+}
+
+fn read_v2000<R: BufRead>(
+    lines: &mut LineReader<R>,
+    counts: &str,
+    molecule: &mut Molecule,
+) -> Result<Vec<NodeIndex>, ReadError> {
+    let atom_count: usize = parse_field(counts, 1, 3, lines.number, "atom count")?;
+    let bond_count: usize = parse_field(counts, 4, 6, lines.number, "bond count")?;
+
+    let mut atoms = Vec::with_capacity(atom_count);
+    for _ in 0..atom_count {
+        let line = lines.expect("atom")?;
+        let number = lines.number;
+        let pos = Vec3::new(
+            parse_field(&line, 1, 10, number, "coordinate")?,
+            parse_field(&line, 11, 20, number, "coordinate")?,
+            parse_field(&line, 21, 30, number, "coordinate")?,
+        );
+        let symbol = columns(&line, 32, 34).trim();
+        let element = symbol
+            .parse::<Element>()
+            .map_err(|err| ReadError::parse(number, err.to_string()))?;
+        // Charges are stored as codes counting down from +3, with 4 marking
+        // a radical
+        let charge = match columns(&line, 37, 39).trim() {
+            "" | "0" | "4" => 0,
+            "1" => 3,
+            "2" => 2,
+            "3" => 1,
+            "5" => -1,
+            "6" => -2,
+            "7" => -3,
+            code => {
+                return Err(ReadError::parse(
+                    number,
+                    format!("invalid charge code {:?}", code),
+                ))
+            }
+        };
+        atoms.push(add_atom(
+            molecule,
+            Atom {
+                charge,
+                ..Atom::new(element)
+            },
+            pos,
+        ));
+    }
+
+    for _ in 0..bond_count {
+        let line = lines.expect("bond")?;
+        let number = lines.number;
+        let a: usize = parse_field(&line, 1, 3, number, "atom number")?;
+        let b: usize = parse_field(&line, 4, 6, number, "atom number")?;
+        let kind = parse_field(&line, 7, 9, number, "bond type")?;
+        let atom = |serial: usize| atoms.get(serial.checked_sub(1)?).copied();
+        add_bond(molecule, atom(a), atom(b), kind, number)?;
+    }
+
+    // The properties block
+    let mut charges_cleared = false;
+    loop {
+        let line = lines.expect("M  END")?;
+        match columns(&line, 1, 6) {
+            "M  END" => break,
+            "M  CHG" => {
+                // Charges given here replace all of those in the atom block
+                if !charges_cleared {
+                    for &atom in atoms.iter() {
+                        atom_mut(molecule, atom).charge = 0;
+                    }
+                    charges_cleared = true;
+                }
+                for (atom, charge) in atom_values(&line, lines.number, &atoms)? {
+                    atom_mut(molecule, atom).charge = charge;
+                }
+            }
+            "M  ISO" => {
+                for (atom, mass_number) in atom_values(&line, lines.number, &atoms)? {
+                    let atom = atom_mut(molecule, atom);
+                    atom.isotope =
+                        Some(check_mass_number(atom.element, mass_number, lines.number)?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(atoms)
+}
+
+// Reads the next line of a V3000 connection table, with the "M  V30 " prefix
+// removed and any continuation lines (marked by a trailing '-') joined on.
+// Returns `None` at the "M  END" line.
+fn read_v30_line<R: BufRead>(lines: &mut LineReader<R>) -> Result<Option<String>, ReadError> {
+    loop {
+        let line = lines.expect("M  END")?;
+        if line.starts_with("M  END") {
+            return Ok(None);
+        }
+        let Some(mut content) = line.strip_prefix("M  V30 ").map(str::to_owned) else {
+            continue;
+        };
+        while content.ends_with('-') {
+            content.pop();
+            let line = lines.expect("continuation line")?;
+            content.push_str(line.strip_prefix("M  V30 ").unwrap_or(&line));
+        }
+        return Ok(Some(content));
+    }
+}
+
+fn read_v3000<R: BufRead>(
+    lines: &mut LineReader<R>,
+    molecule: &mut Molecule,
+) -> Result<Vec<NodeIndex>, ReadError> {
+    let mut atoms = Vec::new();
+    let mut indices = HashMap::<usize, NodeIndex>::new();
+    let mut block = String::new();
+
+    while let Some(line) = read_v30_line(lines)? {
+        let number = lines.number;
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix("BEGIN ") {
+            block = name.trim().to_owned();
+            continue;
+        }
+        if line.starts_with("END ") {
+            block.clear();
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let field = |index: usize, what: &str| {
+            fields
+                .get(index)
+                .copied()
+                .ok_or_else(|| ReadError::parse(number, format!("missing {}", what)))
+        };
+        let parse = |index: usize, what: &str| -> Result<f32, ReadError> {
+            let value = field(index, what)?;
+            value
+                .parse()
+                .map_err(|_| ReadError::parse(number, format!("invalid {} {:?}", what, value)))
+        };
+        let parse_index = |index: usize, what: &str| -> Result<usize, ReadError> {
+            let value = field(index, what)?;
+            value
+                .parse()
+                .map_err(|_| ReadError::parse(number, format!("invalid {} {:?}", what, value)))
+        };
+
+        match block.as_str() {
+            "ATOM" => {
+                let index = parse_index(0, "atom index")?;
+                let symbol = field(1, "atom type")?.trim_matches('"');
+                let element = symbol
+                    .parse::<Element>()
+                    .map_err(|err| ReadError::parse(number, err.to_string()))?;
+                let pos = Vec3::new(
+                    parse(2, "coordinate")?,
+                    parse(3, "coordinate")?,
+                    parse(4, "coordinate")?,
+                );
+                let mut atom = Atom::new(element);
+                for property in fields.iter().skip(6) {
+                    if let Some(value) = property.strip_prefix("CHG=") {
+                        atom.charge = value.parse().map_err(|_| {
+                            ReadError::parse(number, format!("invalid charge {:?}", value))
+                        })?;
+                    } else if let Some(value) = property.strip_prefix("MASS=") {
+                        let mass_number = value.parse().map_err(|_| {
+                            ReadError::parse(number, format!("invalid mass {:?}", value))
+                        })?;
+                        atom.isotope = Some(check_mass_number(element, mass_number, number)?);
+                    }
+                }
+                let atom = add_atom(molecule, atom, pos);
+                indices.insert(index, atom);
+                atoms.push(atom);
+            }
+            "BOND" => {
+                let kind = parse_index(1, "bond type")?;
+                let a = indices.get(&parse_index(2, "atom index")?).copied();
+                let b = indices.get(&parse_index(3, "atom index")?).copied();
+                add_bond(molecule, a, b, kind.min(u8::MAX as usize) as u8, number)?;
+            }
+            // Collections, S-groups and so on are skipped
+            _ => {}
+        }
+    }
+
+    Ok(atoms)
+}
+
+// Reads the data items of an SDF record, up to and including the "$$$$" line
+fn read_data<R: BufRead>(lines: &mut LineReader<R>) -> Result<Vec<(String, String)>, ReadError> {
+    let mut data = Vec::new();
+    'record: while let Some(line) = lines.next()? {
+        if line.starts_with("$$$$") {
+            break;
+        }
+        // A data header looks like "> <NAME>", possibly with other text
+        // around the name
+        if !line.starts_with('>') {
+            continue;
+        }
+        let name = line
+            .split_once('<')
+            .and_then(|(_, rest)| rest.split_once('>'))
+            .map_or("", |(name, _)| name);
+
+        let mut value = Vec::new();
+        while let Some(line) = lines.next()? {
+            if line.starts_with("$$$$") {
+                data.push((name.to_owned(), value.join("\n")));
+                break 'record;
+            }
+            if line.trim().is_empty() {
+                break;
+            }
+            value.push(line);
+        }
+        data.push((name.to_owned(), value.join("\n")));
+    }
+    Ok(data)
+}
+
+/// Writes a molecule as a molfile.  Bonding sites are not written.  Fails if
+/// the molecule has too many atoms or bonds for the given version.
+pub fn write(molecule: &Molecule, version: Version, mut writer: impl Write) -> io::Result<()> {
+    write_molfile(molecule, "", "", version, &mut writer)
+}
+
+/// Writes a record of an SDF file, including its data items and the closing
+/// `$$$$` line.  Records can be written one after another to the same writer.
+pub fn write_record(record: &Record, version: Version, mut writer: impl Write) -> io::Result<()> {
+    write_molfile(
+        &record.molecule,
+        &record.name,
+        &record.comment,
+        version,
+        &mut writer,
+    )?;
+    for (name, value) in record.data.iter() {
+        writeln!(writer, "> <{}>", name)?;
+        writeln!(writer, "{}", value)?;
+        writeln!(writer)?;
+    }
+    writeln!(writer, "$$$$")
+}
+
+// Writes properties block lines such as "M  CHG", each of which lists up to
+// eight pairs of atom numbers and values.
+fn write_atom_values(
+    writer: &mut impl Write,
+    kind: &str,
+    values: &[(usize, i32)],
+) -> io::Result<()> {
+    for chunk in values.chunks(8) {
+        write!(writer, "{}{:>3}", kind, chunk.len())?;
+        for (serial, value) in chunk {
+            write!(writer, " {:>3} {:>3}", serial, value)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn write_molfile(
+    molecule: &Molecule,
+    name: &str,
+    comment: &str,
+    version: Version,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let atoms: Vec<&Atom> = molecule.atoms().map(|(_, atom)| atom).collect();
+    let serials: HashMap<NodeIndex, usize> = molecule
+        .atoms()
+        .enumerate()
+        .map(|(index, (node, _))| (node, index + 1))
+        .collect();
+    let bonds: Vec<(usize, usize, BondOrder)> = molecule
+        .graph
+        .edge_indices()
+        .filter_map(|edge| {
+            let (a, b) = molecule.graph.edge_endpoints(edge)?;
+            Some((*serials.get(&a)?, *serials.get(&b)?, molecule.graph[edge]))
+        })
+        .collect();
+    let positions: Vec<Vec3> = molecule
+        .atoms()
+        .map(|(node, _)| molecule.graph[node].pos)
+        .collect();
+
+    writeln!(writer, "{}", name)?;
+    // The program name goes in columns 3-10, and the dimensions in 21-22
+    writeln!(writer, "  {:<8}{:10}3D", "atomCAD", "")?;
+    writeln!(writer, "{}", comment)?;
+
+    match version {
+        Version::V2000 => {
+            if atoms.len() > 999 || bonds.len() > 999 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "too many atoms or bonds for a V2000 molfile",
+                ));
+            }
+            writeln!(
+                writer,
+                "{:>3}{:>3}  0  0  0  0  0  0  0  0999 V2000",
+                atoms.len(),
+                bonds.len()
+            )?;
+            for (atom, pos) in atoms.iter().zip(positions.iter()) {
+                let charge_code = match atom.charge {
+                    charge @ (-3..=-1 | 1..=3) => 4 - charge,
+                    _ => 0,
+                };
+                writeln!(
+                    writer,
+                    "{:>10.4}{:>10.4}{:>10.4} {:<3} 0{:>3}  0  0  0  0  0  0  0  0  0  0",
+                    pos.x,
+                    pos.y,
+                    pos.z,
+                    atom.element.symbol(),
+                    charge_code
+                )?;
+            }
+            for (a, b, order) in bonds.iter() {
+                writeln!(writer, "{:>3}{:>3}{:>3}  0  0  0  0", a, b, order)?;
+            }
+
+            let charges: Vec<(usize, i32)> = atoms
+                .iter()
+                .enumerate()
+                .filter(|(_, atom)| atom.charge != 0)
+                .map(|(index, atom)| (index + 1, atom.charge as i32))
+                .collect();
+            write_atom_values(writer, "M  CHG", &charges)?;
+            let isotopes: Vec<(usize, i32)> = atoms
+                .iter()
+                .enumerate()
+                .filter_map(|(index, atom)| Some((index + 1, atom.isotope? as i32)))
+                .collect();
+            write_atom_values(writer, "M  ISO", &isotopes)?;
+        }
+        Version::V3000 => {
+            writeln!(writer, "  0  0  0  0  0  0  0  0  0  0999 V3000")?;
+            writeln!(writer, "M  V30 BEGIN CTAB")?;
+            writeln!(
+                writer,
+                "M  V30 COUNTS {} {} 0 0 0",
+                atoms.len(),
+                bonds.len()
+            )?;
+            writeln!(writer, "M  V30 BEGIN ATOM")?;
+            for (index, (atom, pos)) in atoms.iter().zip(positions.iter()).enumerate() {
+                write!(
+                    writer,
+                    "M  V30 {} {} {:.4} {:.4} {:.4} 0",
+                    index + 1,
+                    atom.element.symbol(),
+                    pos.x,
+                    pos.y,
+                    pos.z
+                )?;
+                if atom.charge != 0 {
+                    write!(writer, " CHG={}", atom.charge)?;
+                }
+                if let Some(mass_number) = atom.isotope {
+                    write!(writer, " MASS={}", mass_number)?;
+                }
+                writeln!(writer)?;
+            }
+            writeln!(writer, "M  V30 END ATOM")?;
+            writeln!(writer, "M  V30 BEGIN BOND")?;
+            for (index, (a, b, order)) in bonds.iter().enumerate() {
+                writeln!(writer, "M  V30 {} {} {} {}", index + 1, order, a, b)?;
+            }
+            writeln!(writer, "M  V30 END BOND")?;
+            writeln!(writer, "M  V30 END CTAB")?;
+        }
+    }
+
+    writeln!(writer, "M  END")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Acetate labelled with carbon-13, without its hydrogens
+    const ACETATE_V2000: &str = "\
+acetate
+  atomCAD           3D
+a comment
+  4  3  0  0  0  0  0  0  0  0999 V2000
+    0.0000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    1.5200    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    2.1500    1.0800    0.0000 O   0  0  0  0  0  0  0  0  0  0  0  0
+    2.1500   -1.0800    0.0000 O   0  5  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0  0  0  0
+  2  3  2  0  0  0  0
+  2  4  1  0  0  0  0
+M  CHG  1   4  -1
+M  ISO  1   1  13
+M  END
+";
+
+    const ACETATE_V3000: &str = "\
+acetate
+  atomCAD           3D
+
+  0  0  0     0  0            999 V3000
+M  V30 BEGIN CTAB
+M  V30 COUNTS 4 3 0 0 0
+M  V30 BEGIN ATOM
+M  V30 1 C 0 0 0 0 MASS=13
+M  V30 2 C 1.52 0 0 0
+M  V30 3 O 2.15 1.08 0 0
+M  V30 4 O 2.15 -1.08 0 0 -
+M  V30 CHG=-1
+M  V30 END ATOM
+M  V30 BEGIN BOND
+M  V30 1 1 1 2
+M  V30 2 2 2 3
+M  V30 3 1 2 4
+M  V30 END BOND
+M  V30 END CTAB
+M  END
+";
+
+    fn bond_order(molecule: &Molecule, a: NodeIndex, b: NodeIndex) -> Option<BondOrder> {
+        molecule
+            .bonds(a)
+            .find(|&(neighbor, _)| neighbor == b)
+            .map(|(_, order)| order)
+    }
+
+    fn check_acetate(molecule: &Molecule) {
+        let atoms: Vec<_> = molecule
+            .atoms()
+            .map(|(node, atom)| (node, atom.clone()))
+            .collect();
+        let elements: Vec<_> = atoms.iter().map(|(_, atom)| atom.element).collect();
+        assert_eq!(
+            elements,
+            vec![
+                Element::Carbon,
+                Element::Carbon,
+                Element::Oxygen,
+                Element::Oxygen
+            ]
+        );
+        let charges: Vec<_> = atoms.iter().map(|(_, atom)| atom.charge).collect();
+        assert_eq!(charges, vec![0, 0, 0, -1]);
+        let isotopes: Vec<_> = atoms.iter().map(|(_, atom)| atom.isotope).collect();
+        assert_eq!(isotopes, vec![Some(13), None, None, None]);
+
+        let nodes: Vec<_> = atoms.iter().map(|&(node, _)| node).collect();
+        assert_eq!(bond_order(molecule, nodes[1], nodes[2]), Some(2));
+        assert_eq!(bond_order(molecule, nodes[1], nodes[3]), Some(1));
+        assert!(molecule.graph[nodes[2]]
+            .pos
+            .abs_diff_eq(Vec3::new(2.15, 1.08, 0.0), 1e-4));
+
+        // The methyl carbon is missing three hydrogens, and the charged
+        // oxygen needs no more bonds
+        assert_eq!(molecule.bonding_sites(nodes[0]).count(), 3);
+        assert_eq!(molecule.bonding_sites(nodes[1]).count(), 0);
+        assert_eq!(molecule.bonding_sites(nodes[3]).count(), 0);
+    }
+
+    #[test]
+    fn read_v2000() {
+        let record = read(ACETATE_V2000.as_bytes()).unwrap();
+        assert_eq!(record.name, "acetate");
+        assert_eq!(record.comment, "a comment");
+        assert!(record.data.is_empty());
+        check_acetate(&record.molecule);
+    }
+
+    #[test]
+    fn read_v3000() {
+        let record = read(ACETATE_V3000.as_bytes()).unwrap();
+        check_acetate(&record.molecule);
+    }
+
+    #[test]
+    fn read_sdf() {
+        let sdf = format!(
+            "{}> <ID>\n42\n\n> <NOTES> (1)\nfirst line\nsecond line\n\n$$$$\n{}$$$$\n",
+            ACETATE_V2000, ACETATE_V3000
+        );
+        let records: Vec<Record> = records(sdf.as_bytes()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].data,
+            vec![
+                ("ID".to_owned(), "42".to_owned()),
+                ("NOTES".to_owned(), "first line\nsecond line".to_owned()),
+            ]
+        );
+        check_acetate(&records[1].molecule);
+    }
+
+    #[test]
+    fn errors() {
+        let bad_bond = ACETATE_V2000.replace("  2  4  1", "  2  9  1");
+        let err = read(bad_bond.as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 11, .. })));
+        let bad_element = ACETATE_V2000.replace("O   0  5", "Q   0  5");
+        let err = read(bad_element.as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 8, .. })));
+        let bad_isotope = ACETATE_V2000.replace("M  ISO  1   1", "M  ISO  1   7");
+        let err = read(bad_isotope.as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 13, .. })));
+        // Mass numbers below the atomic number, which would leave the atom
+        // with little or no mass
+        let light_isotope = ACETATE_V2000.replace("M  ISO  1   1  13", "M  ISO  1   1   0");
+        let err = read(light_isotope.as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 13, .. })));
+        let light_isotope = ACETATE_V3000.replace("MASS=13", "MASS=5");
+        let err = read(light_isotope.as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 8, .. })));
+        let truncated = &ACETATE_V3000[..ACETATE_V3000.find("M  V30 END BOND").unwrap()];
+        assert!(read(truncated.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let record = read(ACETATE_V2000.as_bytes()).unwrap();
+        let record = Record {
+            data: vec![("ID".to_owned(), "42".to_owned())],
+            ..record
+        };
+        for version in [Version::V2000, Version::V3000] {
+            let mut sdf = Vec::new();
+            write_record(&record, version, &mut sdf).unwrap();
+            write_record(&record, version, &mut sdf).unwrap();
+            let copies: Vec<Record> = records(sdf.as_slice())
+                .collect::<Result<_, _>>()
+                .unwrap_or_else(|err| panic!("{}\n{}", err, String::from_utf8_lossy(&sdf)));
+            assert_eq!(copies.len(), 2);
+            for copy in copies {
+                assert_eq!(copy.name, record.name);
+                assert_eq!(copy.comment, record.comment);
+                assert_eq!(copy.data, record.data);
+                check_acetate(&copy.molecule);
+            }
+        }
+
+        let mut molfile = Vec::new();
+        write(&record.molecule, Version::V2000, &mut molfile).unwrap();
+        check_acetate(&read(molfile.as_slice()).unwrap().molecule);
+    }
+}
+
+// End of File
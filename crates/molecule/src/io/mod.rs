@@ -0,0 +1,66 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Reading and writing molecules in the file formats used by other chemistry
+//! software.
+
+pub mod mdl;
+pub mod pdb;
+pub mod xyz;
+
+use std::fmt;
+
+/// An error encountered while reading a molecule from a file.
+#[derive(Debug)]
+pub enum ReadError {
+    Io(std::io::Error),
+    /// The file is malformed.  `line` is the 1-based line number at which the
+    /// problem was found.
+    Parse {
+        line: usize,
+        message: String,
+    },
+}
+
+impl ReadError {
+    pub(crate) fn parse(line: usize, message: impl Into<String>) -> Self {
+        ReadError::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(err) => write!(f, "{}", err),
+            ReadError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(err) => Some(err),
+            ReadError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ReadError {
+    fn from(err: std::io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+// The text in the given (1-based, inclusive) columns of a fixed-width record.
+// Columns past the end of the line are treated as blank.
+pub(crate) fn columns(line: &str, first: usize, last: usize) -> &str {
+    let end = last.min(line.len());
+    line.get(first - 1..end).unwrap_or("")
+}
+
+// End of File
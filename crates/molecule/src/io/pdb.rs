@@ -0,0 +1,288 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The Protein Data Bank (PDB) format.  Atoms are read from ATOM and HETATM
+//! records and bonds from CONECT records, where a bond of order n is listed n
+//! times.  Only the first model of a multi-model file is read.  Bonding sites
+//! are not stored in the file; they are added to imported atoms wherever
+//! their valence is not satisfied.
+
+use super::{columns, ReadError};
+use crate::{Atom, MolNode, Molecule, Particle};
+use glam::Vec3;
+use periodic_table::Element;
+use petgraph::stable_graph::NodeIndex;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufRead, Write};
+
+fn parse_serial(line: &str, first: usize, last: usize, number: usize) -> Result<u32, ReadError> {
+    let field = columns(line, first, last).trim();
+    field
+        .parse()
+        .map_err(|_| ReadError::parse(number, format!("invalid atom serial number {:?}", field)))
+}
+
+fn parse_coordinate(
+    line: &str,
+    first: usize,
+    last: usize,
+    number: usize,
+) -> Result<f32, ReadError> {
+    let field = columns(line, first, last).trim();
+    field
+        .parse()
+        .map_err(|_| ReadError::parse(number, format!("invalid coordinate {:?}", field)))
+}
+
+// The element of an ATOM or HETATM record.  Older files leave the element
+// columns blank, in which case it is taken from the atom name: by convention
+// two-letter symbols start in column 13, and one-letter symbols in column 14.
+fn parse_element(line: &str, number: usize) -> Result<Element, ReadError> {
+    let symbol = columns(line, 77, 78).trim();
+    if !symbol.is_empty() {
+        return symbol
+            .parse::<Element>()
+            .map_err(|err| ReadError::parse(number, err.to_string()));
+    }
+
+    let name = columns(line, 13, 16);
+    let letters: String = name
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    let two_letter = (!name.starts_with(' ') && letters.len() >= 2)
+        .then(|| Element::from_symbol(&letters[..2]))
+        .flatten();
+    two_letter
+        .or_else(|| Element::from_symbol(letters.get(..1)?))
+        .ok_or_else(|| {
+            ReadError::parse(
+                number,
+                format!("no element for atom name {:?}", name.trim()),
+            )
+        })
+}
+
+/// Reads the first model of a PDB file.
+pub fn read(reader: impl BufRead) -> Result<Molecule, ReadError> {
+    let mut molecule = Molecule::new();
+    let mut atoms = HashMap::<u32, NodeIndex>::new();
+    let mut atom_nodes = Vec::new();
+    // How many times each (atom, neighbour) pair appears in CONECT records,
+    // and the line on which it first appeared.  The pairs are kept in order,
+    // so that bonds (and so bonding sites) are added the same way every time.
+    let mut connections = BTreeMap::<(u32, u32), (u8, usize)>::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let number = index + 1;
+
+        match columns(&line, 1, 6).trim_end() {
+            "ATOM" | "HETATM" => {
+                let serial = parse_serial(&line, 7, 11, number)?;
+                let pos = Vec3::new(
+                    parse_coordinate(&line, 31, 38, number)?,
+                    parse_coordinate(&line, 39, 46, number)?,
+                    parse_coordinate(&line, 47, 54, number)?,
+                );
+                let element = parse_element(&line, number)?;
+                let node = molecule.graph.add_node(MolNode {
+                    pos,
+                    vel: Vec3::ZERO,
+                    particle: Particle::Atom(Atom::new(element)),
+                });
+                atoms.insert(serial, node);
+                atom_nodes.push(node);
+            }
+            "CONECT" => {
+                let atom = parse_serial(&line, 7, 11, number)?;
+                for first in [12, 17, 22, 27] {
+                    if columns(&line, first, first + 4).trim().is_empty() {
+                        continue;
+                    }
+                    let neighbor = parse_serial(&line, first, first + 4, number)?;
+                    let entry = connections.entry((atom, neighbor)).or_insert((0, number));
+                    entry.0 = entry.0.saturating_add(1);
+                }
+            }
+            "ENDMDL" | "END" => break,
+            _ => {}
+        }
+    }
+
+    for (&(a, b), &(count, number)) in connections.iter() {
+        if a == b {
+            return Err(ReadError::parse(
+                number,
+                format!("atom {} is bonded to itself", a),
+            ));
+        }
+        let (Some(&node_a), Some(&node_b)) = (atoms.get(&a), atoms.get(&b)) else {
+            return Err(ReadError::parse(
+                number,
+                format!("bond between unknown atoms {} and {}", a, b),
+            ));
+        };
+        // Bonds are usually listed from both ends; take whichever end lists
+        // the bond the most times.
+        let reverse_count = connections.get(&(b, a)).map_or(0, |&(count, _)| count);
+        let order = count.max(reverse_count).min(3);
+        molecule.add_bond(node_a, node_b, order);
+    }
+
+    for atom in atom_nodes {
+        molecule.add_bonding_sites(atom);
+    }
+
+    Ok(molecule)
+}
+
+/// Writes the atoms and bonds of a molecule as a PDB file.  Bonding sites are
+/// not written.
+pub fn write(molecule: &Molecule, mut writer: impl Write) -> io::Result<()> {
+    let mut serials = HashMap::<NodeIndex, usize>::new();
+    for (index, (node, atom)) in molecule.atoms().enumerate() {
+        let serial = index + 1;
+        serials.insert(node, serial);
+
+        // Atom names are an element symbol followed by a number, aligned so
+        // that the symbol starts in column 13 if it has two letters and in
+        // column 14 otherwise.
+        let symbol = atom.element.symbol().to_uppercase();
+        let mut name = format!("{}{}", symbol, serial);
+        if symbol.len() == 1 {
+            name.insert(0, ' ');
+        }
+        name.truncate(4);
+
+        let pos = molecule.graph[node].pos;
+        writeln!(
+            writer,
+            // All atoms belong to residue 1 of chain A, of the unknown ligand
+            // type UNL, with an occupancy of 1 and a temperature factor of 0.
+            "HETATM{:>5} {:<4} UNL A   1    {:>8.3}{:>8.3}{:>8.3}  1.00  0.00          {:>2}",
+            serial % 100000,
+            name,
+            pos.x,
+            pos.y,
+            pos.z,
+            symbol
+        )?;
+    }
+
+    for (node, _) in molecule.atoms() {
+        let mut neighbors = Vec::new();
+        for (neighbor, order) in molecule.bonds(node) {
+            for _ in 0..order {
+                neighbors.push(serials[&neighbor]);
+            }
+        }
+        // Each CONECT record lists up to four bonded atoms
+        for chunk in neighbors.chunks(4) {
+            write!(writer, "CONECT{:>5}", serials[&node] % 100000)?;
+            for neighbor in chunk {
+                write!(writer, "{:>5}", neighbor % 100000)?;
+            }
+            writeln!(writer)?;
+        }
+    }
+
+    writeln!(writer, "END")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BondOrder;
+
+    const ETHENE: &str = "\
+HETATM    1  C1  UNL A   1       0.000   0.000   0.000  1.00  0.00           C
+HETATM    2  C2  UNL A   1       1.330   0.000   0.000  1.00  0.00           C
+HETATM    3 CL3  UNL A   1      -0.900   1.500   0.000  1.00  0.00          CL
+CONECT    1    2    2    3
+CONECT    2    1    1
+CONECT    3    1
+END
+";
+
+    fn bond_order(molecule: &Molecule, a: NodeIndex, b: NodeIndex) -> Option<BondOrder> {
+        molecule
+            .bonds(a)
+            .find(|&(neighbor, _)| neighbor == b)
+            .map(|(_, order)| order)
+    }
+
+    #[test]
+    fn read_atoms_and_bonds() {
+        let molecule = read(ETHENE.as_bytes()).unwrap();
+        let atoms: Vec<_> = molecule
+            .atoms()
+            .map(|(node, atom)| (node, atom.element))
+            .collect();
+        assert_eq!(
+            atoms
+                .iter()
+                .map(|&(_, element)| element)
+                .collect::<Vec<_>>(),
+            vec![Element::Carbon, Element::Carbon, Element::Chlorine]
+        );
+        let (c1, c2, cl) = (atoms[0].0, atoms[1].0, atoms[2].0);
+        assert_eq!(bond_order(&molecule, c1, c2), Some(2));
+        assert_eq!(bond_order(&molecule, c1, cl), Some(1));
+        assert_eq!(molecule.graph[c2].pos, Vec3::new(1.33, 0.0, 0.0));
+
+        // The sp2 carbons are missing one and two hydrogens respectively,
+        // while the chlorine's valence is satisfied.
+        assert_eq!(molecule.bonding_sites(c1).count(), 1);
+        assert_eq!(molecule.bonding_sites(c2).count(), 2);
+        assert_eq!(molecule.bonding_sites(cl).count(), 0);
+    }
+
+    #[test]
+    fn element_from_atom_name() {
+        let pdb = "\
+ATOM      1  CA  GLY A   1       0.000   0.000   0.000
+ATOM      2 FE   HEM A   2       1.000   0.000   0.000
+";
+        let molecule = read(pdb.as_bytes()).unwrap();
+        let elements: Vec<_> = molecule.atoms().map(|(_, atom)| atom.element).collect();
+        assert_eq!(elements, vec![Element::Carbon, Element::Iron]);
+    }
+
+    #[test]
+    fn errors() {
+        let err = read("HETATM    1  C1  UNL A   1       0.000   xxx     0.000\n".as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 1, .. })));
+        let err = read(
+            "HETATM    1  C1  UNL A   1       0.000   0.000   0.000\nCONECT    1    7\n".as_bytes(),
+        );
+        assert!(matches!(err, Err(ReadError::Parse { line: 2, .. })));
+    }
+
+    #[test]
+    fn round_trip() {
+        let molecule = read(ETHENE.as_bytes()).unwrap();
+        let mut pdb = Vec::new();
+        write(&molecule, &mut pdb).unwrap();
+        let copy = read(pdb.as_slice()).unwrap();
+
+        let original: Vec<_> = molecule.atoms().collect();
+        let copied: Vec<_> = copy.atoms().collect();
+        assert_eq!(original.len(), copied.len());
+        for (&(a, atom_a), &(b, atom_b)) in original.iter().zip(copied.iter()) {
+            assert_eq!(atom_a.element, atom_b.element);
+            assert!(molecule.graph[a].pos.abs_diff_eq(copy.graph[b].pos, 1e-3));
+        }
+        assert_eq!(
+            bond_order(&copy, copied[0].0, copied[1].0),
+            Some(2),
+            "{}",
+            String::from_utf8_lossy(&pdb)
+        );
+        assert_eq!(copy.graph.node_count(), molecule.graph.node_count());
+    }
+}
+
+// End of File
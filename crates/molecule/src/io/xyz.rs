@@ -0,0 +1,639 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The XYZ format and its extended form (extended XYZ).  A file is a sequence
+//! of frames, each made up of a line with the number of atoms, a comment line,
+//! and then one line per atom with its element and position.  In extended XYZ
+//! the comment line holds key=value pairs: `Lattice` gives the nine components
+//! of the cell vectors, and `Properties` describes the per-atom columns as
+//! name:type:count triples.
+//!
+//! The files contain no bonds, so these are perceived from the positions in
+//! the first frame.  The lattice of the first frame becomes the unit cell of
+//! the molecule.  All frames must list the same elements in the same
+//! order; the later frames are returned as a trajectory.
+
+use super::ReadError;
+use crate::cell::UnitCell;
+use crate::perception::perceive_bonds;
+use crate::trajectory::{Frame, Property, PropertyValue, Trajectory};
+use crate::{Atom, MolNode, Molecule, Particle};
+use glam::{Mat3, Vec3};
+use periodic_table::Element;
+use std::io::{self, BufRead, Write};
+
+// A column of the per-atom lines of an extended XYZ frame
+#[derive(Clone, Debug, PartialEq)]
+struct Column {
+    name: String,
+    kind: char,
+    count: usize,
+}
+
+// The columns of a plain XYZ file
+fn default_columns() -> Vec<Column> {
+    vec![
+        Column {
+            name: "species".to_owned(),
+            kind: 'S',
+            count: 1,
+        },
+        Column {
+            name: "pos".to_owned(),
+            kind: 'R',
+            count: 3,
+        },
+    ]
+}
+
+// Reads one key or value from an extended XYZ comment line.  Values may be
+// quoted with double quotes (in which `\"` is a literal quote) or braces.
+fn read_token(chars: &mut std::iter::Peekable<std::str::Chars>, is_key: bool) -> Option<String> {
+    let mut token = String::new();
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => token.push(chars.next()?),
+                    c => token.push(c),
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '}' => break,
+                    c => token.push(c),
+                }
+            }
+        }
+        _ => {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || (is_key && c == '=') {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+        }
+    }
+    Some(token)
+}
+
+// Splits an extended XYZ comment line into key=value pairs.  A key without a
+// value is a flag, and is given the value "T".  Returns `None` if a quoted
+// value is not terminated.
+fn parse_key_values(line: &str) -> Option<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Some(pairs);
+        }
+        let key = read_token(&mut chars, true)?;
+        let value = if chars.next_if_eq(&'=').is_some() {
+            read_token(&mut chars, false)?
+        } else {
+            "T".to_owned()
+        };
+        pairs.push((key, value));
+    }
+}
+
+fn parse_lattice(value: &str) -> Option<Mat3> {
+    let components = value
+        .split_whitespace()
+        .map(|component| component.parse().ok())
+        .collect::<Option<Vec<f32>>>()?;
+    // The components are listed one cell vector at a time, which is the
+    // column-major order that glam expects.
+    (components.len() == 9).then(|| Mat3::from_cols_slice(&components))
+}
+
+fn parse_columns(value: &str) -> Result<Vec<Column>, String> {
+    let fields: Vec<&str> = value.split(':').collect();
+    if !fields.chunks_exact(3).remainder().is_empty() {
+        return Err(format!("malformed Properties {:?}", value));
+    }
+    let mut columns = Vec::new();
+    for field in fields.chunks(3) {
+        let kind = match field[1] {
+            "S" | "R" | "I" | "L" => field[1].chars().next().unwrap(),
+            kind => return Err(format!("unknown property type {:?}", kind)),
+        };
+        let count = field[2]
+            .parse()
+            .map_err(|_| format!("invalid property column count {:?}", field[2]))?;
+        columns.push(Column {
+            name: field[0].to_owned(),
+            kind,
+            count,
+        });
+    }
+    if !columns.iter().any(|column| column.name == "species") {
+        return Err("Properties has no species column".to_owned());
+    }
+    if !columns
+        .iter()
+        .any(|column| column.name == "pos" && column.kind == 'R' && column.count == 3)
+    {
+        return Err("Properties has no pos:R:3 column".to_owned());
+    }
+    Ok(columns)
+}
+
+// The comment line of a frame
+struct Header {
+    columns: Vec<Column>,
+    lattice: Option<Mat3>,
+    info: Vec<(String, String)>,
+}
+
+fn parse_header(comment: &str) -> Result<Header, String> {
+    let mut header = Header {
+        columns: default_columns(),
+        lattice: None,
+        info: Vec::new(),
+    };
+
+    // A plain XYZ comment is free text, which is kept as it is
+    let pairs = match parse_key_values(comment) {
+        Some(pairs) if comment.contains('=') => pairs,
+        _ => {
+            if !comment.trim().is_empty() {
+                header
+                    .info
+                    .push(("comment".to_owned(), comment.trim().to_owned()));
+            }
+            return Ok(header);
+        }
+    };
+
+    for (key, value) in pairs {
+        if key.eq_ignore_ascii_case("Lattice") {
+            header.lattice =
+                Some(parse_lattice(&value).ok_or_else(|| format!("invalid Lattice {:?}", value))?);
+        } else if key.eq_ignore_ascii_case("Properties") {
+            header.columns = parse_columns(&value)?;
+        } else {
+            header.info.push((key, value));
+        }
+    }
+    Ok(header)
+}
+
+fn parse_species(field: &str) -> Option<Element> {
+    match field.parse::<u8>() {
+        Ok(number) => Element::from_atomic_number(number),
+        Err(_) => field.parse().ok(),
+    }
+}
+
+fn parse_value(field: &str, kind: char) -> Option<PropertyValue> {
+    match kind {
+        'S' => Some(PropertyValue::String(field.to_owned())),
+        'R' => field.parse().ok().map(PropertyValue::Real),
+        'I' => field.parse().ok().map(PropertyValue::Integer),
+        'L' => match field {
+            "T" | "True" | "true" => Some(PropertyValue::Logical(true)),
+            "F" | "False" | "false" => Some(PropertyValue::Logical(false)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// A frame as it is read, before it is checked against the first frame
+struct RawFrame {
+    line: usize,
+    elements: Vec<Element>,
+    frame: Frame,
+}
+
+fn next_line(
+    lines: &mut impl Iterator<Item = io::Result<String>>,
+    number: &mut usize,
+) -> Result<Option<String>, ReadError> {
+    *number += 1;
+    Ok(lines.next().transpose()?)
+}
+
+fn read_frame(
+    lines: &mut impl Iterator<Item = io::Result<String>>,
+    number: &mut usize,
+) -> Result<Option<RawFrame>, ReadError> {
+    // Skip any blank lines between frames and at the end of the file
+    let count_line = loop {
+        match next_line(lines, number)? {
+            None => return Ok(None),
+            Some(line) if line.trim().is_empty() => continue,
+            Some(line) => break line,
+        }
+    };
+    let first_line = *number;
+    let count: usize = count_line.trim().parse().map_err(|_| {
+        ReadError::parse(
+            first_line,
+            format!("invalid atom count {:?}", count_line.trim()),
+        )
+    })?;
+
+    let comment = next_line(lines, number)?
+        .ok_or_else(|| ReadError::parse(*number, "missing comment line"))?;
+    let header = parse_header(&comment).map_err(|message| ReadError::parse(*number, message))?;
+
+    // Nothing is preallocated from the counts in the file, which may be
+    // nonsense
+    let mut elements = Vec::new();
+    let mut positions = Vec::new();
+    let mut properties: Vec<Property> = header
+        .columns
+        .iter()
+        .filter(|column| column.name != "species" && column.name != "pos")
+        .map(|column| Property {
+            name: column.name.clone(),
+            columns: column.count,
+            values: Vec::new(),
+        })
+        .collect();
+
+    for _ in 0..count {
+        let line = next_line(lines, number)?.ok_or_else(|| {
+            ReadError::parse(*number, format!("expected {} atoms in frame", count))
+        })?;
+        let mut fields = line.split_whitespace();
+        let mut property_index = 0;
+        for column in header.columns.iter() {
+            let mut values = Vec::new();
+            for _ in 0..column.count {
+                let field = fields.next().ok_or_else(|| {
+                    ReadError::parse(*number, format!("missing value for {}", column.name))
+                })?;
+                let value = parse_value(field, column.kind).ok_or_else(|| {
+                    ReadError::parse(
+                        *number,
+                        format!("invalid value {:?} for {}", field, column.name),
+                    )
+                })?;
+                values.push(value);
+            }
+
+            match column.name.as_str() {
+                "species" => {
+                    let element = match &values[0] {
+                        PropertyValue::String(field) => parse_species(field),
+                        PropertyValue::Integer(number) => u8::try_from(*number)
+                            .ok()
+                            .and_then(Element::from_atomic_number),
+                        _ => None,
+                    }
+                    .ok_or_else(|| ReadError::parse(*number, "unknown element"))?;
+                    elements.push(element);
+                }
+                "pos" => {
+                    // The column was checked to hold three reals
+                    let mut pos = [0.0; 3];
+                    for (component, value) in pos.iter_mut().zip(values) {
+                        if let PropertyValue::Real(value) = value {
+                            *component = value as f32;
+                        }
+                    }
+                    positions.push(Vec3::from_array(pos));
+                }
+                _ => {
+                    properties[property_index].values.extend(values);
+                    property_index += 1;
+                }
+            }
+        }
+    }
+
+    Ok(Some(RawFrame {
+        line: first_line,
+        elements,
+        frame: Frame {
+            positions,
+            lattice: header.lattice,
+            properties,
+            info: header.info,
+        },
+    }))
+}
+
+/// Reads an XYZ or extended XYZ file.  The molecule is built from the first
+/// frame, and every frame (including the first) is returned in the
+/// trajectory.  Velocities are taken from a `vel` property if there is one.
+pub fn read(reader: impl BufRead) -> Result<(Molecule, Trajectory), ReadError> {
+    let mut lines = reader.lines();
+    let mut number = 0;
+    let mut molecule = Molecule::new();
+    let mut trajectory = Trajectory::default();
+    let mut elements = Vec::new();
+
+    while let Some(raw) = read_frame(&mut lines, &mut number)? {
+        if trajectory.is_empty() {
+            let velocities = raw
+                .frame
+                .property("vel")
+                .and_then(Property::as_vectors)
+                .unwrap_or_else(|| vec![Vec3::ZERO; raw.elements.len()]);
+            for ((&element, &pos), &vel) in raw
+                .elements
+                .iter()
+                .zip(raw.frame.positions.iter())
+                .zip(velocities.iter())
+            {
+                let node = molecule.graph.add_node(MolNode {
+                    pos,
+                    vel,
+                    particle: Particle::Atom(Atom::new(element)),
+                });
+                trajectory.atoms.push(node);
+            }
+            elements = raw.elements;
+            molecule.cell = raw.frame.lattice.and_then(UnitCell::new);
+        } else if raw.elements != elements {
+            return Err(ReadError::parse(
+                raw.line,
+                "frame has different atoms from the first frame",
+            ));
+        }
+        trajectory.frames.push(raw.frame);
+    }
+
+    perceive_bonds(&mut molecule);
+    for &atom in trajectory.atoms.iter() {
+        molecule.add_bonding_sites(atom);
+    }
+
+    Ok((molecule, trajectory))
+}
+
+fn type_code(value: &PropertyValue) -> char {
+    match value {
+        PropertyValue::String(_) => 'S',
+        PropertyValue::Real(_) => 'R',
+        PropertyValue::Integer(_) => 'I',
+        PropertyValue::Logical(_) => 'L',
+    }
+}
+
+fn write_value(writer: &mut impl Write, value: &PropertyValue) -> io::Result<()> {
+    match value {
+        PropertyValue::String(value) => write!(writer, " {}", value),
+        PropertyValue::Real(value) => write!(writer, " {:>12.6}", value),
+        PropertyValue::Integer(value) => write!(writer, " {}", value),
+        PropertyValue::Logical(value) => write!(writer, " {}", if *value { "T" } else { "F" }),
+    }
+}
+
+// Quotes a value of the comment line if it would not otherwise be read back
+// as a single token
+fn quote(value: &str) -> String {
+    if !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '=' || c == '{')
+    {
+        return value.to_owned();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn write_frame(writer: &mut impl Write, elements: &[Element], frame: &Frame) -> io::Result<()> {
+    writeln!(writer, "{}", elements.len())?;
+
+    if let Some(lattice) = frame.lattice {
+        let components: Vec<String> = lattice
+            .to_cols_array()
+            .iter()
+            .map(|component| component.to_string())
+            .collect();
+        write!(writer, "Lattice=\"{}\" ", components.join(" "))?;
+    }
+    write!(writer, "Properties=species:S:1:pos:R:3")?;
+    for property in frame.properties.iter() {
+        let kind = property.values.first().map_or('R', type_code);
+        write!(writer, ":{}:{}:{}", property.name, kind, property.columns)?;
+    }
+    for (key, value) in frame.info.iter() {
+        write!(writer, " {}={}", quote(key), quote(value))?;
+    }
+    writeln!(writer)?;
+
+    for (index, (element, pos)) in elements.iter().zip(frame.positions.iter()).enumerate() {
+        write!(
+            writer,
+            "{:<2} {:>12.6} {:>12.6} {:>12.6}",
+            element.symbol(),
+            pos.x,
+            pos.y,
+            pos.z
+        )?;
+        for property in frame.properties.iter() {
+            for value in property.get(index) {
+                write_value(writer, value)?;
+            }
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Writes the atoms of a molecule as a single extended XYZ frame.  Bonds and
+/// bonding sites are not written.
+pub fn write(molecule: &Molecule, mut writer: impl Write) -> io::Result<()> {
+    let (elements, positions) = molecule
+        .atoms()
+        .map(|(node, atom)| (atom.element, molecule.graph[node].pos))
+        .unzip::<_, _, Vec<_>, Vec<_>>();
+    let frame = Frame {
+        positions,
+        lattice: molecule.cell.map(|cell| cell.lattice()),
+        ..Default::default()
+    };
+    write_frame(&mut writer, &elements, &frame)
+}
+
+/// Writes every frame of a trajectory of `molecule` as extended XYZ.
+pub fn write_trajectory(
+    molecule: &Molecule,
+    trajectory: &Trajectory,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let elements: Vec<Element> = trajectory
+        .atoms
+        .iter()
+        .filter_map(|&node| match &molecule.graph.node_weight(node)?.particle {
+            Particle::Atom(atom) => Some(atom.element),
+            Particle::BondingSite => None,
+        })
+        .collect();
+    for frame in trajectory.frames.iter() {
+        write_frame(&mut writer, &elements, frame)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WATER: &str = "\
+3
+water
+O   0.000   0.000   0.117
+H   0.000   0.757  -0.467
+1   0.000  -0.757  -0.467
+";
+
+    const TRAJECTORY: &str = "\
+2
+Lattice=\"10.0 0.0 0.0 0.0 10.0 0.0 0.0 0.0 12.0\" Properties=species:S:1:pos:R:3:vel:R:3:fixed:L:1 step=0 label=\"two atoms\" pbc
+C 0.0 0.0 0.0 0.1 0.0 0.0 T
+C 1.5 0.0 0.0 -0.1 0.0 0.0 F
+2
+Lattice=\"10.0 0.0 0.0 0.0 10.0 0.0 0.0 0.0 12.0\" Properties=species:S:1:pos:R:3:vel:R:3:fixed:L:1 step=1
+C 0.0 0.0 0.0 0.0 0.0 0.0 T
+C 1.6 0.2 0.0 0.0 0.0 0.0 F
+
+";
+
+    #[test]
+    fn read_plain() {
+        let (molecule, trajectory) = read(WATER.as_bytes()).unwrap();
+        assert_eq!(trajectory.len(), 1);
+        assert_eq!(
+            trajectory.frames[0].info,
+            vec![("comment".to_owned(), "water".to_owned())]
+        );
+        let elements: Vec<_> = molecule.atoms().map(|(_, atom)| atom.element).collect();
+        assert_eq!(
+            elements,
+            vec![Element::Oxygen, Element::Hydrogen, Element::Hydrogen]
+        );
+
+        // Both hydrogens are bonded to the oxygen but not to each other, so
+        // every valence is satisfied
+        let oxygen = trajectory.atoms[0];
+        assert_eq!(molecule.bonds(oxygen).count(), 2);
+        assert_eq!(molecule.bonds(trajectory.atoms[1]).count(), 1);
+        assert_eq!(molecule.graph.node_count(), 3);
+    }
+
+    #[test]
+    fn read_extended() {
+        let (molecule, trajectory) = read(TRAJECTORY.as_bytes()).unwrap();
+        assert_eq!(trajectory.len(), 2);
+
+        let first = &trajectory.frames[0];
+        assert_eq!(
+            first.lattice,
+            Some(Mat3::from_diagonal(Vec3::new(10.0, 10.0, 12.0)))
+        );
+        assert_eq!(molecule.cell.map(|cell| cell.lattice()), first.lattice);
+        assert_eq!(
+            first.info,
+            vec![
+                ("step".to_owned(), "0".to_owned()),
+                ("label".to_owned(), "two atoms".to_owned()),
+                ("pbc".to_owned(), "T".to_owned()),
+            ]
+        );
+        let fixed = first.property("fixed").unwrap();
+        assert_eq!(fixed.get(1), &[PropertyValue::Logical(false)]);
+        assert_eq!(
+            molecule.graph[trajectory.atoms[0]].vel,
+            Vec3::new(0.1, 0.0, 0.0)
+        );
+
+        // The carbons are bonded, and each has three bonding sites
+        let (a, b) = (trajectory.atoms[0], trajectory.atoms[1]);
+        assert_eq!(molecule.bonds(a).collect::<Vec<_>>(), vec![(b, 1)]);
+        assert_eq!(molecule.bonding_sites(b).count(), 3);
+    }
+
+    #[test]
+    fn apply_frame() {
+        let (mut molecule, trajectory) = read(TRAJECTORY.as_bytes()).unwrap();
+        let atom = trajectory.atoms[1];
+        let site = molecule.bonding_sites(atom).next().unwrap();
+        let offset = molecule.graph[site].pos - molecule.graph[atom].pos;
+
+        trajectory.apply_frame(&mut molecule, 1);
+        assert_eq!(molecule.graph[atom].pos, Vec3::new(1.6, 0.2, 0.0));
+        assert!((molecule.graph[site].pos - molecule.graph[atom].pos).abs_diff_eq(offset, 1e-6));
+    }
+
+    #[test]
+    fn errors() {
+        let err = read("2\n\nC 0 0 0\n".as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 4, .. })));
+        let err = read("1\n\nQ 0 0 0\n".as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 3, .. })));
+        let err = read("1\nProperties=pos:R:3\n0 0 0\n".as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 2, .. })));
+        let err = read("1\n\nC 0 0 0\n1\n\nN 0 0 0\n".as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 4, .. })));
+        // Absurd counts are reported as missing atoms and values, rather than
+        // allocated up front
+        let err = read("99999999999\n\nC 0 0 0\n".as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 4, .. })));
+        let err =
+            read("1\nProperties=species:S:1:pos:R:3:charge:R:99999999999\nC 0 0 0 1\n".as_bytes());
+        assert!(matches!(err, Err(ReadError::Parse { line: 3, .. })));
+    }
+
+    #[test]
+    fn round_trip() {
+        let (molecule, trajectory) = read(TRAJECTORY.as_bytes()).unwrap();
+        let mut xyz = Vec::new();
+        write_trajectory(&molecule, &trajectory, &mut xyz).unwrap();
+        let (copy, copied) = read(xyz.as_slice()).unwrap();
+
+        assert_eq!(copy.graph.node_count(), molecule.graph.node_count());
+        assert_eq!(copied.len(), trajectory.len());
+        for (a, b) in trajectory.frames.iter().zip(copied.frames.iter()) {
+            assert_eq!(a.lattice, b.lattice);
+            assert_eq!(a.info, b.info);
+            assert_eq!(a.properties, b.properties);
+            for (p, q) in a.positions.iter().zip(b.positions.iter()) {
+                assert!(p.abs_diff_eq(*q, 1e-5));
+            }
+        }
+
+        let mut single = Vec::new();
+        write(&molecule, &mut single).unwrap();
+        let (copy, frames) = read(single.as_slice()).unwrap();
+        assert_eq!(frames.frames[0].positions, trajectory.frames[0].positions);
+        assert_eq!(copy.cell, molecule.cell);
+    }
+
+    #[test]
+    fn read_periodic() {
+        // Two carbons either side of a face of the cell, which are only
+        // bonded through it
+        let xyz = "\
+2
+Lattice=\"10.0 0.0 0.0 0.0 10.0 0.0 0.0 0.0 10.0\"
+C 0.4 5.0 5.0
+C 9.0 5.0 5.0
+";
+        let (molecule, trajectory) = read(xyz.as_bytes()).unwrap();
+        let (a, b) = (trajectory.atoms[0], trajectory.atoms[1]);
+        assert_eq!(molecule.bonds(a).collect::<Vec<_>>(), vec![(b, 1)]);
+        assert!((molecule.offset(a, b) - Vec3::new(-1.4, 0.0, 0.0)).length() < 1e-4);
+
+        // The bonding sites point away from the bond through the face
+        for site in molecule.bonding_sites(a) {
+            assert!(molecule.offset(a, site).x > 0.0);
+        }
+    }
+}
+
+// End of File
@@ -0,0 +1,680 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A headless model of molecules: atoms, the bonds between them, and the
+//! bonding sites where further atoms can be attached.  Nothing in this crate
+//! depends on a running application, so molecules can be built, edited and
+//! simulated from unit tests and command-line tools.  The atomCAD application
+//! mirrors each `Molecule` into the ECS for display.
+//!
+//! Positions are measured in angstroms.
+
+pub mod cell;
+pub mod dynamics;
+pub mod edit;
+#[doc(hidden)]
+pub mod fixtures;
+pub mod forcefield;
+pub mod io;
+pub mod minimize;
+pub mod perception;
+pub mod picking;
+pub mod representation;
+pub mod trajectory;
+pub mod vsepr;
+
+use forcefield::{ForceField, Parameterized};
+use glam::{Quat, Vec3};
+use periodic_table::Element;
+use petgraph::stable_graph::{EdgeIndex, NodeIndex, StableUnGraph};
+use petgraph::visit::EdgeRef;
+use std::f32::consts::PI;
+
+pub type BondOrder = u8;
+
+/// The furthest a particle moves in one step of `Molecule::relax_step`, in
+/// angstroms.
+pub const MAX_RELAX_STEP: f32 = 0.05;
+
+/// Describes how different particles in a molecule (including unbonded
+/// electrons and atoms) are connected using a stable undirected graph.  The
+/// edge weights represent integer bond order (1 indicates a single bond and so
+/// on).  If a node of the molecule graph is a `BondingSite`, it must have
+/// exactly one bond, and that bond must be to an `Atom`.
+pub type MolGraph = StableUnGraph<MolNode, BondOrder>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MolNode {
+    pub pos: Vec3,
+    pub vel: Vec3,
+    pub particle: Particle,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Particle {
+    Atom(Atom),
+    BondingSite,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Atom {
+    pub element: Element,
+    /// The formal charge of the atom, in units of the elementary charge.
+    pub charge: i8,
+    // The NodeIndex of the atom that this Atom points towards. If None,
+    // this atom's +z axis is aligned with the molecule's +z axis. If Some, the
+    // +z axis of this atom points from the atom's center to the center of the
+    // atom it is facing.
+    pub facing: Option<NodeIndex>,
+    /// The mass number of the atom's isotope, or `None` for the natural
+    /// mixture of isotopes.
+    pub isotope: Option<u16>,
+}
+
+impl Atom {
+    /// A neutral atom of the natural mixture of isotopes, facing +z.
+    pub fn new(element: Element) -> Self {
+        Atom {
+            element,
+            charge: 0,
+            facing: None,
+            isotope: None,
+        }
+    }
+
+    /// The mass of the atom in daltons: the mass of its isotope, or the
+    /// standard atomic weight of its element.  Isotopes missing from the
+    /// periodic table's isotope table are taken to weigh their mass number,
+    /// and impossible ones (lighter than the element's atomic number) are
+    /// ignored.
+    pub fn mass(&self) -> f32 {
+        let lightest = u16::from(self.element.atomic_number());
+        match self.isotope.filter(|&mass_number| mass_number >= lightest) {
+            Some(mass_number) => self
+                .element
+                .isotope(mass_number)
+                .map_or(mass_number as f32, |isotope| isotope.mass as f32),
+            None => self.element.atomic_mass(),
+        }
+    }
+}
+
+/// A single molecule, stored as a graph of particles.
+#[derive(Clone, Debug, Default)]
+pub struct Molecule {
+    pub graph: MolGraph,
+    /// The unit cell the molecule repeats in, if it is periodic.
+    pub cell: Option<cell::UnitCell>,
+}
+
+impl Molecule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an atom of the given element at `position`, surrounded by a
+    /// bonding site in each of the directions in `bonding_sites`.  The
+    /// directions are unit vectors relative to `up`, which becomes the atom's
+    /// +z axis.  If `skip_first_bonding_site` is set the first direction is
+    /// left empty, which is used when the atom is about to be bonded along its
+    /// +z axis.
+    pub fn add_atom(
+        &mut self,
+        element: Element,
+        position: Vec3,
+        up: Vec3,
+        facing: Option<NodeIndex>,
+        bonding_sites: &[Vec3],
+        skip_first_bonding_site: bool,
+    ) -> NodeIndex {
+        // Create a quaternion that will rotate from the global +z vector to the
+        // up vector
+        let up_rotation = Quat::from_rotation_arc(Vec3::Z, up);
+
+        let atom_node = self.graph.add_node(MolNode {
+            pos: position,
+            vel: Vec3::ZERO,
+            particle: Particle::Atom(Atom {
+                facing,
+                ..Atom::new(element)
+            }),
+        });
+
+        let mut direction_iter = bonding_sites.iter();
+        if skip_first_bonding_site {
+            direction_iter.next();
+        }
+
+        for direction in direction_iter {
+            let displacement = up_rotation * *direction;
+
+            let bonding_site_node = self.graph.add_node(MolNode {
+                pos: position + displacement,
+                vel: Vec3::ZERO,
+                particle: Particle::BondingSite,
+            });
+            self.graph.add_edge(atom_node, bonding_site_node, 1);
+        }
+
+        atom_node
+    }
+
+    /// Iterates over the atoms of the molecule (but not its bonding sites).
+    pub fn atoms(&self) -> impl Iterator<Item = (NodeIndex, &Atom)> + '_ {
+        self.graph
+            .node_indices()
+            .filter_map(|index| match &self.graph[index].particle {
+                Particle::Atom(atom) => Some((index, atom)),
+                Particle::BondingSite => None,
+            })
+    }
+
+    /// The total mass of the molecule's atoms, in daltons.
+    pub fn mass(&self) -> f32 {
+        self.atoms().map(|(_, atom)| atom.mass()).sum()
+    }
+
+    /// The mass-weighted mean position of the molecule's atoms, or `None` if
+    /// it has no atoms.
+    pub fn center_of_mass(&self) -> Option<Vec3> {
+        let mass = self.mass();
+        (mass > 0.0).then(|| {
+            self.atoms()
+                .map(|(node, atom)| self.graph[node].pos * atom.mass())
+                .sum::<Vec3>()
+                / mass
+        })
+    }
+
+    /// Iterates over the atoms bonded to `atom`, along with the order of each
+    /// bond.  Bonding sites are not included.
+    pub fn bonds(&self, atom: NodeIndex) -> impl Iterator<Item = (NodeIndex, BondOrder)> + '_ {
+        self.graph
+            .edges(atom)
+            .filter(|edge| matches!(self.graph[edge.target()].particle, Particle::Atom(_)))
+            .map(|edge| (edge.target(), *edge.weight()))
+    }
+
+    /// Iterates over the bonding sites of `atom`.
+    pub fn bonding_sites(&self, atom: NodeIndex) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.graph
+            .neighbors(atom)
+            .filter(|&neighbor| matches!(self.graph[neighbor].particle, Particle::BondingSite))
+    }
+
+    /// Bonds two atoms, or changes the order of the bond if they are already
+    /// bonded.
+    pub fn add_bond(&mut self, a: NodeIndex, b: NodeIndex, order: BondOrder) -> EdgeIndex {
+        self.graph.update_edge(a, b, order)
+    }
+
+    /// Adds bonding sites to an atom wherever its valence is not satisfied by
+    /// its bonds and existing bonding sites.  The new sites are placed in the
+    /// directions of the atom's VSEPR geometry which are not already taken by
+    /// a bond, a bonding site or a lone pair.
+    pub fn add_bonding_sites(&mut self, atom: NodeIndex) {
+        let Particle::Atom(Atom {
+            element, charge, ..
+        }) = self.graph[atom].particle
+        else {
+            return;
+        };
+        let position = self.graph[atom].pos;
+
+        let mut bond_orders = Vec::new();
+        let mut bond_directions = Vec::new();
+        for edge in self.graph.edges(atom) {
+            bond_orders.push(*edge.weight());
+            bond_directions.push(self.offset(atom, edge.target()).normalize());
+        }
+
+        let domains = vsepr::charged_domains(element, charge, &bond_orders);
+        for direction in vsepr::free_bond_directions(domains, &bond_directions) {
+            let bonding_site_node = self.graph.add_node(MolNode {
+                pos: position + direction,
+                vel: Vec3::ZERO,
+                particle: Particle::BondingSite,
+            });
+            self.graph.add_edge(atom, bonding_site_node, 1);
+        }
+    }
+
+    /// Returns the atom that a bonding site belongs to, or `None` if `site` is
+    /// not a bonding site of this molecule.
+    pub fn bonding_site_owner(&self, site: NodeIndex) -> Option<NodeIndex> {
+        match self.graph.node_weight(site)?.particle {
+            // Recall that we demand that all bonding sites have exactly one
+            // neighbor.
+            Particle::BondingSite => self.graph.neighbors(site).next(),
+            Particle::Atom(_) => None,
+        }
+    }
+
+    /// Replaces a bonding site with a new atom of the given element, single
+    /// bonded to the atom that owned the site.  The new atom's remaining
+    /// bonding sites are laid out according to VSEPR theory.  Returns the new
+    /// atom, or `None` if `site` is not a bonding site.
+    pub fn bond_atom_at_site(&mut self, site: NodeIndex, element: Element) -> Option<NodeIndex> {
+        let bond_target = self.bonding_site_owner(site)?;
+        let site_pos = self.graph.remove_node(site)?.pos;
+
+        // The bonding sites are displayed quite close to the atom - because the
+        // atoms are larger, we extend this displacement and place the new atom
+        // further than the bonding site was located from its parent
+        let displacement = site_pos - self.graph[bond_target].pos;
+        let new_atom_pos = site_pos + displacement.normalize() * 0.5;
+
+        // We want the +z axis of this new atom to point from its center towards
+        // the atom it's bonded to
+        let up = -displacement.normalize();
+
+        // The first bond direction is taken by the bond to the old atom
+        let atom_node = self.add_atom(
+            element,
+            new_atom_pos,
+            up,
+            Some(bond_target),
+            &vsepr::bond_directions(element, &[1]),
+            true,
+        );
+
+        // Add a single bond between the old atom and this atom:
+        self.graph.add_edge(atom_node, bond_target, 1);
+
+        Some(atom_node)
+    }
+
+    /// Removes an atom along with its bonding sites, returning the atom, or
+    /// `None` if `atom` is not an atom of this molecule.  Each atom it was
+    /// bonded to is given a bonding site in the direction of the removed atom,
+    /// along with any further sites needed to satisfy its valence.
+    pub fn remove_atom(&mut self, atom: NodeIndex) -> Option<Atom> {
+        if !matches!(self.graph.node_weight(atom)?.particle, Particle::Atom(_)) {
+            return None;
+        }
+        // The direction of the removed atom from each of its neighbours
+        let neighbors: Vec<_> = self
+            .bonds(atom)
+            .map(|(neighbor, _)| (neighbor, self.offset(neighbor, atom)))
+            .collect();
+        let sites: Vec<_> = self.bonding_sites(atom).collect();
+        for site in sites {
+            self.graph.remove_node(site);
+        }
+        let removed = match self.graph.remove_node(atom)?.particle {
+            Particle::Atom(atom) => atom,
+            Particle::BondingSite => unreachable!(),
+        };
+
+        // The index of the removed atom may be reused, so nothing should keep
+        // facing it
+        for node in self.graph.node_weights_mut() {
+            if let Particle::Atom(other) = &mut node.particle {
+                if other.facing == Some(atom) {
+                    other.facing = None;
+                }
+            }
+        }
+
+        for (neighbor, offset) in neighbors {
+            self.restore_bonding_site(neighbor, offset);
+        }
+        Some(removed)
+    }
+
+    /// Removes the bond between two atoms, returning its order, or `None` if
+    /// they are not bonded atoms.  Each atom is given a bonding site where the
+    /// bond was, along with any further sites needed to satisfy its valence.
+    pub fn remove_bond(&mut self, a: NodeIndex, b: NodeIndex) -> Option<BondOrder> {
+        for node in [a, b] {
+            if !matches!(self.graph.node_weight(node)?.particle, Particle::Atom(_)) {
+                return None;
+            }
+        }
+        let order = self.graph.remove_edge(self.graph.find_edge(a, b)?)?;
+        let offset = self.offset(a, b);
+        self.restore_bonding_site(a, offset);
+        self.restore_bonding_site(b, -offset);
+        Some(order)
+    }
+
+    /// Changes the order of the bond between two atoms, returning its previous
+    /// order.  Raising the order uses up a bonding site of each atom per step,
+    /// and lowering it gives the sites back; the sites of both atoms are then
+    /// laid out afresh for their new VSEPR geometry, so that e.g. a carbon
+    /// atom goes from tetrahedral to trigonal planar when it gains a double
+    /// bond.  Where they can be, the ends of a double bond are turned to lie
+    /// in one plane.  Returns `None`, leaving the molecule unchanged, if the order is
+    /// not between 1 and 3, the atoms are not bonded, or one of them has too
+    /// few bonding sites left.
+    pub fn set_bond_order(
+        &mut self,
+        a: NodeIndex,
+        b: NodeIndex,
+        order: BondOrder,
+    ) -> Option<BondOrder> {
+        if !(1..=3).contains(&order) {
+            return None;
+        }
+        for node in [a, b] {
+            if !matches!(self.graph.node_weight(node)?.particle, Particle::Atom(_)) {
+                return None;
+            }
+        }
+        let edge = self.graph.find_edge(a, b)?;
+        let previous = self.graph[edge];
+        let needed = order.saturating_sub(previous) as usize;
+        if self.bonding_sites(a).count() < needed || self.bonding_sites(b).count() < needed {
+            return None;
+        }
+
+        self.graph[edge] = order;
+        for atom in [a, b] {
+            let sites: Vec<_> = self.bonding_sites(atom).collect();
+            for site in sites {
+                self.graph.remove_node(site);
+            }
+            self.add_bonding_sites(atom);
+        }
+        if order == 2 {
+            self.align_double_bond(a, b);
+        }
+        Some(previous)
+    }
+
+    // Turns the bonding sites of one end of a double bond about the bond, so
+    // that they lie in the plane of the other end's substituents, as the pi
+    // bond demands.  The sites are laid out for each atom on its own, which
+    // leaves the two ends twisted at random.  Only an end with no other
+    // bonded atoms can be turned.
+    fn align_double_bond(&mut self, a: NodeIndex, b: NodeIndex) {
+        for (fixed, turned) in [(a, b), (b, a)] {
+            if self.bonds(turned).any(|(neighbor, _)| neighbor != fixed) {
+                continue;
+            }
+            let axis = self.offset(fixed, turned).normalize_or_zero();
+            let perpendicular = |molecule: &Self, atom: NodeIndex, neighbor: NodeIndex| {
+                molecule
+                    .offset(atom, neighbor)
+                    .reject_from_normalized(axis)
+                    .try_normalize()
+            };
+            let reference = self
+                .graph
+                .neighbors(fixed)
+                .filter(|&neighbor| neighbor != turned)
+                .find_map(|neighbor| perpendicular(self, fixed, neighbor));
+            let sites: Vec<_> = self.bonding_sites(turned).collect();
+            let current = sites
+                .iter()
+                .find_map(|&site| perpendicular(self, turned, site));
+            let (Some(reference), Some(current)) = (reference, current) else {
+                continue;
+            };
+
+            // Either side of the plane will do, so take the smaller turn
+            let mut angle = axis
+                .dot(current.cross(reference))
+                .atan2(current.dot(reference));
+            if angle.abs() > PI / 2.0 {
+                angle -= PI.copysign(angle);
+            }
+            let rotation = Quat::from_axis_angle(axis, angle);
+            let position = self.graph[turned].pos;
+            for site in sites {
+                let offset = self.graph[site].pos - position;
+                self.graph[site].pos = position + rotation * offset;
+            }
+            return;
+        }
+    }
+
+    // Gives an atom which has just lost a bond a bonding site along `offset`,
+    // the offset to the (nearest copy of the) atom it was bonded to, so that
+    // the site takes the place of the bond in the atom's VSEPR geometry.  The
+    // bond may have been a multiple bond, so the atom's valence is then
+    // topped up.
+    fn restore_bonding_site(&mut self, atom: NodeIndex, offset: Vec3) {
+        let position = self.graph[atom].pos;
+        let direction = offset.try_normalize().unwrap_or(Vec3::Z);
+        let site = self.graph.add_node(MolNode {
+            pos: position + direction,
+            vel: Vec3::ZERO,
+            particle: Particle::BondingSite,
+        });
+        self.graph.add_edge(atom, site, 1);
+        self.add_bonding_sites(atom);
+    }
+
+    /// Moves an atom to `position`, carrying its bonding sites along with it.
+    /// Returns `false` if `atom` is not an atom of this molecule.
+    pub fn move_atom(&mut self, atom: NodeIndex, position: Vec3) -> bool {
+        match self.graph.node_weight(atom) {
+            Some(MolNode {
+                particle: Particle::Atom(_),
+                ..
+            }) => {}
+            _ => return false,
+        }
+        let offset = position - self.graph[atom].pos;
+        let sites: Vec<_> = self.bonding_sites(atom).collect();
+        for node in sites.into_iter().chain([atom]) {
+            self.graph[node].pos += offset;
+        }
+        true
+    }
+
+    /// Advances the relaxation of the molecule in the given force field by
+    /// one damped step, in which each particle accelerates along the force on
+    /// it.  No particle moves further than `MAX_RELAX_STEP` at once, so that
+    /// badly strained molecules do not fly apart.  Atoms that leave the unit
+    /// cell are wrapped back into it.
+    ///
+    /// `parameterized` keeps the force field's parameters for the molecule
+    /// from one step to the next.  It is filled in by the first step, and
+    /// again whenever the molecule's bonds or unit cell have changed since.
+    pub fn relax_step(
+        &mut self,
+        force_field: &dyn ForceField,
+        parameterized: &mut Option<Parameterized>,
+    ) {
+        if let Some(parameterized) = parameterized {
+            parameterized.update(self, force_field);
+        }
+        let Parameterized {
+            topology,
+            potential,
+        } = parameterized.get_or_insert_with(|| Parameterized::new(self, force_field));
+        let positions = topology.positions(self);
+        let mut forces = vec![Vec3::ZERO; positions.len()];
+        potential.evaluate(&positions, &mut forces);
+
+        for (&node_index, force) in topology.nodes.iter().zip(forces) {
+            let node = &mut self.graph[node_index];
+            node.vel += force * 0.1;
+            node.pos += (node.vel * 0.01).clamp_length_max(MAX_RELAX_STEP);
+            node.vel *= 0.9;
+        }
+        self.wrap_into_cell();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    // Checks that every bonding site has exactly one neighbour, an atom, as
+    // `MolGraph` demands
+    fn assert_sites_owned(molecule: &Molecule) {
+        for node in molecule.graph.node_indices() {
+            if molecule.graph[node].particle != Particle::BondingSite {
+                continue;
+            }
+            let neighbors: Vec<_> = molecule.graph.neighbors(node).collect();
+            assert_eq!(neighbors.len(), 1, "site {:?} has {:?}", node, neighbors);
+            assert!(matches!(
+                molecule.graph[neighbors[0]].particle,
+                Particle::Atom(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn add_atom() {
+        let directions = vsepr::bond_directions(Element::Carbon, &[]);
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        let up = Vec3::X;
+        let rotation = Quat::from_rotation_arc(Vec3::Z, up);
+        for skip in [false, true] {
+            let mut molecule = Molecule::new();
+            let carbon = molecule.add_atom(Element::Carbon, position, up, None, &directions, skip);
+            assert_eq!(molecule.graph[carbon].pos, position);
+            assert_eq!(molecule.atoms().count(), 1);
+
+            // One site at unit distance along each direction but the skipped
+            // one, turned so that +z points along `up`
+            let sites: Vec<_> = molecule.bonding_sites(carbon).collect();
+            assert_eq!(sites.len(), if skip { 3 } else { 4 });
+            for &site in &sites {
+                let offset = molecule.graph[site].pos - position;
+                assert!((offset.length() - 1.0).abs() < 1e-4);
+                let expected = directions.iter().skip(skip as usize);
+                assert_eq!(
+                    expected
+                        .filter(|&&direction| close(rotation * direction, offset))
+                        .count(),
+                    1
+                );
+            }
+            assert_sites_owned(&molecule);
+        }
+    }
+
+    #[test]
+    fn bond_atom_at_site() {
+        let mut molecule = Molecule::new();
+        let directions = vsepr::bond_directions(Element::Carbon, &[]);
+        let carbon = molecule.add_atom(
+            Element::Carbon,
+            Vec3::ZERO,
+            Vec3::Z,
+            None,
+            &directions,
+            false,
+        );
+        let site = molecule.bonding_sites(carbon).next().unwrap();
+        let site_pos = molecule.graph[site].pos;
+
+        let oxygen = molecule.bond_atom_at_site(site, Element::Oxygen).unwrap();
+        // The new atom sits beyond the site, single bonded to the site's owner
+        // and facing it
+        assert!(close(molecule.graph[oxygen].pos, site_pos * 1.5));
+        assert_eq!(molecule.bonds(carbon).collect::<Vec<_>>(), [(oxygen, 1)]);
+        assert_eq!(molecule.bonds(oxygen).collect::<Vec<_>>(), [(carbon, 1)]);
+        match &molecule.graph[oxygen].particle {
+            Particle::Atom(atom) => {
+                assert_eq!(atom.element, Element::Oxygen);
+                assert_eq!(atom.facing, Some(carbon));
+            }
+            Particle::BondingSite => panic!("not an atom"),
+        }
+
+        // Carbon has three sites left, and oxygen one for its remaining bond,
+        // pointing away from the bond
+        assert_eq!(molecule.bonding_sites(carbon).count(), 3);
+        let oxygen_sites: Vec<_> = molecule.bonding_sites(oxygen).collect();
+        assert_eq!(oxygen_sites.len(), 1);
+        let offset = molecule.graph[oxygen_sites[0]].pos - molecule.graph[oxygen].pos;
+        assert!((offset.length() - 1.0).abs() < 1e-4);
+        let angle = offset.angle_between(-site_pos).to_degrees();
+        assert!(angle > 90.0, "{}", angle);
+        assert_sites_owned(&molecule);
+
+        // Atoms are not bonding sites
+        assert_eq!(molecule.bond_atom_at_site(carbon, Element::Carbon), None);
+        assert_eq!(molecule.graph.node_count(), 6);
+    }
+
+    #[test]
+    fn bonding_site_owner() {
+        let (molecule, atoms) = fixtures::chain(&[Element::Carbon, Element::Carbon]);
+        let (carbon, other) = (atoms[0], atoms[1]);
+
+        for atom in [carbon, other] {
+            for site in molecule.bonding_sites(atom) {
+                assert_eq!(molecule.bonding_site_owner(site), Some(atom));
+            }
+            assert_eq!(molecule.bonding_site_owner(atom), None);
+        }
+        assert_eq!(molecule.bonding_site_owner(NodeIndex::new(100)), None);
+    }
+
+    #[test]
+    fn bond_order_range() {
+        let (mut molecule, atoms) = fixtures::chain(&[Element::Carbon, Element::Carbon]);
+        let (carbon, other) = (atoms[0], atoms[1]);
+
+        // Carbon has sites to spare for a fourth bond, but no such bond exists
+        for order in [0, 4] {
+            assert_eq!(molecule.set_bond_order(carbon, other, order), None);
+            assert_eq!(molecule.bonds(carbon).collect::<Vec<_>>(), [(other, 1)]);
+            assert_eq!(molecule.bonding_sites(carbon).count(), 3);
+        }
+        assert_eq!(molecule.set_bond_order(carbon, other, 3), Some(1));
+        assert_eq!(molecule.set_bond_order(carbon, other, 1), Some(3));
+    }
+
+    #[test]
+    fn planar_double_bond() {
+        let (mut molecule, atoms) = fixtures::chain(&[Element::Carbon, Element::Carbon]);
+        let (carbon, other) = (atoms[0], atoms[1]);
+        molecule.set_bond_order(carbon, other, 2).unwrap();
+
+        // Ethylene's two carbons and four bonding sites lie in one plane
+        let position = |node: NodeIndex| molecule.graph[node].pos;
+        let sites: Vec<_> = molecule
+            .bonding_sites(carbon)
+            .chain(molecule.bonding_sites(other))
+            .collect();
+        assert_eq!(sites.len(), 4);
+        let normal = (position(other) - position(carbon))
+            .cross(position(sites[0]) - position(carbon))
+            .normalize();
+        for site in sites {
+            let offset = position(site) - position(carbon);
+            assert!(offset.dot(normal).abs() < 1e-4, "{:?}", offset);
+        }
+    }
+
+    #[test]
+    fn sites_stay_owned() {
+        let (mut molecule, atoms) = fixtures::chain(&[Element::Carbon, Element::Carbon]);
+        let (carbon, other) = (atoms[0], atoms[1]);
+        assert_sites_owned(&molecule);
+
+        for order in [2, 3, 1] {
+            assert!(molecule.set_bond_order(carbon, other, order).is_some());
+            assert_sites_owned(&molecule);
+        }
+        let site = molecule.bonding_sites(other).next().unwrap();
+        let nitrogen = molecule.bond_atom_at_site(site, Element::Nitrogen).unwrap();
+        assert_sites_owned(&molecule);
+        assert!(molecule.move_atom(nitrogen, Vec3::new(3.0, 0.0, 0.0)));
+        assert_sites_owned(&molecule);
+        assert_eq!(molecule.remove_bond(carbon, other), Some(1));
+        assert_sites_owned(&molecule);
+        assert!(molecule.remove_atom(other).is_some());
+        assert_sites_owned(&molecule);
+
+        // Every atom left has its full valence of bonds and sites
+        assert_eq!(molecule.bonding_sites(carbon).count(), 4);
+        assert_eq!(molecule.bonding_sites(nitrogen).count(), 3);
+    }
+}
+
+// End of File
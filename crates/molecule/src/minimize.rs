@@ -0,0 +1,223 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Geometry optimization: moving the particles of a molecule to a local
+//! minimum of the potential energy, using the fast inertial relaxation engine
+//! (FIRE) of Bitzek et al., Phys. Rev. Lett. 97, 170201 (2006).  FIRE is a
+//! damped molecular dynamics which steers the velocity towards the force, and
+//! speeds up for as long as it keeps going downhill.
+
+use crate::forcefield::{Energy, ForceField, Topology};
+use crate::Molecule;
+use glam::Vec3;
+
+/// When to stop minimizing, and how far particles may move in a step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinimizeOptions {
+    /// The root mean square force on the particles, in kcal/mol/angstrom,
+    /// below which the molecule counts as minimized.
+    pub rms_force_tolerance: f32,
+    pub max_iterations: usize,
+    /// The furthest any particle moves in one step, in angstroms.
+    pub max_step: f32,
+}
+
+impl Default for MinimizeOptions {
+    fn default() -> Self {
+        Self {
+            rms_force_tolerance: 0.1,
+            max_iterations: 2000,
+            max_step: 0.1,
+        }
+    }
+}
+
+/// The outcome of a minimization.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MinimizeReport {
+    /// The potential energy of the final geometry.
+    pub energy: Energy,
+    /// The largest force on any particle, in kcal/mol/angstrom.
+    pub max_force: f32,
+    pub rms_force: f32,
+    pub iterations: usize,
+    /// Whether the RMS force fell below the tolerance before running out of
+    /// iterations.
+    pub converged: bool,
+}
+
+// The parameters of FIRE, as recommended in the paper.  Times are in units in
+// which every particle has unit mass.
+const INITIAL_TIMESTEP: f32 = 0.01;
+const MAX_TIMESTEP: f32 = 0.05;
+const MIN_DOWNHILL_STEPS: usize = 5;
+const TIMESTEP_INCREASE: f32 = 1.1;
+const TIMESTEP_DECREASE: f32 = 0.5;
+const INITIAL_MIXING: f32 = 0.1;
+const MIXING_DECREASE: f32 = 0.99;
+
+fn largest_and_rms(forces: &[Vec3]) -> (f32, f32) {
+    if forces.is_empty() {
+        return (0.0, 0.0);
+    }
+    let (max, sum) = forces.iter().fold((0.0_f32, 0.0), |(max, sum), force| {
+        (max.max(force.length()), sum + force.length_squared())
+    });
+    (max, (sum / forces.len() as f32).sqrt())
+}
+
+/// Minimizes the potential energy of `molecule` in `force_field`, leaving its
+/// particles at rest (and inside its unit cell, if it has one).
+pub fn minimize(
+    molecule: &mut Molecule,
+    force_field: &dyn ForceField,
+    options: &MinimizeOptions,
+) -> MinimizeReport {
+    let topology = Topology::new(molecule);
+    let potential = force_field.parameterize(&topology);
+    let mut positions = topology.positions(molecule);
+    let mut velocities = vec![Vec3::ZERO; positions.len()];
+    let mut forces = vec![Vec3::ZERO; positions.len()];
+
+    let mut timestep = INITIAL_TIMESTEP;
+    let mut mixing = INITIAL_MIXING;
+    let mut downhill_steps = 0;
+    let mut report = MinimizeReport::default();
+
+    loop {
+        forces.fill(Vec3::ZERO);
+        report.energy = potential.evaluate(&positions, &mut forces);
+        (report.max_force, report.rms_force) = largest_and_rms(&forces);
+        report.converged = report.rms_force < options.rms_force_tolerance;
+        if report.converged || report.iterations == options.max_iterations {
+            break;
+        }
+        report.iterations += 1;
+
+        let power: f32 = forces.iter().zip(&velocities).map(|(f, v)| f.dot(*v)).sum();
+        if power > 0.0 {
+            // Going downhill: turn the velocity towards the force
+            let speed = velocities
+                .iter()
+                .map(|v| v.length_squared())
+                .sum::<f32>()
+                .sqrt();
+            let force = forces
+                .iter()
+                .map(|f| f.length_squared())
+                .sum::<f32>()
+                .sqrt();
+            let scale = mixing * speed / force.max(f32::MIN_POSITIVE);
+            for (velocity, force) in velocities.iter_mut().zip(&forces) {
+                *velocity = (1.0 - mixing) * *velocity + scale * *force;
+            }
+            downhill_steps += 1;
+            if downhill_steps > MIN_DOWNHILL_STEPS {
+                timestep = (timestep * TIMESTEP_INCREASE).min(MAX_TIMESTEP);
+                mixing *= MIXING_DECREASE;
+            }
+        } else {
+            // Overshot the minimum: stop, and start again more carefully
+            velocities.fill(Vec3::ZERO);
+            timestep *= TIMESTEP_DECREASE;
+            mixing = INITIAL_MIXING;
+            downhill_steps = 0;
+        }
+
+        for ((position, velocity), force) in positions.iter_mut().zip(&mut velocities).zip(&forces)
+        {
+            *velocity += *force * timestep;
+            *position += (*velocity * timestep).clamp_length_max(options.max_step);
+        }
+        topology.wrap(&mut positions);
+    }
+
+    topology.set_positions(molecule, &positions);
+    for node in molecule.graph.node_weights_mut() {
+        node.vel = Vec3::ZERO;
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::forcefield::uff::Uff;
+    use periodic_table::Element;
+
+    fn methanol() -> Molecule {
+        fixtures::chain(&[Element::Carbon, Element::Oxygen]).0
+    }
+
+    #[test]
+    fn converges() {
+        let mut molecule = methanol();
+        let force_field = Uff::default();
+        let before = molecule.potential_energy(&force_field).total();
+        let options = MinimizeOptions {
+            rms_force_tolerance: 0.01,
+            ..MinimizeOptions::default()
+        };
+        let report = minimize(&mut molecule, &force_field, &options);
+
+        assert!(report.converged, "{:?}", report);
+        assert!(report.rms_force < 0.01);
+        assert!(report.max_force >= report.rms_force);
+        assert!(report.energy.total() < before);
+        // The report describes the geometry the molecule was left in
+        let after = molecule.potential_energy(&force_field);
+        assert!((after.total() - report.energy.total()).abs() < 1e-3);
+        assert!(molecule
+            .graph
+            .node_weights()
+            .all(|node| node.vel == Vec3::ZERO));
+
+        let carbon = molecule
+            .atoms()
+            .find(|(_, atom)| atom.element == Element::Carbon)
+            .unwrap()
+            .0;
+        let oxygen = molecule
+            .atoms()
+            .find(|(_, atom)| atom.element == Element::Oxygen)
+            .unwrap()
+            .0;
+        let length = molecule.graph[carbon]
+            .pos
+            .distance(molecule.graph[oxygen].pos);
+        assert!((length - 1.42).abs() < 0.03, "{}", length);
+
+        // Minimizing again starts out converged
+        let again = minimize(&mut molecule, &force_field, &options);
+        assert!(again.converged);
+        assert_eq!(again.iterations, 0);
+    }
+
+    #[test]
+    fn iteration_limit() {
+        let mut molecule = methanol();
+        let options = MinimizeOptions {
+            rms_force_tolerance: 0.0,
+            max_iterations: 10,
+            ..MinimizeOptions::default()
+        };
+        let report = minimize(&mut molecule, &Uff::default(), &options);
+        assert!(!report.converged);
+        assert_eq!(report.iterations, 10);
+    }
+
+    #[test]
+    fn empty_molecule() {
+        let report = minimize(
+            &mut Molecule::new(),
+            &Uff::default(),
+            &MinimizeOptions::default(),
+        );
+        assert!(report.converged);
+        assert_eq!(report.energy.total(), 0.0);
+    }
+}
+
+// End of File
@@ -0,0 +1,66 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Working out which atoms are bonded from their positions alone, for file
+//! formats which only store coordinates.
+
+use crate::forcefield::neighbors::NeighborList;
+use crate::Molecule;
+use glam::Vec3;
+use petgraph::stable_graph::NodeIndex;
+
+/// How much longer than the sum of two atoms' covalent radii a bond between
+/// them may be, in angstroms.
+pub const BOND_TOLERANCE: f32 = 0.45;
+
+// Atoms closer together than this (in angstroms) are assumed to be
+// overlapping copies of each other rather than bonded.
+const MIN_BOND_LENGTH: f32 = 0.4;
+
+/// Adds a single bond between every pair of atoms closer together than the
+/// sum of their covalent radii plus `BOND_TOLERANCE`.  Atoms which are already
+/// bonded keep their existing bond order.  Candidate pairs are found with a
+/// neighbour list no shorter than the longest possible bond, so that only
+/// atoms in neighbouring cells of a grid need to be compared.  In a molecule
+/// with a unit cell, atoms bond to the nearest copy of each other, so bonds
+/// may cross the faces of the cell.
+pub fn perceive_bonds(molecule: &mut Molecule) {
+    let atoms: Vec<(NodeIndex, Vec3, f32)> = molecule
+        .atoms()
+        .map(|(node, atom)| {
+            let pos = molecule.graph[node].pos;
+            (node, pos, atom.element.covalent_radius())
+        })
+        .collect();
+
+    let max_radius = atoms
+        .iter()
+        .map(|&(_, _, radius)| radius)
+        .fold(0.0, f32::max);
+    let mut neighbors = NeighborList::new(2.0 * max_radius + BOND_TOLERANCE, 0.0);
+    neighbors.cell = molecule.cell;
+    let positions: Vec<Vec3> = atoms.iter().map(|&(_, pos, _)| pos).collect();
+    neighbors.rebuild(&positions, |_, _| false);
+
+    let mut bonds = Vec::new();
+    for &(a, b) in neighbors.pairs() {
+        let ((node, pos, radius), (other, other_pos, other_radius)) = (atoms[a], atoms[b]);
+        let offset = other_pos - pos;
+        let distance = molecule
+            .cell
+            .map_or(offset, |cell| cell.minimum_image(offset))
+            .length();
+        if distance > MIN_BOND_LENGTH && distance <= radius + other_radius + BOND_TOLERANCE {
+            bonds.push((node, other));
+        }
+    }
+
+    for (a, b) in bonds {
+        if molecule.graph.find_edge(a, b).is_none() {
+            molecule.add_bond(a, b, 1);
+        }
+    }
+}
+
+// End of File
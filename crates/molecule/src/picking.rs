@@ -0,0 +1,268 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Finding the particle or bond under the cursor.  Atoms and bonds are drawn
+//! as instances of a few meshes rather than as entities of their own, so
+//! there is nothing for a generic picking backend to hit; instead the ray
+//! through the cursor is intersected with the sphere each particle is drawn
+//! as, and the cylinders of each bond.
+
+use crate::representation::BondStyle;
+use crate::{Molecule, Particle};
+use glam::Vec3;
+use petgraph::stable_graph::NodeIndex;
+
+/// The distance along a ray from `origin` in the (normalized) `direction` at
+/// which it first enters the sphere of `radius` around `center`, or `None` if
+/// it misses the sphere.  A ray starting inside the sphere hits it at its
+/// origin.
+pub fn ray_sphere(origin: Vec3, direction: Vec3, center: Vec3, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    Some(-b - discriminant.sqrt())
+}
+
+/// The distance along a ray from `origin` in the (normalized) `direction` at
+/// which it first enters the closed cylinder of `radius` whose ends are
+/// centered on `start` and `end`, or `None` if it misses the cylinder.  A ray
+/// starting inside the cylinder hits it at its origin.
+pub fn ray_cylinder(
+    origin: Vec3,
+    direction: Vec3,
+    start: Vec3,
+    end: Vec3,
+    radius: f32,
+) -> Option<f32> {
+    let length = start.distance(end);
+    let axis = (end - start).try_normalize()?;
+    let offset = origin - start;
+    let along = offset.dot(axis);
+    // The ray and its origin, seen along the axis
+    let flat_direction = direction.reject_from_normalized(axis);
+    let flat_offset = offset.reject_from_normalized(axis);
+    let c = flat_offset.length_squared() - radius * radius;
+    if c <= 0.0 && (0.0..=length).contains(&along) {
+        return Some(0.0);
+    }
+
+    // Where the ray enters the side, if it does so between the ends
+    let a = flat_direction.length_squared();
+    let b = flat_offset.dot(flat_direction);
+    let discriminant = b * b - a * c;
+    let side = (a > 1e-12 && discriminant >= 0.0)
+        .then(|| (-b - discriminant.sqrt()) / a)
+        .filter(|&distance| {
+            distance >= 0.0 && (0.0..=length).contains(&(along + direction.dot(axis) * distance))
+        });
+
+    // Where the ray crosses the flat caps at either end
+    let caps = [start, end].into_iter().filter_map(|center| {
+        let speed = direction.dot(axis);
+        if speed.abs() < 1e-12 {
+            return None;
+        }
+        let distance = (center - origin).dot(axis) / speed;
+        let hit = origin + direction * distance;
+        (distance >= 0.0 && hit.distance_squared(center) <= radius * radius).then_some(distance)
+    });
+    side.into_iter().chain(caps).min_by(f32::total_cmp)
+}
+
+impl Molecule {
+    /// The node nearest to `origin` whose sphere is hit by the ray in the
+    /// (normalized) `direction`, along with the distance to it.  `radius`
+    /// gives the radius each particle is drawn at.
+    pub fn pick(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        radius: impl Fn(&Particle) -> f32,
+    ) -> Option<(NodeIndex, f32)> {
+        self.graph
+            .node_indices()
+            .filter_map(|index| {
+                let node = &self.graph[index];
+                ray_sphere(origin, direction, node.pos, radius(&node.particle))
+                    .map(|distance| (index, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+
+    /// The bond nearest to `origin` one of whose cylinders, as drawn in
+    /// `style`, is hit by the ray in the (normalized) `direction`, along with
+    /// the distance to it.  The bond's atoms are given lower index first.
+    /// Bonds to bonding sites are not drawn, and so cannot be picked.
+    pub fn pick_bond(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        style: &BondStyle,
+    ) -> Option<((NodeIndex, NodeIndex), f32)> {
+        let is_atom = |node: NodeIndex| matches!(self.graph[node].particle, Particle::Atom(_));
+        self.graph
+            .edge_indices()
+            .filter_map(|edge| {
+                let (a, b) = self.graph.edge_endpoints(edge)?;
+                if !is_atom(a) || !is_atom(b) {
+                    return None;
+                }
+                style
+                    .strand_ends(self, a, b)
+                    .into_iter()
+                    .filter_map(|(start, end, radius)| {
+                        ray_cylinder(origin, direction, start, end, radius)
+                    })
+                    .min_by(f32::total_cmp)
+                    .map(|distance| ((a.min(b), a.max(b)), distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{vsepr, MolNode};
+    use periodic_table::Element;
+
+    #[test]
+    fn spheres() {
+        let center = Vec3::new(0.0, 0.0, -5.0);
+        assert_eq!(ray_sphere(Vec3::ZERO, -Vec3::Z, center, 1.0), Some(4.0));
+        // Grazing, missing, and pointing away from the sphere
+        assert_eq!(
+            ray_sphere(Vec3::X, -Vec3::Z, center, 1.0).map(f32::round),
+            Some(5.0)
+        );
+        assert_eq!(ray_sphere(Vec3::X * 1.1, -Vec3::Z, center, 1.0), None);
+        assert_eq!(ray_sphere(Vec3::ZERO, Vec3::Z, center, 1.0), None);
+        assert_eq!(ray_sphere(center, Vec3::Z, center, 1.0), Some(0.0));
+    }
+
+    #[test]
+    fn cylinders() {
+        let (start, end) = (Vec3::new(0.0, -1.0, -5.0), Vec3::new(0.0, 1.0, -5.0));
+        let hit = |origin: Vec3, direction: Vec3| {
+            ray_cylinder(origin, direction, start, end, 0.5).map(|distance| {
+                assert!(distance >= 0.0);
+                (distance * 1e4).round() / 1e4
+            })
+        };
+        // Through the side, and along the axis through a cap
+        assert_eq!(hit(Vec3::ZERO, -Vec3::Z), Some(4.5));
+        assert_eq!(hit(Vec3::new(0.0, 0.9, 0.0), -Vec3::Z), Some(4.5));
+        assert_eq!(hit(Vec3::new(0.0, 3.0, -5.0), -Vec3::Y), Some(2.0));
+        assert_eq!(hit(Vec3::new(0.3, -3.0, -5.0), Vec3::Y), Some(2.0));
+        // Beyond the ends, beside the side, and pointing away
+        assert_eq!(hit(Vec3::new(0.0, 1.1, 0.0), -Vec3::Z), None);
+        assert_eq!(hit(Vec3::new(0.6, 3.0, -5.0), -Vec3::Y), None);
+        assert_eq!(hit(Vec3::ZERO, Vec3::Z), None);
+        // Starting inside
+        assert_eq!(hit(Vec3::new(0.0, 0.0, -5.0), Vec3::X), Some(0.0));
+    }
+
+    #[test]
+    fn nearest_bond() {
+        let style = BondStyle {
+            radius: 0.1,
+            multiple_radius: 0.05,
+            spacing: 0.2,
+            show_order: true,
+        };
+        let mut molecule = Molecule::new();
+        let [a, b, c] = [-1.0, 1.0, 3.0].map(|x| {
+            molecule.add_atom(
+                Element::Carbon,
+                Vec3::new(x, 0.0, 0.0),
+                Vec3::Z,
+                None,
+                &[],
+                false,
+            )
+        });
+        molecule.add_bond(a, b, 2);
+        molecule.add_bond(c, b, 1);
+        // A bonding site of `a`, whose bond is not drawn
+        let site = molecule.graph.add_node(MolNode {
+            pos: Vec3::new(-1.0, 0.0, 1.0),
+            vel: Vec3::ZERO,
+            particle: Particle::BondingSite,
+        });
+        molecule.graph.add_edge(a, site, 1);
+
+        // The strands of the double bond are spread out in the plane of the
+        // bonding site, so a ray through the bond's axis in that plane meets
+        // a strand, and one across it passes between them
+        let origin = Vec3::new(0.0, 0.0, 10.0);
+        let (bond, distance) = molecule.pick_bond(origin, -Vec3::Z, &style).unwrap();
+        assert_eq!(bond, (a.min(b), a.max(b)));
+        assert!((distance - 9.85).abs() < 1e-4, "{}", distance);
+        let origin = Vec3::new(0.0, 10.0, 0.0);
+        assert_eq!(molecule.pick_bond(origin, -Vec3::Y, &style), None);
+
+        // The nearer of two bonds along the ray
+        let origin = Vec3::new(10.0, 0.0, 0.0);
+        let (bond, _) = molecule.pick_bond(origin, -Vec3::X, &style).unwrap();
+        assert_eq!(bond, (b.min(c), b.max(c)));
+        // Through the middle of the bond to the bonding site
+        let origin = Vec3::new(-1.0, 10.0, 0.5);
+        assert_eq!(molecule.pick_bond(origin, -Vec3::Y, &style), None);
+    }
+
+    #[test]
+    fn nearest_particle() {
+        let radius = |particle: &Particle| match particle {
+            Particle::Atom(_) => 0.5,
+            Particle::BondingSite => 0.1,
+        };
+        let mut molecule = Molecule::new();
+        let [near, far] = [1.0, -1.0].map(|z| {
+            molecule.add_atom(
+                Element::Carbon,
+                Vec3::new(0.0, 0.0, z),
+                Vec3::Z,
+                None,
+                &[],
+                false,
+            )
+        });
+
+        // The ray passes through both atoms, and hits the nearer one first
+        let origin = Vec3::new(0.0, 0.0, 10.0);
+        let (node, distance) = molecule.pick(origin, -Vec3::Z, radius).unwrap();
+        assert_eq!(node, near);
+        assert!((distance - 8.5).abs() < 1e-5);
+        let (node, _) = molecule.pick(-origin, Vec3::Z, radius).unwrap();
+        assert_eq!(node, far);
+        assert_eq!(molecule.pick(origin, Vec3::Z, radius), None);
+
+        // Every bonding site can be picked by aiming at it from outside
+        let carbon = molecule.add_atom(
+            Element::Carbon,
+            Vec3::new(5.0, 0.0, 0.0),
+            Vec3::Z,
+            None,
+            &vsepr::bond_directions(Element::Carbon, &[]),
+            false,
+        );
+        let sites: Vec<_> = molecule.bonding_sites(carbon).collect();
+        assert_eq!(sites.len(), 4);
+        for site in sites {
+            let target = molecule.graph[site].pos;
+            let origin = target + (target - molecule.graph[carbon].pos) * 10.0;
+            let direction = (target - origin).normalize();
+            assert_eq!(molecule.pick(origin, direction, radius).unwrap().0, site);
+        }
+    }
+}
+
+// End of File
@@ -0,0 +1,429 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The styles in which a molecule can be drawn, and the meshes it is drawn
+//! with.  Everything here is plain geometry, so that it can be checked without
+//! a GPU; the application turns `MeshData` into meshes for the renderer.
+
+use crate::{BondOrder, Molecule};
+use glam::{Mat4, Vec3};
+use petgraph::stable_graph::NodeIndex;
+use std::f32::consts::{PI, TAU};
+
+/// How the atoms and bonds of a molecule are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Representation {
+    /// Atoms as small spheres, joined by thin sticks showing the bond order.
+    #[default]
+    BallAndStick,
+    /// Atoms as spheres of their van der Waals radius, without bonds.
+    SpaceFilling,
+    /// Bonds as thick sticks with rounded joints at the atoms.
+    Licorice,
+    /// Bonds as thin lines, with the atoms only marked where the lines meet.
+    Wireframe,
+}
+
+/// The radius of the cylinders a bond is drawn with, and how they are laid
+/// out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BondStyle {
+    /// The radius of the cylinder drawn for a single bond.
+    pub radius: f32,
+    /// The radius of each of the cylinders drawn side by side for a double or
+    /// triple bond.
+    pub multiple_radius: f32,
+    /// The distance between the axes of neighbouring cylinders of a multiple
+    /// bond.
+    pub spacing: f32,
+    /// Whether multiple bonds are drawn as several cylinders, or as a single
+    /// one like a single bond.
+    pub show_order: bool,
+}
+
+impl BondStyle {
+    /// The cylinders a bond of the given order is drawn with, as their offset
+    /// from the bond's axis (across the plane in which they are spread out)
+    /// and their radius.
+    pub fn strands(&self, order: BondOrder) -> Vec<(f32, f32)> {
+        let offsets: &[f32] = match order {
+            _ if !self.show_order => &[0.0],
+            0 | 1 => &[0.0],
+            2 => &[-0.5, 0.5],
+            _ => &[-1.0, 0.0, 1.0],
+        };
+        let radius = if offsets.len() > 1 {
+            self.multiple_radius
+        } else {
+            self.radius
+        };
+        offsets
+            .iter()
+            .map(|offset| (offset * self.spacing, radius))
+            .collect()
+    }
+
+    /// The cylinders the bond between atoms `a` and `b` is drawn with, as the
+    /// centers of their ends (the first at `a`) and their radius.  This is
+    /// empty if the atoms are not bonded.
+    pub fn strand_ends(
+        &self,
+        molecule: &Molecule,
+        a: NodeIndex,
+        b: NodeIndex,
+    ) -> Vec<(Vec3, Vec3, f32)> {
+        let Some(edge) = molecule.graph.find_edge(a, b) else {
+            return Vec::new();
+        };
+        let transform = bond_transform(molecule, a, b);
+        self.strands(molecule.graph[edge])
+            .into_iter()
+            .map(|(offset, radius)| {
+                let start = transform.transform_point3(Vec3::new(offset, 0.0, 0.0));
+                let end = transform.transform_point3(Vec3::new(offset, 1.0, 0.0));
+                (start, end, radius)
+            })
+            .collect()
+    }
+}
+
+/// The direction, perpendicular to the bond from `a` to `b`, in which the
+/// strands of a multiple bond are spread out.  This is chosen to lie in the
+/// plane of the bond and another bond (or bonding site) of one of its atoms,
+/// which for a double bond is the plane of its substituents.
+pub fn strand_direction(molecule: &Molecule, a: NodeIndex, b: NodeIndex) -> Vec3 {
+    let graph = &molecule.graph;
+    let axis = molecule.offset(a, b).normalize_or_zero();
+    [(a, b), (b, a)]
+        .into_iter()
+        .flat_map(|(atom, other)| {
+            graph
+                .neighbors(atom)
+                .filter(move |&neighbor| neighbor != other)
+                .map(move |neighbor| molecule.offset(atom, neighbor))
+        })
+        .find_map(|direction| direction.reject_from_normalized(axis).try_normalize())
+        .unwrap_or_else(|| axis.any_orthonormal_vector())
+}
+
+/// The transform from the frame a bond's strands are laid out in to the
+/// molecule.  It takes the origin to atom `a` and stretches the +y axis to
+/// reach atom `b`, with the +x axis along the strand direction, so that
+/// `BondStyle::strands` offsets are along x and the bond is one unit long.
+/// A bond through a face of a periodic cell reaches the copy of `b` nearest
+/// to `a`.
+pub fn bond_transform(molecule: &Molecule, a: NodeIndex, b: NodeIndex) -> Mat4 {
+    let start = molecule.graph[a].pos;
+    let offset = molecule.offset(a, b);
+    let axis = offset.try_normalize().unwrap_or(Vec3::Y);
+    let across = strand_direction(molecule, a, b);
+    Mat4::from_cols(
+        across.extend(0.0),
+        (axis * offset.length()).extend(0.0),
+        across.cross(axis).extend(0.0),
+        start.extend(1.0),
+    )
+}
+
+// The fraction of their van der Waals radius that atoms are drawn at in ball
+// and stick mode, so that the bonds between them remain visible.
+const BALL_RADIUS_SCALE: f32 = 0.3;
+const LICORICE_RADIUS: f32 = 0.2;
+const WIREFRAME_RADIUS: f32 = 0.03;
+
+impl Representation {
+    pub const ALL: [Representation; 4] = [
+        Representation::BallAndStick,
+        Representation::SpaceFilling,
+        Representation::Licorice,
+        Representation::Wireframe,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Representation::BallAndStick => "Ball and stick",
+            Representation::SpaceFilling => "Space filling",
+            Representation::Licorice => "Licorice",
+            Representation::Wireframe => "Wireframe",
+        }
+    }
+
+    /// The radius of the sphere an atom is drawn with, given its van der
+    /// Waals radius.
+    pub fn atom_radius(self, van_der_waals_radius: f32) -> f32 {
+        match self {
+            Representation::BallAndStick => van_der_waals_radius * BALL_RADIUS_SCALE,
+            Representation::SpaceFilling => van_der_waals_radius,
+            // The atoms round off the joints between the sticks
+            Representation::Licorice => LICORICE_RADIUS,
+            Representation::Wireframe => WIREFRAME_RADIUS,
+        }
+    }
+
+    /// How bonds are drawn, or `None` if they are not drawn at all.
+    pub fn bond_style(self) -> Option<BondStyle> {
+        match self {
+            Representation::BallAndStick => Some(BondStyle {
+                radius: 0.1,
+                multiple_radius: 0.06,
+                spacing: 0.16,
+                show_order: true,
+            }),
+            Representation::SpaceFilling => None,
+            Representation::Licorice => Some(BondStyle {
+                radius: LICORICE_RADIUS,
+                multiple_radius: LICORICE_RADIUS,
+                spacing: 0.0,
+                show_order: false,
+            }),
+            Representation::Wireframe => Some(BondStyle {
+                radius: WIREFRAME_RADIUS,
+                multiple_radius: WIREFRAME_RADIUS,
+                spacing: 0.1,
+                show_order: true,
+            }),
+        }
+    }
+}
+
+/// An indexed triangle mesh.  Triangles are wound counter-clockwise when seen
+/// from outside the mesh.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    fn push(&mut self, position: Vec3, normal: Vec3, uv: [f32; 2]) -> u32 {
+        self.positions.push(position.to_array());
+        self.normals.push(normal.to_array());
+        self.uvs.push(uv);
+        (self.positions.len() - 1) as u32
+    }
+
+    /// A sphere of unit radius centered on the origin, divided into `sectors`
+    /// around the y axis and `stacks` from pole to pole.
+    pub fn sphere(sectors: u32, stacks: u32) -> Self {
+        let mut mesh = MeshData::default();
+        for stack in 0..=stacks {
+            let v = stack as f32 / stacks as f32;
+            let polar = v * PI;
+            for sector in 0..=sectors {
+                let u = sector as f32 / sectors as f32;
+                let azimuth = u * TAU;
+                let normal = Vec3::new(
+                    polar.sin() * azimuth.cos(),
+                    polar.cos(),
+                    -polar.sin() * azimuth.sin(),
+                );
+                mesh.push(normal, normal, [u, v]);
+            }
+        }
+
+        let row = sectors + 1;
+        for stack in 0..stacks {
+            for sector in 0..sectors {
+                let top = stack * row + sector;
+                let bottom = top + row;
+                // The triangles touching the poles would be degenerate
+                if stack != 0 {
+                    mesh.indices.extend([top, bottom, top + 1]);
+                }
+                if stack != stacks - 1 {
+                    mesh.indices.extend([top + 1, bottom, bottom + 1]);
+                }
+            }
+        }
+        mesh
+    }
+
+    /// A closed cylinder of unit radius and unit height, centered on the
+    /// origin along the y axis, with `resolution` sides.
+    pub fn cylinder(resolution: u32) -> Self {
+        let mut mesh = MeshData::default();
+        let ring = |side: u32| {
+            let azimuth = side as f32 / resolution as f32 * TAU;
+            Vec3::new(azimuth.cos(), 0.0, -azimuth.sin())
+        };
+
+        // The side, whose vertices are shared by neighbouring faces so that it
+        // is shaded smoothly
+        for side in 0..=resolution {
+            let u = side as f32 / resolution as f32;
+            let normal = ring(side);
+            mesh.push(normal - Vec3::Y * 0.5, normal, [u, 1.0]);
+            mesh.push(normal + Vec3::Y * 0.5, normal, [u, 0.0]);
+        }
+        for side in 0..resolution {
+            let (bottom, top) = (side * 2, side * 2 + 1);
+            mesh.indices
+                .extend([bottom, bottom + 2, top, top, bottom + 2, top + 2]);
+        }
+
+        // The flat caps at either end
+        for normal in [Vec3::Y, -Vec3::Y] {
+            let center = mesh.push(normal * 0.5, normal, [0.5, 0.5]);
+            for side in 0..resolution {
+                let direction = ring(side);
+                mesh.push(
+                    direction + normal * 0.5,
+                    normal,
+                    [0.5 + direction.x * 0.5, 0.5 + direction.z * 0.5],
+                );
+            }
+            for side in 0..resolution {
+                let a = center + 1 + side;
+                let b = center + 1 + (side + 1) % resolution;
+                if normal.y > 0.0 {
+                    mesh.indices.extend([center, a, b]);
+                } else {
+                    mesh.indices.extend([center, b, a]);
+                }
+            }
+        }
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::UnitCell;
+    use crate::fixtures;
+    use periodic_table::Element;
+
+    // Checks that the mesh is well formed, and that each of its triangles
+    // faces the way of its vertex normals, i.e. outwards.
+    fn check_mesh(mesh: &MeshData) {
+        let count = mesh.positions.len();
+        assert_eq!(mesh.normals.len(), count);
+        assert_eq!(mesh.uvs.len(), count);
+        assert_eq!(mesh.indices.len() % 3, 0);
+        assert!(mesh.indices.iter().all(|&index| (index as usize) < count));
+        for normal in mesh.normals.iter() {
+            assert!((Vec3::from(*normal).length() - 1.0).abs() < 1e-5);
+        }
+
+        for triangle in mesh.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(mesh.positions[triangle[i] as usize]));
+            let face = (b - a).cross(c - a);
+            assert!(face.length() > 1e-6, "degenerate triangle {:?}", triangle);
+            for &index in triangle {
+                let normal = Vec3::from(mesh.normals[index as usize]);
+                assert!(
+                    face.dot(normal) > 0.0,
+                    "triangle {:?} faces inwards",
+                    triangle
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sphere() {
+        let mesh = MeshData::sphere(14, 14);
+        check_mesh(&mesh);
+        for position in mesh.positions.iter() {
+            assert!((Vec3::from(*position).length() - 1.0).abs() < 1e-5);
+        }
+        // Two triangles per quad, less one at each pole
+        assert_eq!(mesh.indices.len(), 3 * 14 * (2 * 14 - 2));
+    }
+
+    #[test]
+    fn cylinder() {
+        let mesh = MeshData::cylinder(12);
+        check_mesh(&mesh);
+        for position in mesh.positions.iter() {
+            let position = Vec3::from(*position);
+            assert!(position.y.abs() <= 0.5 + 1e-6);
+            assert!(position.x.hypot(position.z) <= 1.0 + 1e-5);
+        }
+        // Two triangles per side, and one per side in each cap
+        assert_eq!(mesh.indices.len(), 3 * 12 * 4);
+    }
+
+    #[test]
+    fn representations() {
+        let carbon = 1.7;
+        for representation in Representation::ALL {
+            let radius = representation.atom_radius(carbon);
+            assert!(radius > 0.0 && radius <= carbon);
+
+            // Bonds are no thicker than the atoms they join
+            if let Some(style) = representation.bond_style() {
+                for order in 1..=3 {
+                    let strands = style.strands(order);
+                    let expected = if style.show_order { order as usize } else { 1 };
+                    assert_eq!(strands.len(), expected, "{:?}", representation);
+                    for (_, strand_radius) in strands {
+                        assert!(strand_radius <= radius + 1e-6);
+                    }
+                }
+            }
+        }
+
+        assert_eq!(Representation::SpaceFilling.atom_radius(carbon), carbon);
+        assert_eq!(Representation::SpaceFilling.bond_style(), None);
+        let style = Representation::BallAndStick.bond_style().unwrap();
+        assert_eq!(style.strands(2), vec![(-0.08, 0.06), (0.08, 0.06)]);
+    }
+
+    #[test]
+    fn bond_across_cell_face() {
+        let mut molecule = Molecule::new();
+        let [a, b] = [0.5, 9.5].map(|x| {
+            molecule.add_atom(
+                Element::Carbon,
+                Vec3::new(x, 5.0, 5.0),
+                Vec3::Z,
+                None,
+                &[],
+                false,
+            )
+        });
+        molecule.add_bond(a, b, 1);
+        molecule.cell = UnitCell::cubic(10.0);
+
+        // The bond is one angstrom long, leaving `a` through the -x face
+        let transform = bond_transform(&molecule, a, b);
+        let start = transform.transform_point3(Vec3::ZERO);
+        let end = transform.transform_point3(Vec3::Y);
+        assert!(start.distance(Vec3::new(0.5, 5.0, 5.0)) < 1e-5);
+        assert!(end.distance(Vec3::new(-0.5, 5.0, 5.0)) < 1e-5);
+        // Strands are offset perpendicular to the bond, without stretching
+        let across = transform.transform_vector3(Vec3::X);
+        assert!((across.length() - 1.0).abs() < 1e-5);
+        assert!(across.dot(end - start).abs() < 1e-5);
+    }
+
+    #[test]
+    fn double_bond_strands() {
+        // Ethylene, whose bonding sites (for hydrogens) all lie in one plane
+        let (mut molecule, atoms) = fixtures::chain(&[Element::Carbon, Element::Carbon]);
+        let (carbon, other) = (atoms[0], atoms[1]);
+        molecule.set_bond_order(carbon, other, 2).unwrap();
+
+        let transform = bond_transform(&molecule, carbon, other);
+        let axis = transform.transform_vector3(Vec3::Y);
+        let across = transform.transform_vector3(Vec3::X);
+        let normal = axis.cross(across).normalize();
+        for atom in [carbon, other] {
+            let sites: Vec<_> = molecule.bonding_sites(atom).collect();
+            assert_eq!(sites.len(), 2);
+            for site in sites {
+                let offset = molecule.graph[site].pos - molecule.graph[atom].pos;
+                assert!(offset.dot(normal).abs() < 1e-4, "{:?}", offset);
+            }
+        }
+        // Either way round, the strands are spread across the same plane
+        let reverse = bond_transform(&molecule, other, carbon);
+        assert!(reverse.transform_vector3(Vec3::X).dot(normal).abs() < 1e-4);
+    }
+}
+
+// End of File
@@ -0,0 +1,115 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Trajectories: the positions of a molecule's atoms over a sequence of
+//! frames, e.g. from a molecular dynamics simulation.
+
+use crate::Molecule;
+use glam::{Mat3, Vec3};
+use petgraph::stable_graph::NodeIndex;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyValue {
+    String(String),
+    Real(f64),
+    Integer(i64),
+    Logical(bool),
+}
+
+/// A per-atom property of a frame, such as the force on each atom.  There are
+/// `columns` values for each atom, stored atom by atom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub columns: usize,
+    pub values: Vec<PropertyValue>,
+}
+
+impl Property {
+    /// The values of this property for the atom at `index` in the frame.
+    pub fn get(&self, index: usize) -> &[PropertyValue] {
+        &self.values[index * self.columns..(index + 1) * self.columns]
+    }
+
+    /// Reads the property as a vector for each atom, if it consists of three
+    /// real numbers per atom.
+    pub fn as_vectors(&self) -> Option<Vec<Vec3>> {
+        if self.columns != 3 {
+            return None;
+        }
+        let mut vectors = Vec::with_capacity(self.values.len() / 3);
+        for chunk in self.values.chunks(3) {
+            let mut vector = [0.0; 3];
+            for (component, value) in vector.iter_mut().zip(chunk) {
+                let PropertyValue::Real(value) = value else {
+                    return None;
+                };
+                *component = *value as f32;
+            }
+            vectors.push(Vec3::from_array(vector));
+        }
+        Some(vectors)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frame {
+    /// The position of each atom, in the order of `Trajectory::atoms`.
+    pub positions: Vec<Vec3>,
+    /// The lattice vectors of the periodic simulation cell, if there is one,
+    /// as the columns of a matrix.
+    pub lattice: Option<Mat3>,
+    /// Per-atom properties other than the element and position.
+    pub properties: Vec<Property>,
+    /// Any other information about the frame, as key-value pairs.
+    pub info: Vec<(String, String)>,
+}
+
+impl Frame {
+    pub fn property(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+}
+
+/// A sequence of frames for the atoms of a molecule.
+#[derive(Clone, Debug, Default)]
+pub struct Trajectory {
+    /// The atoms whose positions are stored in each frame.
+    pub atoms: Vec<NodeIndex>,
+    pub frames: Vec<Frame>,
+}
+
+impl Trajectory {
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Moves the atoms of `molecule` to their positions in the frame at
+    /// `index`.  Bonding sites move along with their atoms.
+    pub fn apply_frame(&self, molecule: &mut Molecule, index: usize) {
+        let Some(frame) = self.frames.get(index) else {
+            return;
+        };
+        for (&atom, &pos) in self.atoms.iter().zip(frame.positions.iter()) {
+            let Some(node) = molecule.graph.node_weight_mut(atom) else {
+                continue;
+            };
+            let offset = pos - node.pos;
+            node.pos = pos;
+
+            let sites: Vec<NodeIndex> = molecule.bonding_sites(atom).collect();
+            for site in sites {
+                molecule.graph[site].pos += offset;
+            }
+        }
+    }
+}
+
+// End of File
@@ -0,0 +1,479 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Valence shell electron pair repulsion (VSEPR) theory: works out how many
+//! electron domains (bonds and lone pairs) surround an atom, and in which
+//! directions its bonds point.
+
+use crate::BondOrder;
+use glam::{Quat, Vec3};
+use periodic_table::{Block, Element};
+use std::f32::consts::{PI, TAU};
+
+/// The angle between any two bonds of a tetrahedral atom, acos(-1 / 3).
+pub const TETRAHEDRAL_ANGLE: f32 = 1.910_633_2;
+
+/// The arrangement of the electron domains around an atom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BondGeometry {
+    /// A single domain (s orbital only, i.e. H2)
+    Single,
+    /// sp hybridization (i.e. CO2)
+    Linear,
+    /// sp2 hybridization
+    TrigonalPlanar,
+    /// sp3 hybridization
+    Tetrahedral,
+    /// sp3d hybridization
+    TrigonalBipyramidal,
+    /// sp3d2 hybridization
+    Octahedral,
+    // TODO: Investigate wether or not we need to support hypervalent bonding or if this is enough.
+}
+
+// Directions at the given polar angle from the +z axis, evenly spaced around
+// it starting from the +x axis.
+fn ring(polar: f32, count: usize) -> impl Iterator<Item = Vec3> {
+    (0..count).map(move |i| {
+        let azimuthal = TAU * i as f32 / count as f32;
+        Vec3::new(
+            azimuthal.cos() * polar.sin(),
+            azimuthal.sin() * polar.sin(),
+            polar.cos(),
+        )
+    })
+}
+
+impl BondGeometry {
+    /// The number of electron domains in this geometry.
+    pub fn domains(self) -> usize {
+        self as usize + 1
+    }
+
+    /// Unit vectors pointing towards each of the electron domains.  The first
+    /// direction is always +z, and any remaining domains are spread evenly
+    /// around it: e.g. in the tetrahedral geometry the other three domains are
+    /// 120 degrees apart about the z axis.
+    pub fn directions(self) -> Vec<Vec3> {
+        let mut directions = vec![Vec3::Z];
+        match self {
+            BondGeometry::Single => {}
+            BondGeometry::Linear => directions.push(-Vec3::Z),
+            BondGeometry::TrigonalPlanar => directions.extend(ring(2.0 * PI / 3.0, 2)),
+            BondGeometry::Tetrahedral => directions.extend(ring(TETRAHEDRAL_ANGLE, 3)),
+            BondGeometry::TrigonalBipyramidal => {
+                directions.extend(ring(PI / 2.0, 3));
+                directions.push(-Vec3::Z);
+            }
+            BondGeometry::Octahedral => {
+                directions.extend(ring(PI / 2.0, 4));
+                directions.push(-Vec3::Z);
+            }
+        }
+        directions
+    }
+}
+
+/// The hybridization of an atom's orbitals, which follows from its steric
+/// number (the number of bonds and lone pairs around it).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hybridization {
+    S,
+    Sp,
+    Sp2,
+    Sp3,
+    Sp3d,
+    Sp3d2,
+}
+
+impl Hybridization {
+    pub fn from_steric_number(steric_number: usize) -> Option<Self> {
+        match steric_number {
+            1 => Some(Hybridization::S),
+            2 => Some(Hybridization::Sp),
+            3 => Some(Hybridization::Sp2),
+            4 => Some(Hybridization::Sp3),
+            5 => Some(Hybridization::Sp3d),
+            6 => Some(Hybridization::Sp3d2),
+            _ => None,
+        }
+    }
+
+    pub fn steric_number(self) -> usize {
+        self as usize + 1
+    }
+
+    pub fn geometry(self) -> BondGeometry {
+        match self {
+            Hybridization::S => BondGeometry::Single,
+            Hybridization::Sp => BondGeometry::Linear,
+            Hybridization::Sp2 => BondGeometry::TrigonalPlanar,
+            Hybridization::Sp3 => BondGeometry::Tetrahedral,
+            Hybridization::Sp3d => BondGeometry::TrigonalBipyramidal,
+            Hybridization::Sp3d2 => BondGeometry::Octahedral,
+        }
+    }
+}
+
+/// The electron domains surrounding an atom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Domains {
+    /// The number of sigma bonds, i.e. the number of bonded neighbours
+    /// (including bonding sites which have yet to be filled).
+    pub sigma_bonds: usize,
+    pub lone_pairs: usize,
+}
+
+impl Domains {
+    pub fn steric_number(&self) -> usize {
+        self.sigma_bonds + self.lone_pairs
+    }
+
+    pub fn hybridization(&self) -> Option<Hybridization> {
+        Hybridization::from_steric_number(self.steric_number())
+    }
+}
+
+/// The number of valence electrons of a main-group element, or `None` for the
+/// transition metals, lanthanides and actinides, for which VSEPR theory does
+/// not apply.
+pub fn valence_electrons(element: Element) -> Option<u8> {
+    match element.block() {
+        // Helium is in group 18, but has only two electrons
+        Block::S => Some(element.group()?.min(2)),
+        Block::P => Some(element.group()? - 10),
+        Block::D | Block::F => None,
+    }
+}
+
+/// Works out the electron domains around a neutral atom with bonds of the
+/// given orders.  The atom is given enough extra single bonds to reach its
+/// typical valence, and its remaining valence electrons form lone pairs.
+/// Transition metals, lanthanides and actinides are treated as octahedral.
+pub fn domains(element: Element, bond_orders: &[BondOrder]) -> Domains {
+    charged_domains(element, 0, bond_orders)
+}
+
+/// Like `domains`, for an atom with the given formal charge.  A charged atom
+/// bonds like a neutral atom with the same number of valence electrons, so
+/// e.g. the nitrogen of ammonium forms four bonds.
+pub fn charged_domains(element: Element, charge: i8, bond_orders: &[BondOrder]) -> Domains {
+    let existing_bonds = bond_orders.len();
+    let bonding_electrons: usize = bond_orders.iter().map(|&order| order as usize).sum();
+
+    let Some(valence) = valence_electrons(element) else {
+        return Domains {
+            sigma_bonds: existing_bonds.max(6),
+            lone_pairs: 0,
+        };
+    };
+    let valence = (i16::from(valence) - i16::from(charge)).clamp(0, 8) as usize;
+
+    // The number of covalent bonds a neutral atom typically forms: one per
+    // electron up to a half-filled shell, then one per missing electron.
+    // Helium's shell is full with only two electrons.
+    let typical_valence = match (element, valence) {
+        (Element::Helium, _) => 0,
+        (_, 0..=4) => valence,
+        (_, _) => 8 - valence,
+    };
+
+    let new_bonds = typical_valence.saturating_sub(bonding_electrons);
+    let sigma_bonds = existing_bonds + new_bonds;
+    let lone_pairs = valence.saturating_sub(bonding_electrons + new_bonds) / 2;
+
+    Domains {
+        sigma_bonds,
+        // Keep within the geometries we know how to lay out
+        lone_pairs: lone_pairs.min(6_usize.saturating_sub(sigma_bonds)),
+    }
+}
+
+// The domains which are occupied by lone pairs, in order of preference, as
+// indices into `BondGeometry::directions`.  The first domain is always kept for
+// a bond, as it is used for the bond to the atom's parent.  In a trigonal
+// bipyramid the equatorial positions (1-3) are taken first, and in an
+// octahedron lone pairs are placed opposite each other.
+fn lone_pair_domains(hybridization: Hybridization) -> &'static [usize] {
+    match hybridization {
+        Hybridization::S => &[],
+        Hybridization::Sp => &[1],
+        Hybridization::Sp2 => &[1, 2],
+        Hybridization::Sp3 => &[1, 2, 3],
+        Hybridization::Sp3d => &[1, 2, 3, 4],
+        Hybridization::Sp3d2 => &[1, 3, 2, 4, 5],
+    }
+}
+
+/// The directions of the sigma bonds of a neutral atom with bonds of the given
+/// orders (see `domains`).  The directions are relative to the atom's +z axis,
+/// which is the direction of the first bond.  The directions taken by lone
+/// pairs are left out, so e.g. nitrogen is trigonal pyramidal.
+pub fn bond_directions(element: Element, bond_orders: &[BondOrder]) -> Vec<Vec3> {
+    let domains = domains(element, bond_orders);
+    let Some(hybridization) = domains.hybridization() else {
+        return Vec::new();
+    };
+    if domains.sigma_bonds == 0 {
+        return Vec::new();
+    }
+
+    let lone_pairs = &lone_pair_domains(hybridization)[..domains.lone_pairs];
+    hybridization
+        .geometry()
+        .directions()
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !lone_pairs.contains(index))
+        .map(|(_, direction)| direction)
+        .collect()
+}
+
+/// The directions in which an atom with the given electron domains can form
+/// new bonds, given the directions of the bonds it already has.  The atom's
+/// geometry is turned to line up with the existing bonds as closely as
+/// possible, and the directions of the domains which are neither taken by an
+/// existing bond nor by a lone pair are returned.
+pub fn free_bond_directions(domains: Domains, bonds: &[Vec3]) -> Vec<Vec3> {
+    let Some(hybridization) = domains.hybridization() else {
+        return Vec::new();
+    };
+    let mut directions = hybridization.geometry().directions();
+    if bonds.len() > directions.len() {
+        return Vec::new();
+    }
+
+    // Align the first domain with the first bond, then twist the geometry
+    // about that bond so that another domain lines up with the second bond.
+    let mut rotation = Quat::IDENTITY;
+    if let Some(first) = bonds.first() {
+        rotation = Quat::from_rotation_arc(Vec3::Z, first.normalize());
+        if let Some(second) = bonds.get(1) {
+            let second = rotation.inverse() * second.normalize();
+            let polar = second.angle_between(Vec3::Z);
+            let closest = directions[1..]
+                .iter()
+                .min_by(|a, b| {
+                    let a = (a.angle_between(Vec3::Z) - polar).abs();
+                    let b = (b.angle_between(Vec3::Z) - polar).abs();
+                    a.total_cmp(&b)
+                })
+                .copied();
+            // A bond along the z axis can't be lined up by twisting about it
+            if let Some(closest) = closest {
+                if second.truncate().length() > 1e-4 && closest.truncate().length() > 1e-4 {
+                    let twist = second.y.atan2(second.x) - closest.y.atan2(closest.x);
+                    rotation *= Quat::from_rotation_z(twist);
+                }
+            }
+        }
+    }
+    for direction in directions.iter_mut() {
+        *direction = rotation * *direction;
+    }
+
+    // Each existing bond takes the free domain closest to it
+    let mut taken = vec![false; directions.len()];
+    for bond in bonds {
+        let closest = (0..directions.len())
+            .filter(|&index| !taken[index])
+            .min_by(|&a, &b| {
+                let a = directions[a].angle_between(*bond);
+                let b = directions[b].angle_between(*bond);
+                a.total_cmp(&b)
+            });
+        if let Some(index) = closest {
+            taken[index] = true;
+        }
+    }
+
+    // Lone pairs take their preferred domains among those remaining
+    let lone_pairs: Vec<usize> = lone_pair_domains(hybridization)
+        .iter()
+        .copied()
+        .filter(|&index| !taken[index])
+        .take(domains.lone_pairs)
+        .collect();
+    for index in lone_pairs {
+        taken[index] = true;
+    }
+
+    directions
+        .into_iter()
+        .zip(taken)
+        .filter(|(_, taken)| !taken)
+        .map(|(direction, _)| direction)
+        .take(domains.sigma_bonds.saturating_sub(bonds.len()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    // The angles between every pair of directions, in ascending order.
+    fn pairwise_angles(directions: &[Vec3]) -> Vec<f32> {
+        let mut angles = Vec::new();
+        for (i, a) in directions.iter().enumerate() {
+            for b in &directions[i + 1..] {
+                angles.push(a.angle_between(*b));
+            }
+        }
+        angles.sort_by(f32::total_cmp);
+        angles
+    }
+
+    fn assert_geometry(geometry: BondGeometry, expected: &[(usize, f32)]) {
+        let directions = geometry.directions();
+        assert_eq!(directions.len(), geometry.domains());
+        assert!(directions[0].abs_diff_eq(Vec3::Z, EPSILON));
+        for direction in directions.iter() {
+            assert!((direction.length() - 1.0).abs() < EPSILON);
+        }
+
+        let mut expected_angles = Vec::new();
+        for &(count, angle) in expected {
+            expected_angles.extend(vec![angle; count]);
+        }
+        expected_angles.sort_by(f32::total_cmp);
+
+        let angles = pairwise_angles(&directions);
+        assert_eq!(angles.len(), expected_angles.len());
+        for (angle, expected_angle) in angles.iter().zip(expected_angles) {
+            assert!(
+                (angle - expected_angle).abs() < EPSILON,
+                "{:?}: found an angle of {} radians, expected {}",
+                geometry,
+                angle,
+                expected_angle
+            );
+        }
+    }
+
+    #[test]
+    fn single() {
+        assert_geometry(BondGeometry::Single, &[]);
+    }
+
+    #[test]
+    fn linear() {
+        assert_geometry(BondGeometry::Linear, &[(1, PI)]);
+    }
+
+    #[test]
+    fn trigonal_planar() {
+        assert_geometry(BondGeometry::TrigonalPlanar, &[(3, 2.0 * PI / 3.0)]);
+    }
+
+    #[test]
+    fn tetrahedral() {
+        assert_geometry(BondGeometry::Tetrahedral, &[(6, TETRAHEDRAL_ANGLE)]);
+        assert!((TETRAHEDRAL_ANGLE - (-1.0f32 / 3.0).acos()).abs() < EPSILON);
+    }
+
+    #[test]
+    fn trigonal_bipyramidal() {
+        assert_geometry(
+            BondGeometry::TrigonalBipyramidal,
+            &[(1, PI), (6, PI / 2.0), (3, 2.0 * PI / 3.0)],
+        );
+    }
+
+    #[test]
+    fn octahedral() {
+        assert_geometry(BondGeometry::Octahedral, &[(3, PI), (12, PI / 2.0)]);
+    }
+
+    #[test]
+    fn hybridization_geometry() {
+        for steric_number in 1..=6 {
+            let hybridization = Hybridization::from_steric_number(steric_number).unwrap();
+            assert_eq!(hybridization.steric_number(), steric_number);
+            assert_eq!(hybridization.geometry().domains(), steric_number);
+        }
+        assert_eq!(Hybridization::from_steric_number(0), None);
+        assert_eq!(Hybridization::from_steric_number(7), None);
+    }
+
+    #[test]
+    fn element_geometry() {
+        // Methane-like carbon is tetrahedral
+        let carbon = bond_directions(Element::Carbon, &[]);
+        assert_eq!(carbon.len(), 4);
+        for angle in pairwise_angles(&carbon) {
+            assert!((angle - TETRAHEDRAL_ANGLE).abs() < EPSILON);
+        }
+
+        // Nitrogen is trigonal pyramidal, with a lone pair in the fourth
+        // tetrahedral direction
+        let nitrogen = bond_directions(Element::Nitrogen, &[1]);
+        assert_eq!(nitrogen.len(), 3);
+        for angle in pairwise_angles(&nitrogen) {
+            assert!((angle - TETRAHEDRAL_ANGLE).abs() < EPSILON);
+        }
+
+        // Oxygen is bent, and hydrogen has a single bond
+        assert_eq!(bond_directions(Element::Oxygen, &[1]).len(), 2);
+        assert_eq!(bond_directions(Element::Hydrogen, &[1]), vec![Vec3::Z]);
+
+        // A double bonded carbon is trigonal planar
+        assert_eq!(
+            domains(Element::Carbon, &[2]).hybridization(),
+            Some(Hybridization::Sp2)
+        );
+    }
+
+    #[test]
+    fn valence() {
+        assert_eq!(valence_electrons(Element::Hydrogen), Some(1));
+        assert_eq!(valence_electrons(Element::Helium), Some(2));
+        assert_eq!(valence_electrons(Element::Carbon), Some(4));
+        assert_eq!(valence_electrons(Element::Nitrogen), Some(5));
+        assert_eq!(valence_electrons(Element::Oxygen), Some(6));
+        assert_eq!(valence_electrons(Element::Chlorine), Some(7));
+        assert_eq!(valence_electrons(Element::Iron), None);
+    }
+
+    #[test]
+    fn domain_counts() {
+        let counts = |element, bond_orders: &[BondOrder]| {
+            let domains = domains(element, bond_orders);
+            (domains.sigma_bonds, domains.lone_pairs)
+        };
+        // Methane, ammonia and water are all sp3, with zero to two lone pairs
+        assert_eq!(counts(Element::Carbon, &[]), (4, 0));
+        assert_eq!(counts(Element::Nitrogen, &[]), (3, 1));
+        assert_eq!(counts(Element::Oxygen, &[]), (2, 2));
+        assert_eq!(
+            domains(Element::Oxygen, &[1, 1]).hybridization(),
+            Some(Hybridization::Sp3)
+        );
+
+        // In carbon dioxide the carbon is linear, and each oxygen has a double
+        // bond and two lone pairs
+        assert_eq!(counts(Element::Carbon, &[2, 2]), (2, 0));
+        assert_eq!(
+            domains(Element::Carbon, &[2, 2]).hybridization(),
+            Some(Hybridization::Sp)
+        );
+        assert_eq!(counts(Element::Oxygen, &[2]), (1, 2));
+
+        // Transition metals are octahedral
+        assert_eq!(counts(Element::Iron, &[]), (6, 0));
+    }
+
+    #[test]
+    fn charged_atoms() {
+        // Ammonium is tetrahedral with no lone pairs, and the oxygen of
+        // hydroxide has a single bond and three lone pairs
+        let ammonium = charged_domains(Element::Nitrogen, 1, &[]);
+        assert_eq!((ammonium.sigma_bonds, ammonium.lone_pairs), (4, 0));
+        let hydroxide = charged_domains(Element::Oxygen, -1, &[]);
+        assert_eq!((hydroxide.sigma_bonds, hydroxide.lone_pairs), (1, 3));
+    }
+}
+
+// End of File
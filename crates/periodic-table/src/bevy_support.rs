@@ -0,0 +1,30 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Conversions to Bevy's types, enabled by the `bevy` feature.  Element data
+// can also be copied straight into GPU buffers.
+
+use crate::{ColorScheme, Element, ElementRepr};
+use bevy::prelude::Color;
+
+impl ElementRepr {
+    pub fn bevy_color(&self) -> Color {
+        let [r, g, b] = self.color;
+        Color::rgb(r, g, b)
+    }
+}
+
+// SAFETY: `ElementRepr` is `#[repr(C)]` and made only of `f32`s, so it has no
+// padding and every bit pattern is a valid value.
+unsafe impl bytemuck::Zeroable for ElementRepr {}
+unsafe impl bytemuck::Pod for ElementRepr {}
+
+impl ColorScheme {
+    pub fn bevy_color(&self, element: Element) -> Color {
+        let [r, g, b] = self.color(element);
+        Color::rgb(r, g, b)
+    }
+}
+
+// End of File
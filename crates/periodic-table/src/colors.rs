@@ -0,0 +1,154 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Color schemes, which give each element a color to draw its atoms in.
+//! atomCAD comes with its own scheme and the CPK, Jmol and RasMol schemes,
+//! and with the `toml` feature more can be loaded with
+//! `ColorScheme::from_toml`.
+
+use crate::data::JMOL_COLORS;
+use crate::Element;
+
+/// A color for every element, as red, green and blue components between 0 and
+/// 1 in the sRGB color space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorScheme {
+    pub name: String,
+    pub(crate) colors: Vec<[f32; 3]>,
+}
+
+// Converts a color written as 0xRRGGBB.
+pub(crate) fn rgb(hex: u32) -> [f32; 3] {
+    let [_, r, g, b] = hex.to_be_bytes();
+    [r, g, b].map(|component| component as f32 / 255.0)
+}
+
+impl ColorScheme {
+    fn from_fn(name: &str, color: impl Fn(Element) -> [f32; 3]) -> Self {
+        ColorScheme {
+            name: name.to_owned(),
+            colors: Element::all().map(color).collect(),
+        }
+    }
+
+    /// The Jmol colors, with a few common elements in atomCAD's own, more
+    /// muted colors.
+    pub fn atomcad() -> Self {
+        let mut scheme = Self::jmol();
+        scheme.name = "atomCAD".to_owned();
+        let custom_colors = [
+            (Element::Hydrogen, [0.8510, 0.8510, 0.8510]), // white
+            (Element::Carbon, [0.30196, 0.2902, 0.3098]),  // dark grey
+            (Element::Oxygen, [0.7490, 0.2118, 0.3176]),   // red
+            // (Element::Silicon, [0.7294, 0.5804, 0.1686]), // yellow
+            (Element::Silicon, [0.5234, 0.5234, 0.5234]), // light grey
+            (Element::Phosphorus, [0.7019, 0.4314, 0.1451]), // orange
+            (Element::Nitrogen, [0.2078, 0.4549, 0.6118]), // blue
+            (Element::Sulfur, [0.7294, 0.5804, 0.1686]),  // yellow
+        ];
+        for (element, color) in custom_colors {
+            scheme.set_color(element, color);
+        }
+        scheme
+    }
+
+    /// The Corey-Pauling-Koltun colors, which only distinguish the common
+    /// elements of organic chemistry and color the rest pink.
+    pub fn cpk() -> Self {
+        Self::from_fn("CPK", |element| {
+            rgb(match element {
+                Element::Hydrogen => 0xFFFFFF,
+                Element::Carbon => 0x222222,
+                Element::Nitrogen => 0x2233FF,
+                Element::Oxygen => 0xFF2200,
+                Element::Fluorine | Element::Chlorine => 0x1FF01F,
+                Element::Bromine => 0x992200,
+                Element::Iodine => 0x6600BB,
+                Element::Phosphorus => 0xFF9900,
+                Element::Sulfur => 0xDDDD00,
+                Element::Boron => 0xFFAA77,
+                Element::Titanium => 0x999999,
+                Element::Iron => 0xDD7700,
+                _ => match element.group() {
+                    Some(18) => 0x00FFFF,
+                    Some(1) => 0x7700FF,
+                    Some(2) => 0x007700,
+                    _ => 0xDD77FF,
+                },
+            })
+        })
+    }
+
+    /// The default colors of the Jmol molecule viewer.
+    pub fn jmol() -> Self {
+        Self::from_fn("Jmol", |element| rgb(JMOL_COLORS[element as usize - 1]))
+    }
+
+    /// The colors of RasMol's `cpk` scheme.
+    pub fn rasmol() -> Self {
+        Self::from_fn("RasMol", |element| {
+            use Element::*;
+            rgb(match element {
+                Hydrogen => 0xFFFFFF,
+                Helium => 0xFFC0CB,
+                Lithium => 0xB22222,
+                Carbon => 0xC8C8C8,
+                Nitrogen => 0x8F8FFF,
+                Oxygen => 0xF00000,
+                Sodium => 0x0000FF,
+                Magnesium => 0x228B22,
+                Sulfur => 0xFFC832,
+                Iodine => 0xA020F0,
+                Boron | Chlorine => 0x00FF00,
+                Phosphorus | Iron | Barium => 0xFFA500,
+                Nickel | Copper | Zinc | Bromine => 0xA52A2A,
+                Aluminium | Calcium | Titanium | Chromium | Manganese | Silver => 0x808090,
+                Fluorine | Silicon | Gold => 0xDAA520,
+                _ => 0xFF1493,
+            })
+        })
+    }
+
+    /// All of the built-in schemes, the default first.
+    pub fn builtin() -> Vec<Self> {
+        vec![Self::atomcad(), Self::cpk(), Self::jmol(), Self::rasmol()]
+    }
+
+    pub fn color(&self, element: Element) -> [f32; 3] {
+        self.colors[element as usize - 1]
+    }
+
+    pub fn set_color(&mut self, element: Element, color: [f32; 3]) {
+        self.colors[element as usize - 1] = color;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin() {
+        let schemes = ColorScheme::builtin();
+        assert_eq!(schemes[0], ColorScheme::atomcad());
+        for scheme in &schemes {
+            assert_eq!(scheme.colors.len(), 118);
+        }
+
+        let cpk = ColorScheme::cpk();
+        assert_eq!(cpk.color(Element::Argon), rgb(0x00FFFF));
+        assert_eq!(cpk.color(Element::Potassium), rgb(0x7700FF));
+        assert_eq!(cpk.color(Element::Hydrogen), rgb(0xFFFFFF));
+        assert_eq!(cpk.color(Element::Calcium), rgb(0x007700));
+        assert_eq!(cpk.color(Element::Gold), rgb(0xDD77FF));
+        assert_eq!(ColorScheme::jmol().color(Element::Oxygen), rgb(0xFF0D0D));
+        assert_eq!(ColorScheme::rasmol().color(Element::Gold), rgb(0xDAA520));
+        assert_eq!(
+            ColorScheme::atomcad().color(Element::Chlorine),
+            ColorScheme::jmol().color(Element::Chlorine)
+        );
+    }
+}
+
+// End of File
@@ -0,0 +1,371 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Per-element reference data.  Every table is indexed by atomic number - 1,
+// and a value of 0.0 marks a quantity which is unknown for that element.
+
+// Chemical symbols.
+pub(crate) static SYMBOLS: [&str; 118] = [
+    "H", "He", "Li", "Be", "B", "C", "N", "O", "F", "Ne", "Na", "Mg", "Al", "Si", "P", "S", "Cl",
+    "Ar", "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As",
+    "Se", "Br", "Kr", "Rb", "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In",
+    "Sn", "Sb", "Te", "I", "Xe", "Cs", "Ba", "La", "Ce", "Pr", "Nd", "Pm", "Sm", "Eu", "Gd", "Tb",
+    "Dy", "Ho", "Er", "Tm", "Yb", "Lu", "Hf", "Ta", "W", "Re", "Os", "Ir", "Pt", "Au", "Hg", "Tl",
+    "Pb", "Bi", "Po", "At", "Rn", "Fr", "Ra", "Ac", "Th", "Pa", "U", "Np", "Pu", "Am", "Cm", "Bk",
+    "Cf", "Es", "Fm", "Md", "No", "Lr", "Rf", "Db", "Sg", "Bh", "Hs", "Mt", "Ds", "Rg", "Cn", "Nh",
+    "Fl", "Mc", "Lv", "Ts", "Og",
+];
+
+// English names, spelled as in the `Element` enum.
+pub(crate) static NAMES: [&str; 118] = [
+    "Hydrogen",
+    "Helium",
+    "Lithium",
+    "Beryllium",
+    "Boron",
+    "Carbon",
+    "Nitrogen",
+    "Oxygen",
+    "Fluorine",
+    "Neon",
+    "Sodium",
+    "Magnesium",
+    "Aluminium",
+    "Silicon",
+    "Phosphorus",
+    "Sulfur",
+    "Chlorine",
+    "Argon",
+    "Potassium",
+    "Calcium",
+    "Scandium",
+    "Titanium",
+    "Vanadium",
+    "Chromium",
+    "Manganese",
+    "Iron",
+    "Cobalt",
+    "Nickel",
+    "Copper",
+    "Zinc",
+    "Gallium",
+    "Germanium",
+    "Arsenic",
+    "Selenium",
+    "Bromine",
+    "Krypton",
+    "Rubidium",
+    "Strontium",
+    "Yttrium",
+    "Zirconium",
+    "Niobium",
+    "Molybdenum",
+    "Technetium",
+    "Ruthenium",
+    "Rhodium",
+    "Palladium",
+    "Silver",
+    "Cadmium",
+    "Indium",
+    "Tin",
+    "Antimony",
+    "Tellurium",
+    "Iodine",
+    "Xenon",
+    "Cesium",
+    "Barium",
+    "Lanthanum",
+    "Cerium",
+    "Praseodymium",
+    "Neodymium",
+    "Promethium",
+    "Samarium",
+    "Europium",
+    "Gadolinium",
+    "Terbium",
+    "Dysprosium",
+    "Holmium",
+    "Erbium",
+    "Thulium",
+    "Ytterbium",
+    "Lutetium",
+    "Hafnium",
+    "Tantalum",
+    "Tungsten",
+    "Rhenium",
+    "Osmium",
+    "Iridium",
+    "Platinum",
+    "Gold",
+    "Mercury",
+    "Thallium",
+    "Lead",
+    "Bismuth",
+    "Polonium",
+    "Astatine",
+    "Radon",
+    "Francium",
+    "Radium",
+    "Actinium",
+    "Thorium",
+    "Protactinium",
+    "Uranium",
+    "Neptunium",
+    "Plutonium",
+    "Americium",
+    "Curium",
+    "Berkelium",
+    "Californium",
+    "Einsteinium",
+    "Fermium",
+    "Mendelevium",
+    "Nobelium",
+    "Lawrencium",
+    "Rutherfordium",
+    "Dubnium",
+    "Seaborgium",
+    "Bohrium",
+    "Hassium",
+    "Meitnerium",
+    "Darmstadtium",
+    "Roentgenium",
+    "Copernicium",
+    "Nihonium",
+    "Flerovium",
+    "Moscovium",
+    "Livermorium",
+    "Tennessine",
+    "Oganesson",
+];
+
+// Standard atomic weights in daltons, abridged from the IUPAC values.
+// Elements without stable isotopes are given the mass number of their
+// longest-lived known isotope.
+pub(crate) static ATOMIC_MASSES: [f32; 118] = [
+    1.008, 4.0026, 6.94, 9.0122, 10.81, 12.011, 14.007, 15.999, 18.998, 20.180, 22.990, 24.305,
+    26.982, 28.085, 30.974, 32.06, 35.45, 39.948, 39.098, 40.078, 44.956, 47.867, 50.942, 51.996,
+    54.938, 55.845, 58.933, 58.693, 63.546, 65.38, 69.723, 72.630, 74.922, 78.971, 79.904, 83.798,
+    85.468, 87.62, 88.906, 91.224, 92.906, 95.95, 98.0, 101.07, 102.91, 106.42, 107.87, 112.41,
+    114.82, 118.71, 121.76, 127.60, 126.90, 131.29, 132.91, 137.33, 138.91, 140.12, 140.91, 144.24,
+    145.0, 150.36, 151.96, 157.25, 158.93, 162.50, 164.93, 167.26, 168.93, 173.05, 174.97, 178.49,
+    180.95, 183.84, 186.21, 190.23, 192.22, 195.08, 196.97, 200.59, 204.38, 207.2, 208.98, 209.0,
+    210.0, 222.0, 223.0, 226.0, 227.0, 232.04, 231.04, 238.03, 237.0, 244.0, 243.0, 247.0, 247.0,
+    251.0, 252.0, 257.0, 258.0, 259.0, 266.0, 267.0, 268.0, 269.0, 270.0, 269.0, 278.0, 281.0,
+    282.0, 285.0, 286.0, 289.0, 290.0, 293.0, 294.0, 294.0,
+];
+
+// Single-bond covalent radii in angstroms.  From P. Pyykkö and M. Atsumi,
+// "Molecular Single-Bond Covalent Radii for Elements 1-118", Chemistry - A
+// European Journal 15, 186-197 (2009).
+pub(crate) static COVALENT_RADII: [f32; 118] = [
+    0.32, 0.46, 1.33, 1.02, 0.85, 0.75, 0.71, 0.63, 0.64, 0.67, 1.55, 1.39, 1.26, 1.16, 1.11, 1.03,
+    0.99, 0.96, 1.96, 1.71, 1.48, 1.36, 1.34, 1.22, 1.19, 1.16, 1.11, 1.10, 1.12, 1.18, 1.24, 1.21,
+    1.21, 1.16, 1.14, 1.17, 2.10, 1.85, 1.63, 1.54, 1.47, 1.38, 1.28, 1.25, 1.25, 1.20, 1.28, 1.36,
+    1.42, 1.40, 1.40, 1.36, 1.33, 1.31, 2.32, 1.96, 1.80, 1.63, 1.76, 1.74, 1.73, 1.72, 1.68, 1.69,
+    1.68, 1.67, 1.66, 1.65, 1.64, 1.70, 1.62, 1.52, 1.46, 1.37, 1.31, 1.29, 1.22, 1.23, 1.24, 1.33,
+    1.44, 1.44, 1.51, 1.45, 1.47, 1.42, 2.23, 2.01, 1.86, 1.75, 1.69, 1.70, 1.71, 1.72, 1.66, 1.66,
+    1.68, 1.68, 1.65, 1.67, 1.73, 1.76, 1.61, 1.57, 1.49, 1.43, 1.41, 1.34, 1.29, 1.28, 1.21, 1.22,
+    1.36, 1.43, 1.62, 1.75, 1.65, 1.57,
+];
+
+// Double-bond covalent radii in angstroms.  From P. Pyykkö and M. Atsumi,
+// "Molecular Double-Bond Covalent Radii for Elements Li-E112", Chemistry - A
+// European Journal 15, 12770-12779 (2009).
+pub(crate) static DOUBLE_BOND_RADII: [f32; 118] = [
+    0.0, 0.0, 1.24, 0.90, 0.78, 0.67, 0.60, 0.57, 0.59, 0.96, 1.60, 1.32, 1.13, 1.07, 1.02, 0.94,
+    0.95, 1.07, 1.93, 1.47, 1.16, 1.17, 1.12, 1.11, 1.05, 1.09, 1.03, 1.01, 1.15, 1.20, 1.17, 1.11,
+    1.14, 1.07, 1.09, 1.21, 2.02, 1.57, 1.30, 1.27, 1.25, 1.21, 1.20, 1.14, 1.10, 1.17, 1.39, 1.44,
+    1.36, 1.30, 1.33, 1.28, 1.29, 1.35, 2.09, 1.61, 1.39, 1.37, 1.38, 1.37, 1.35, 1.34, 1.34, 1.35,
+    1.35, 1.33, 1.33, 1.33, 1.31, 1.29, 1.31, 1.28, 1.26, 1.20, 1.19, 1.16, 1.15, 1.12, 1.21, 1.42,
+    1.42, 1.35, 1.41, 1.35, 1.38, 1.45, 2.18, 1.73, 1.53, 1.43, 1.38, 1.34, 1.36, 1.35, 1.35, 1.36,
+    1.39, 1.40, 1.40, 0.0, 1.39, 0.0, 1.41, 1.40, 1.36, 1.28, 1.28, 1.25, 1.25, 1.16, 1.16, 1.37,
+    0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+];
+
+// Triple-bond covalent radii in angstroms.  From P. Pyykkö, S. Riedel and M.
+// Patzschke, "Triple-Bond Covalent Radii", Chemistry - A European Journal 11,
+// 3511-3520 (2005).
+pub(crate) static TRIPLE_BOND_RADII: [f32; 118] = [
+    0.0, 0.0, 0.0, 0.85, 0.73, 0.60, 0.54, 0.53, 0.53, 0.0, 0.0, 1.27, 1.11, 1.02, 0.94, 0.95,
+    0.93, 0.96, 0.0, 1.33, 1.14, 1.08, 1.06, 1.03, 1.03, 1.02, 0.96, 1.01, 1.20, 0.0, 1.21, 1.14,
+    1.06, 1.07, 1.10, 1.08, 0.0, 1.39, 1.24, 1.21, 1.16, 1.13, 1.10, 1.03, 1.06, 1.12, 1.37, 0.0,
+    1.46, 1.32, 1.27, 1.21, 1.25, 1.22, 0.0, 1.49, 1.39, 1.31, 1.28, 0.0, 0.0, 0.0, 0.0, 1.32, 0.0,
+    0.0, 0.0, 0.0, 0.0, 0.0, 1.31, 1.22, 1.19, 1.15, 1.10, 1.09, 1.07, 1.10, 1.23, 0.0, 1.50, 1.37,
+    1.35, 1.29, 1.38, 1.33, 0.0, 1.59, 1.40, 1.36, 1.29, 1.18, 1.16, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+    0.0, 0.0, 0.0, 0.0, 1.31, 1.26, 1.21, 1.19, 1.18, 1.13, 1.12, 1.18, 1.30, 0.0, 0.0, 0.0, 0.0,
+    0.0, 0.0,
+];
+
+// Van der Waals radii in angstroms.  From A. Bondi, "van der Waals Volumes and
+// Radii", Journal of Physical Chemistry 68, 441-451 (1964), with the
+// main-group elements Bondi left out taken from M. Mantina et al.,
+// "Consistent van der Waals Radii for the Whole Main Group", Journal of
+// Physical Chemistry A 113, 5806-5812 (2009).
+pub(crate) static VAN_DER_WAALS_RADII: [f32; 118] = [
+    1.20, 1.40, 1.82, 1.53, 1.92, 1.70, 1.55, 1.52, 1.47, 1.54, 2.27, 1.73, 1.84, 2.10, 1.80, 1.80,
+    1.75, 1.88, 2.75, 2.31, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.63, 1.40, 1.39, 1.87, 2.11, 1.85,
+    1.90, 1.85, 2.02, 3.03, 2.49, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.63, 1.72, 1.58, 1.93, 2.17,
+    2.06, 2.06, 1.98, 2.16, 3.43, 2.68, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+    0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.75, 1.66, 1.55, 1.96, 2.02, 2.07, 1.97, 2.02,
+    2.20, 3.48, 2.83, 0.0, 0.0, 0.0, 1.86, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+    0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+];
+
+// Pauling electronegativities.
+pub(crate) static ELECTRONEGATIVITIES: [f32; 118] = [
+    2.20, 0.0, 0.98, 1.57, 2.04, 2.55, 3.04, 3.44, 3.98, 0.0, 0.93, 1.31, 1.61, 1.90, 2.19, 2.58,
+    3.16, 0.0, 0.82, 1.00, 1.36, 1.54, 1.63, 1.66, 1.55, 1.83, 1.88, 1.91, 1.90, 1.65, 1.81, 2.01,
+    2.18, 2.55, 2.96, 3.00, 0.82, 0.95, 1.22, 1.33, 1.6, 2.16, 1.9, 2.2, 2.28, 2.20, 1.93, 1.69,
+    1.78, 1.96, 2.05, 2.1, 2.66, 2.6, 0.79, 0.89, 1.10, 1.12, 1.13, 1.14, 1.13, 1.17, 1.2, 1.2,
+    1.1, 1.22, 1.23, 1.24, 1.25, 1.1, 1.27, 1.3, 1.5, 2.36, 1.9, 2.2, 2.20, 2.28, 2.54, 2.00, 1.62,
+    2.33, 2.02, 2.0, 2.2, 2.2, 0.7, 0.9, 1.1, 1.3, 1.5, 1.38, 1.36, 1.28, 1.13, 1.28, 1.3, 1.3,
+    1.3, 1.3, 1.3, 1.3, 1.3, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+    0.0,
+];
+
+// The number of bonds an atom of each element typically forms (or, for the
+// metals, its common oxidation states), most common first.  The noble gases
+// are listed with a valence of zero, and elements too short-lived to have a
+// known chemistry have none.
+pub(crate) static VALENCES: [&[u8]; 118] = [
+    &[1],
+    &[0],
+    &[1],
+    &[2],
+    &[3],
+    &[4],
+    &[3],
+    &[2],
+    &[1],
+    &[0],
+    &[1],
+    &[2],
+    &[3],
+    &[4],
+    &[3, 5],
+    &[2, 4, 6],
+    &[1, 3, 5, 7],
+    &[0],
+    &[1],
+    &[2],
+    &[3],
+    &[4, 3],
+    &[5, 4, 3],
+    &[3, 6, 2],
+    &[2, 4, 7],
+    &[2, 3],
+    &[2, 3],
+    &[2],
+    &[2, 1],
+    &[2],
+    &[3],
+    &[4, 2],
+    &[3, 5],
+    &[2, 4, 6],
+    &[1, 3, 5],
+    &[0, 2],
+    &[1],
+    &[2],
+    &[3],
+    &[4],
+    &[5, 3],
+    &[6, 4],
+    &[7, 4],
+    &[3, 4],
+    &[3],
+    &[2, 4],
+    &[1],
+    &[2],
+    &[3, 1],
+    &[4, 2],
+    &[3, 5],
+    &[2, 4, 6],
+    &[1, 3, 5, 7],
+    &[0, 2, 4, 6],
+    &[1],
+    &[2],
+    &[3],
+    &[3, 4],
+    &[3],
+    &[3],
+    &[3],
+    &[3, 2],
+    &[3, 2],
+    &[3],
+    &[3, 4],
+    &[3],
+    &[3],
+    &[3],
+    &[3, 2],
+    &[3, 2],
+    &[3],
+    &[4],
+    &[5],
+    &[6, 4],
+    &[7, 4],
+    &[4, 8],
+    &[3, 4],
+    &[2, 4],
+    &[3, 1],
+    &[2, 1],
+    &[1, 3],
+    &[2, 4],
+    &[3, 5],
+    &[2, 4],
+    &[1],
+    &[0, 2],
+    &[1],
+    &[2],
+    &[3],
+    &[4],
+    &[5],
+    &[6, 4],
+    &[5],
+    &[4],
+    &[3],
+    &[3],
+    &[3],
+    &[3],
+    &[3],
+    &[3],
+    &[3],
+    &[2],
+    &[3],
+    &[4],
+    &[5],
+    &[6],
+    &[7],
+    &[8],
+    &[],
+    &[],
+    &[],
+    &[],
+    &[],
+    &[],
+    &[],
+    &[],
+    &[],
+    &[],
+];
+
+// The colors used by Jmol, as 0xRRGGBB.  Jmol has no colors for the elements
+// after meitnerium, which are given its color for unknown elements.
+pub(crate) static JMOL_COLORS: [u32; 118] = [
+    0xFFFFFF, 0xD9FFFF, 0xCC80FF, 0xC2FF00, 0xFFB5B5, 0x909090, 0x3050F8, 0xFF0D0D, 0x90E050,
+    0xB3E3F5, 0xAB5CF2, 0x8AFF00, 0xBFA6A6, 0xF0C8A0, 0xFF8000, 0xFFFF30, 0x1FF01F, 0x80D1E3,
+    0x8F40D4, 0x3DFF00, 0xE6E6E6, 0xBFC2C7, 0xA6A6AB, 0x8A99C7, 0x9C7AC7, 0xE06633, 0xF090A0,
+    0x50D050, 0xC88033, 0x7D80B0, 0xC28F8F, 0x668F8F, 0xBD80E3, 0xFFA100, 0xA62929, 0x5CB8D1,
+    0x702EB0, 0x00FF00, 0x94FFFF, 0x94E0E0, 0x73C2C9, 0x54B5B5, 0x3B9E9E, 0x248F8F, 0x0A7D8C,
+    0x006985, 0xC0C0C0, 0xFFD98F, 0xA67573, 0x668080, 0x9E63B5, 0xD47A00, 0x940094, 0x429EB0,
+    0x57178F, 0x00C900, 0x70D4FF, 0xFFFFC7, 0xD9FFC7, 0xC7FFC7, 0xA3FFC7, 0x8FFFC7, 0x61FFC7,
+    0x45FFC7, 0x30FFC7, 0x1FFFC7, 0x00FF9C, 0x00E675, 0x00D452, 0x00BF38, 0x00AB24, 0x4DC2FF,
+    0x4DA6FF, 0x2194D6, 0x267DAB, 0x266696, 0x175487, 0xD0D0E0, 0xFFD123, 0xB8B8D0, 0xA6544D,
+    0x575961, 0x9E4FB5, 0xAB5C00, 0x754F45, 0x428296, 0x420066, 0x007D00, 0x70ABFA, 0x00BAFF,
+    0x00A1FF, 0x008FFF, 0x0080FF, 0x006BFF, 0x545CF2, 0x785CE3, 0x8A4FE3, 0xA136D4, 0xB31FD4,
+    0xB31FBA, 0xB30DA6, 0xBD0D87, 0xC70066, 0xCC0059, 0xD1004F, 0xD90045, 0xE00038, 0xE6002E,
+    0xEB0026, 0xFF1493, 0xFF1493, 0xFF1493, 0xFF1493, 0xFF1493, 0xFF1493, 0xFF1493, 0xFF1493,
+    0xFF1493,
+];
+
+// End of File
@@ -0,0 +1,209 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Isotopes and their nuclear data.  The table covers the naturally occurring
+//! isotopes of the elements up to argon and of a few heavier elements common
+//! in molecular designs, plus tritium, carbon-14 and plutonium-239.  Masses
+//! are from the 2016 Atomic Mass Evaluation and abundances from the IUPAC
+//! isotopic compositions.
+
+use crate::Element;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Isotope {
+    pub element: Element,
+    /// The number of protons and neutrons in the nucleus.
+    pub mass_number: u16,
+    /// The mass of a neutral atom, in daltons.
+    pub mass: f64,
+    /// The fraction of the element's atoms in nature which are this isotope,
+    /// from 0 to 1.  Zero for isotopes which do not occur naturally in
+    /// significant amounts.
+    pub abundance: f64,
+    /// The spin of the nucleus in its ground state, in units of ħ.
+    pub spin: f32,
+}
+
+const fn isotope(
+    element: Element,
+    mass_number: u16,
+    mass: f64,
+    abundance: f64,
+    spin: f32,
+) -> Isotope {
+    Isotope {
+        element,
+        mass_number,
+        mass,
+        abundance,
+        spin,
+    }
+}
+
+use Element::*;
+
+// Sorted by atomic number and then by mass number.
+static ISOTOPES: [Isotope; 75] = [
+    isotope(Hydrogen, 1, 1.007_825_032_23, 0.999_885, 0.5),
+    isotope(Hydrogen, 2, 2.014_101_778_12, 0.000_115, 1.0),
+    isotope(Hydrogen, 3, 3.016_049_277_9, 0.0, 0.5),
+    isotope(Helium, 3, 3.016_029_320_1, 0.000_001_34, 0.5),
+    isotope(Helium, 4, 4.002_603_254_13, 0.999_998_66, 0.0),
+    isotope(Lithium, 6, 6.015_122_887_4, 0.0759, 1.0),
+    isotope(Lithium, 7, 7.016_003_436_6, 0.9241, 1.5),
+    isotope(Beryllium, 9, 9.012_183_065, 1.0, 1.5),
+    isotope(Boron, 10, 10.012_936_95, 0.199, 3.0),
+    isotope(Boron, 11, 11.009_305_36, 0.801, 1.5),
+    isotope(Carbon, 12, 12.0, 0.9893, 0.0),
+    isotope(Carbon, 13, 13.003_354_835_07, 0.0107, 0.5),
+    isotope(Carbon, 14, 14.003_241_988_4, 0.0, 0.0),
+    isotope(Nitrogen, 14, 14.003_074_004_43, 0.996_36, 1.0),
+    isotope(Nitrogen, 15, 15.000_108_898_88, 0.003_64, 0.5),
+    isotope(Oxygen, 16, 15.994_914_619_57, 0.997_57, 0.0),
+    isotope(Oxygen, 17, 16.999_131_756_50, 0.000_38, 2.5),
+    isotope(Oxygen, 18, 17.999_159_612_86, 0.002_05, 0.0),
+    isotope(Fluorine, 19, 18.998_403_162_73, 1.0, 0.5),
+    isotope(Neon, 20, 19.992_440_176_2, 0.9048, 0.0),
+    isotope(Neon, 21, 20.993_846_685, 0.0027, 1.5),
+    isotope(Neon, 22, 21.991_385_114, 0.0925, 0.0),
+    isotope(Sodium, 23, 22.989_769_282_0, 1.0, 1.5),
+    isotope(Magnesium, 24, 23.985_041_697, 0.7899, 0.0),
+    isotope(Magnesium, 25, 24.985_836_976, 0.1000, 2.5),
+    isotope(Magnesium, 26, 25.982_592_968, 0.1101, 0.0),
+    isotope(Aluminium, 27, 26.981_538_53, 1.0, 2.5),
+    isotope(Silicon, 28, 27.976_926_534_65, 0.922_23, 0.0),
+    isotope(Silicon, 29, 28.976_494_664_90, 0.046_85, 0.5),
+    isotope(Silicon, 30, 29.973_770_136, 0.030_92, 0.0),
+    isotope(Phosphorus, 31, 30.973_761_998_42, 1.0, 0.5),
+    isotope(Sulfur, 32, 31.972_071_174_4, 0.9499, 0.0),
+    isotope(Sulfur, 33, 32.971_458_909_8, 0.0075, 1.5),
+    isotope(Sulfur, 34, 33.967_867_004, 0.0425, 0.0),
+    isotope(Sulfur, 36, 35.967_080_71, 0.0001, 0.0),
+    isotope(Chlorine, 35, 34.968_852_682, 0.7576, 1.5),
+    isotope(Chlorine, 37, 36.965_902_602, 0.2424, 1.5),
+    isotope(Argon, 36, 35.967_545_105, 0.003_336, 0.0),
+    isotope(Argon, 38, 37.962_732_11, 0.000_629, 0.0),
+    isotope(Argon, 40, 39.962_383_123_7, 0.996_035, 0.0),
+    isotope(Potassium, 39, 38.963_706_486_4, 0.932_581, 1.5),
+    isotope(Potassium, 40, 39.963_998_166, 0.000_117, 4.0),
+    isotope(Potassium, 41, 40.961_825_257_9, 0.067_302, 1.5),
+    isotope(Calcium, 40, 39.962_590_863, 0.969_41, 0.0),
+    isotope(Calcium, 42, 41.958_617_83, 0.006_47, 0.0),
+    isotope(Calcium, 43, 42.958_766_44, 0.001_35, 3.5),
+    isotope(Calcium, 44, 43.955_481_56, 0.020_86, 0.0),
+    isotope(Calcium, 46, 45.953_689_0, 0.000_04, 0.0),
+    isotope(Calcium, 48, 47.952_522_76, 0.001_87, 0.0),
+    isotope(Iron, 54, 53.939_608_99, 0.058_45, 0.0),
+    isotope(Iron, 56, 55.934_936_33, 0.917_54, 0.0),
+    isotope(Iron, 57, 56.935_392_84, 0.021_19, 0.5),
+    isotope(Iron, 58, 57.933_274_43, 0.002_82, 0.0),
+    isotope(Copper, 63, 62.929_597_72, 0.6915, 1.5),
+    isotope(Copper, 65, 64.927_789_70, 0.3085, 1.5),
+    isotope(Zinc, 64, 63.929_142_01, 0.4917, 0.0),
+    isotope(Zinc, 66, 65.926_033_81, 0.2773, 0.0),
+    isotope(Zinc, 67, 66.927_127_75, 0.0404, 2.5),
+    isotope(Zinc, 68, 67.924_844_55, 0.1845, 0.0),
+    isotope(Zinc, 70, 69.925_319_2, 0.0061, 0.0),
+    isotope(Germanium, 70, 69.924_248_75, 0.2057, 0.0),
+    isotope(Germanium, 72, 71.922_075_826, 0.2745, 0.0),
+    isotope(Germanium, 73, 72.923_458_956, 0.0775, 4.5),
+    isotope(Germanium, 74, 73.921_177_761, 0.3650, 0.0),
+    isotope(Germanium, 76, 75.921_402_726, 0.0773, 0.0),
+    isotope(Bromine, 79, 78.918_337_6, 0.5069, 1.5),
+    isotope(Bromine, 81, 80.916_289_7, 0.4931, 1.5),
+    isotope(Silver, 107, 106.905_091_6, 0.518_39, 0.5),
+    isotope(Silver, 109, 108.904_755_3, 0.481_61, 0.5),
+    isotope(Iodine, 127, 126.904_471_9, 1.0, 2.5),
+    isotope(Gold, 197, 196.966_568_79, 1.0, 1.5),
+    isotope(Uranium, 234, 234.040_952_3, 0.000_054, 0.0),
+    isotope(Uranium, 235, 235.043_930_1, 0.007_204, 3.5),
+    isotope(Uranium, 238, 238.050_788_4, 0.992_742, 0.0),
+    isotope(Plutonium, 239, 239.052_163_4, 0.0, 0.5),
+];
+
+impl Element {
+    /// The isotopes of the element in the isotope table, in order of mass
+    /// number.  Empty for elements the table doesn't cover.
+    pub fn isotopes(self) -> &'static [Isotope] {
+        let start = ISOTOPES.partition_point(|isotope| isotope.element < self);
+        let end = ISOTOPES.partition_point(|isotope| isotope.element <= self);
+        &ISOTOPES[start..end]
+    }
+
+    pub fn isotope(self, mass_number: u16) -> Option<&'static Isotope> {
+        self.isotopes()
+            .iter()
+            .find(|isotope| isotope.mass_number == mass_number)
+    }
+
+    pub fn most_abundant_isotope(self) -> Option<&'static Isotope> {
+        self.isotopes()
+            .iter()
+            .filter(|isotope| isotope.abundance > 0.0)
+            .max_by(|a, b| a.abundance.total_cmp(&b.abundance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table() {
+        for pair in ISOTOPES.windows(2) {
+            assert!(
+                (pair[0].element, pair[0].mass_number) < (pair[1].element, pair[1].mass_number),
+                "{:?}",
+                pair[1]
+            );
+        }
+
+        for element in Element::all() {
+            let isotopes = element.isotopes();
+            for isotope in isotopes {
+                assert_eq!(isotope.element, element);
+                assert!(isotope.mass_number >= element.atomic_number() as u16);
+                assert!((isotope.mass - isotope.mass_number as f64).abs() < 0.1);
+                assert_eq!(isotope.spin % 0.5, 0.0);
+            }
+
+            // The abundances of a naturally occurring element add up to one,
+            // and its standard atomic weight is their weighted mean
+            let total: f64 = isotopes.iter().map(|isotope| isotope.abundance).sum();
+            if total > 0.0 {
+                assert!((total - 1.0).abs() < 1e-3, "{:?}", element);
+                let mean: f64 = isotopes
+                    .iter()
+                    .map(|isotope| isotope.mass * isotope.abundance)
+                    .sum();
+                let weight = element.atomic_mass() as f64;
+                assert!((mean - weight).abs() < 0.01 * weight, "{:?}", element);
+            }
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let deuterium = Element::Hydrogen.isotope(2).unwrap();
+        assert_eq!(deuterium.spin, 1.0);
+        assert!((deuterium.mass - 2.0141).abs() < 1e-4);
+        assert_eq!(Element::Hydrogen.isotopes().len(), 3);
+        assert_eq!(Element::Hydrogen.isotope(4), None);
+        assert_eq!(
+            Element::Carbon.most_abundant_isotope().unwrap().mass_number,
+            12
+        );
+        assert_eq!(
+            Element::Chlorine
+                .most_abundant_isotope()
+                .unwrap()
+                .mass_number,
+            35
+        );
+        assert_eq!(Element::Plutonium.most_abundant_isotope(), None);
+        assert!(Element::Oganesson.isotopes().is_empty());
+    }
+}
+
+// End of File
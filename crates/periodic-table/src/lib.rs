@@ -2,9 +2,28 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this file,
 // You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use bevy::prelude::*;
-use static_assertions::const_assert_eq;
+//! Reference data for the chemical elements, and how to draw them.
+//!
+//! The crate has no dependencies by default.  The `toml` feature adds loading
+//! color schemes from TOML files, and the `bevy` feature adds conversions to
+//! Bevy's types.
+
+#[cfg(feature = "bevy")]
+mod bevy_support;
+pub mod colors;
+mod data;
+pub mod isotopes;
+#[cfg(feature = "toml")]
+mod scheme_file;
+
+pub use colors::ColorScheme;
+use data::*;
+pub use isotopes::Isotope;
+#[cfg(feature = "toml")]
+pub use scheme_file::ColorSchemeError;
+use std::fmt;
 use std::mem;
+use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u8)] // Oganesson == 118
@@ -128,7 +147,26 @@ pub enum Element {
     Tennessine,
     Oganesson,
 }
-const_assert_eq!(Element::Oganesson as usize, 118);
+const _: () = assert!(Element::Oganesson as usize == 118);
+
+/// The block of the periodic table an element belongs to, named after the
+/// orbital its highest-energy electrons occupy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Block {
+    S,
+    P,
+    D,
+    F,
+}
+
+// The atomic number of the first element in each period.
+const PERIOD_STARTS: [u8; 7] = [1, 3, 11, 19, 37, 55, 87];
+
+// Returns a value from one of the data tables, or `None` if it is unknown.
+fn known(table: &[f32; 118], element: Element) -> Option<f32> {
+    let value = table[element as usize - 1];
+    (value != 0.0).then_some(value)
+}
 
 impl Element {
     pub const MIN: Self = Element::Hydrogen; // 1
@@ -136,66 +174,363 @@ impl Element {
 
     pub fn from_atomic_number(n: u8) -> Option<Self> {
         if Self::MIN as u8 <= n && n <= Self::MAX as u8 {
-            Some(unsafe { mem::transmute(n) })
+            Some(unsafe { mem::transmute::<u8, Element>(n) })
         } else {
             None
         }
     }
+
+    /// Every element, in order of atomic number.
+    pub fn all() -> impl Iterator<Item = Self> {
+        (Self::MIN as u8..=Self::MAX as u8).filter_map(Self::from_atomic_number)
+    }
+
+    /// Looks up an element by its chemical symbol, ignoring case.
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        let index = SYMBOLS
+            .iter()
+            .position(|candidate| candidate.eq_ignore_ascii_case(symbol))?;
+        Self::from_atomic_number(index as u8 + 1)
+    }
+
+    /// Looks up an element by its English name, ignoring case.  The
+    /// alternative spellings "aluminum", "caesium" and "sulphur" are also
+    /// accepted.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let index = NAMES
+            .iter()
+            .position(|candidate| candidate.eq_ignore_ascii_case(name));
+        match index {
+            Some(index) => Self::from_atomic_number(index as u8 + 1),
+            None => ALTERNATIVE_NAMES
+                .iter()
+                .find(|(candidate, _)| candidate.eq_ignore_ascii_case(name))
+                .map(|&(_, element)| element),
+        }
+    }
+
+    pub fn atomic_number(self) -> u8 {
+        self as u8
+    }
+
+    /// The element's chemical symbol, e.g. "C" or "Cl".
+    pub fn symbol(self) -> &'static str {
+        SYMBOLS[self as usize - 1]
+    }
+
+    /// The element's English name, e.g. "Carbon".
+    pub fn name(self) -> &'static str {
+        NAMES[self as usize - 1]
+    }
+
+    /// The standard atomic weight, in daltons.  For elements without stable
+    /// isotopes this is the mass number of the longest-lived isotope.
+    pub fn atomic_mass(self) -> f32 {
+        ATOMIC_MASSES[self as usize - 1]
+    }
+
+    /// The element's single-bond covalent radius, in angstroms.
+    pub fn covalent_radius(self) -> f32 {
+        COVALENT_RADII[self as usize - 1]
+    }
+
+    /// The element's double-bond covalent radius, in angstroms, if known.
+    pub fn double_bond_radius(self) -> Option<f32> {
+        known(&DOUBLE_BOND_RADII, self)
+    }
+
+    /// The element's triple-bond covalent radius, in angstroms, if known.
+    pub fn triple_bond_radius(self) -> Option<f32> {
+        known(&TRIPLE_BOND_RADII, self)
+    }
+
+    /// The element's van der Waals radius, in angstroms, if known.
+    pub fn van_der_waals_radius(self) -> Option<f32> {
+        known(&VAN_DER_WAALS_RADII, self)
+    }
+
+    /// The element's Pauling electronegativity, if known.
+    pub fn electronegativity(self) -> Option<f32> {
+        known(&ELECTRONEGATIVITIES, self)
+    }
+
+    /// The numbers of bonds the element typically forms, most common first.
+    /// For metals these are its common oxidation states.
+    pub fn valences(self) -> &'static [u8] {
+        VALENCES[self as usize - 1]
+    }
+
+    /// The period (row) of the periodic table, from 1 to 7.
+    pub fn period(self) -> u8 {
+        PERIOD_STARTS
+            .iter()
+            .rposition(|&start| start <= self as u8)
+            .unwrap() as u8
+            + 1
+    }
+
+    // The position of the element within its period, counting from 0.
+    fn period_offset(self) -> u8 {
+        self as u8 - PERIOD_STARTS[self.period() as usize - 1]
+    }
+
+    /// The group (column) of the periodic table, from 1 to 18, or `None` for
+    /// the lanthanides and actinides of the f-block.  Group 3 holds lutetium
+    /// and lawrencium, as IUPAC recommends.
+    pub fn group(self) -> Option<u8> {
+        let offset = self.period_offset();
+        match self.period() {
+            1 => Some(if offset == 0 { 1 } else { 18 }),
+            // Periods 2 and 3 have no d-block
+            2 | 3 => Some(if offset < 2 { offset + 1 } else { offset + 11 }),
+            4 | 5 => Some(offset + 1),
+            // ... and periods 6 and 7 have 14 f-block elements after group 2
+            _ => match offset {
+                0..=1 => Some(offset + 1),
+                2..=15 => None,
+                _ => Some(offset - 13),
+            },
+        }
+    }
+
+    pub fn block(self) -> Block {
+        match self.group() {
+            None => Block::F,
+            // Helium is placed in group 18, but only has s electrons
+            Some(18) if self == Element::Helium => Block::S,
+            Some(1..=2) => Block::S,
+            Some(3..=12) => Block::D,
+            Some(_) => Block::P,
+        }
+    }
+}
+
+// Spellings of element names other than those in `NAMES`.
+const ALTERNATIVE_NAMES: [(&str, Element); 3] = [
+    ("Aluminum", Element::Aluminium),
+    ("Caesium", Element::Cesium),
+    ("Sulphur", Element::Sulfur),
+];
+
+/// Formats the element as its chemical symbol.
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.symbol())
+    }
+}
+
+/// The error returned when a string is neither the symbol nor the name of an
+/// element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseElementError {
+    Empty,
+    Unknown(String),
+}
+
+impl fmt::Display for ParseElementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseElementError::Empty => write!(f, "missing element"),
+            ParseElementError::Unknown(s) => {
+                write!(f, "unknown element {:?}: not a chemical symbol or name", s)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseElementError {}
+
+/// Parses a chemical symbol such as "Cl" or an element name such as
+/// "chlorine", ignoring case and surrounding whitespace.
+impl FromStr for Element {
+    type Err = ParseElementError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseElementError::Empty);
+        }
+        Self::from_symbol(s)
+            .or_else(|| Self::from_name(s))
+            .ok_or_else(|| ParseElementError::Unknown(s.to_owned()))
+    }
 }
 
 pub struct PeriodicTable {
     pub element_reprs: Vec<ElementRepr>,
 }
 
+impl Default for PeriodicTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The radius at which atoms of elements without a known van der Waals radius
+// (mostly transition metals) are drawn.
+const DEFAULT_RADIUS: f32 = 2.0;
+
 impl PeriodicTable {
+    /// Elements are drawn at their van der Waals radius, in the colors of the
+    /// atomCAD color scheme.
     pub fn new() -> Self {
-        let mut element_reprs = vec![
-            ElementRepr {
-                color: Vec3::new(0.0, 0.0, 0.0), // Black
-                radius: 1.0,
-            };
-            118
-        ];
-        element_reprs[Element::Hydrogen as usize - 1] = ElementRepr {
-            color: Vec3::new(0.8510, 0.8510, 0.8510), // white
-            radius: 1.2,
-        };
-        element_reprs[Element::Carbon as usize - 1] = ElementRepr {
-            color: Vec3::new(0.30196, 0.2902, 0.3098), // dark grey
-            radius: 1.7,
-        };
-        element_reprs[Element::Oxygen as usize - 1] = ElementRepr {
-            color: Vec3::new(0.7490, 0.2118, 0.3176), // red
-            radius: 1.52,
-        };
-        element_reprs[Element::Silicon as usize - 1] = ElementRepr {
-            // color: Vec3::new(0.7294, 0.5804, 0.1686), // yellow
-            color: Vec3::new(0.5234, 0.5234, 0.5234), // light grey
-            radius: 2.1,
-        };
-        element_reprs[Element::Phosphorus as usize - 1] = ElementRepr {
-            color: Vec3::new(0.7019, 0.4314, 0.1451), // orange
-            radius: 1.8,
-        };
-        element_reprs[Element::Nitrogen as usize - 1] = ElementRepr {
-            color: Vec3::new(0.2078, 0.4549, 0.6118), // blue
-            radius: 1.55,
-        };
-        element_reprs[Element::Sulfur as usize - 1] = ElementRepr {
-            color: Vec3::new(0.7294, 0.5804, 0.1686), // yellow
-            radius: 1.8,
-        };
+        Self::with_color_scheme(&ColorScheme::atomcad())
+    }
 
+    pub fn with_color_scheme(scheme: &ColorScheme) -> Self {
+        let element_reprs = Element::all()
+            .map(|element| ElementRepr {
+                color: scheme.color(element),
+                radius: element.van_der_waals_radius().unwrap_or(DEFAULT_RADIUS),
+            })
+            .collect();
         Self { element_reprs }
     }
+
+    pub fn set_color_scheme(&mut self, scheme: &ColorScheme) {
+        for (element, repr) in Element::all().zip(self.element_reprs.iter_mut()) {
+            repr.color = scheme.color(element);
+        }
+    }
+
+    pub fn element_repr(&self, element: Element) -> &ElementRepr {
+        &self.element_reprs[element as usize - 1]
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+// The layout is fixed so that the table can be uploaded to the GPU as is.
+#[derive(Debug, Copy, Clone, PartialEq)]
 #[repr(C)]
 pub struct ElementRepr {
-    color: Vec3, // RGB color space
-    radius: f32, // in angstroms
+    pub color: [f32; 3], // sRGB color space
+    pub radius: f32,     // in angstroms
+}
+const _: () = assert!(mem::size_of::<ElementRepr>() == 16);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let cases = [
+            (Element::Hydrogen, 1, Some(1), Block::S),
+            (Element::Helium, 1, Some(18), Block::S),
+            (Element::Boron, 2, Some(13), Block::P),
+            (Element::Chlorine, 3, Some(17), Block::P),
+            (Element::Iron, 4, Some(8), Block::D),
+            (Element::Lanthanum, 6, None, Block::F),
+            (Element::Ytterbium, 6, None, Block::F),
+            (Element::Lutetium, 6, Some(3), Block::D),
+            (Element::Gold, 6, Some(11), Block::D),
+            (Element::Radium, 7, Some(2), Block::S),
+            (Element::Nobelium, 7, None, Block::F),
+            (Element::Oganesson, 7, Some(18), Block::P),
+        ];
+        for (element, period, group, block) in cases {
+            assert_eq!(element.period(), period, "{:?}", element);
+            assert_eq!(element.group(), group, "{:?}", element);
+            assert_eq!(element.block(), block, "{:?}", element);
+        }
+
+        // Each period has two s-block elements, and the f-block has 14
+        // elements in each of periods 6 and 7
+        let count = |block| Element::all().filter(|e| e.block() == block).count();
+        assert_eq!(count(Block::S), 14);
+        assert_eq!(count(Block::F), 28);
+        assert_eq!(count(Block::D), 40);
+        assert_eq!(count(Block::P), 36);
+    }
+
+    #[test]
+    fn parse() {
+        for element in Element::all() {
+            assert_eq!(element.to_string().parse(), Ok(element));
+            assert_eq!(element.symbol().to_uppercase().parse(), Ok(element));
+            assert_eq!(element.symbol().to_lowercase().parse(), Ok(element));
+            assert_eq!(element.name().parse(), Ok(element));
+            assert_eq!(element.name().to_uppercase().parse(), Ok(element));
+            assert_eq!(element.name().to_lowercase().parse(), Ok(element));
+        }
+
+        assert_eq!(" Cl\n".parse(), Ok(Element::Chlorine));
+        assert_eq!("carbon".parse(), Ok(Element::Carbon));
+        assert_eq!("Sulphur".parse(), Ok(Element::Sulfur));
+        assert_eq!("aluminum".parse(), Ok(Element::Aluminium));
+        assert_eq!(format!("{:>3}", Element::Carbon), "  C");
+
+        assert_eq!("".parse::<Element>(), Err(ParseElementError::Empty));
+        assert_eq!("  ".parse::<Element>(), Err(ParseElementError::Empty));
+        let err = "Xx".parse::<Element>().unwrap_err();
+        assert_eq!(err, ParseElementError::Unknown("Xx".to_owned()));
+        assert_eq!(
+            err.to_string(),
+            "unknown element \"Xx\": not a chemical symbol or name"
+        );
+        assert!("C1".parse::<Element>().is_err());
+    }
+
+    #[test]
+    fn data() {
+        assert_eq!(Element::all().count(), 118);
+        let mut mass = 0.0;
+        for element in Element::all() {
+            assert_eq!(
+                Element::from_atomic_number(element.atomic_number()),
+                Some(element)
+            );
+            assert_eq!(element.name(), format!("{:?}", element));
+            assert!(element.atomic_mass() > 0.0);
+            // Masses only go down between a few neighbouring pairs, such as
+            // tellurium and iodine
+            assert!(element.atomic_mass() > mass - 2.0, "{:?}", element);
+            mass = element.atomic_mass();
+
+            let single = element.covalent_radius();
+            for radius in [element.double_bond_radius(), element.triple_bond_radius()]
+                .into_iter()
+                .flatten()
+            {
+                assert!(
+                    radius > 0.5 * single && radius < 1.5 * single,
+                    "{:?}",
+                    element
+                );
+            }
+            if let Some(vdw) = element.van_der_waals_radius() {
+                assert!(vdw > single, "{:?}", element);
+            }
+        }
+
+        assert_eq!(Element::Carbon.name(), "Carbon");
+        assert_eq!(Element::Carbon.valences(), &[4]);
+        // Bonds between the common elements get shorter as their order
+        // increases
+        for element in [Element::Carbon, Element::Nitrogen, Element::Oxygen] {
+            let double = element.double_bond_radius().unwrap();
+            let triple = element.triple_bond_radius().unwrap();
+            assert!(element.covalent_radius() > double && double > triple);
+        }
+        assert_eq!(Element::Carbon.van_der_waals_radius(), Some(1.7));
+        assert_eq!(Element::Fluorine.electronegativity(), Some(3.98));
+        assert_eq!(Element::Neon.electronegativity(), None);
+        assert!((Element::Oxygen.atomic_mass() - 15.999).abs() < 1e-3);
+
+        let table = PeriodicTable::new();
+        assert_eq!(table.element_reprs.len(), 118);
+        assert_eq!(table.element_repr(Element::Oxygen).radius, 1.52);
+        assert_eq!(
+            table.element_repr(Element::Chlorine).color,
+            [31.0 / 255.0, 240.0 / 255.0, 31.0 / 255.0]
+        );
+        let mut table = PeriodicTable::with_color_scheme(&ColorScheme::cpk());
+        assert_eq!(table.element_repr(Element::Carbon).color, [34.0 / 255.0; 3]);
+        table.set_color_scheme(&ColorScheme::jmol());
+        assert_eq!(
+            table.element_repr(Element::Carbon).color,
+            [144.0 / 255.0; 3]
+        );
+    }
 }
-const_assert_eq!(mem::size_of::<ElementRepr>(), 16);
 
 // End of File
@@ -0,0 +1,134 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Color scheme files, which are written in TOML:
+//!
+//! ```toml
+//! name = "Dark carbon"
+//! # The scheme to take the colors of unlisted elements from (default: Jmol)
+//! base = "CPK"
+//!
+//! [colors]
+//! C = "#202020"
+//! oxygen = "#ff4040"
+//! ```
+
+use crate::colors::{rgb, ColorScheme};
+use crate::ParseElementError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+impl ColorScheme {
+    /// Reads a scheme from a TOML file, like the one described in the module
+    /// documentation.  Colors are written as "#RRGGBB", and elements by their
+    /// symbol or name.
+    pub fn from_toml(source: &str) -> Result<Self, ColorSchemeError> {
+        #[derive(Deserialize)]
+        struct SchemeFile {
+            name: String,
+            base: Option<String>,
+            #[serde(default)]
+            colors: BTreeMap<String, String>,
+        }
+
+        let file: SchemeFile = toml::from_str(source).map_err(ColorSchemeError::Toml)?;
+        let base = file.base.as_deref().unwrap_or("Jmol");
+        let mut scheme = Self::builtin()
+            .into_iter()
+            .find(|scheme| scheme.name.eq_ignore_ascii_case(base))
+            .ok_or_else(|| ColorSchemeError::UnknownBase(base.to_owned()))?;
+        scheme.name = file.name;
+
+        for (element, color) in file.colors {
+            let element = element.parse().map_err(ColorSchemeError::Element)?;
+            let hex = color
+                .strip_prefix('#')
+                .filter(|hex| hex.len() == 6)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or(ColorSchemeError::Color(color))?;
+            scheme.set_color(element, rgb(hex));
+        }
+        Ok(scheme)
+    }
+}
+
+/// An error in a color scheme file.
+#[derive(Debug)]
+pub enum ColorSchemeError {
+    Toml(toml::de::Error),
+    Element(ParseElementError),
+    /// A color which isn't written as "#RRGGBB".
+    Color(String),
+    /// The scheme is based on a scheme which doesn't exist.
+    UnknownBase(String),
+}
+
+impl fmt::Display for ColorSchemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorSchemeError::Toml(err) => write!(f, "{}", err),
+            ColorSchemeError::Element(err) => write!(f, "{}", err),
+            ColorSchemeError::Color(color) => {
+                write!(f, "invalid color {:?}, expected \"#RRGGBB\"", color)
+            }
+            ColorSchemeError::UnknownBase(name) => write!(f, "unknown base scheme {:?}", name),
+        }
+    }
+}
+
+impl std::error::Error for ColorSchemeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ColorSchemeError::Toml(err) => Some(err),
+            ColorSchemeError::Element(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Element;
+
+    #[test]
+    fn from_toml() {
+        let scheme = ColorScheme::from_toml(
+            r##"
+            name = "Custom"
+            base = "cpk"
+
+            [colors]
+            C = "#404040"
+            oxygen = "#FF0000"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(scheme.name, "Custom");
+        assert_eq!(scheme.color(Element::Carbon), [64.0 / 255.0; 3]);
+        assert_eq!(scheme.color(Element::Oxygen), [1.0, 0.0, 0.0]);
+        assert_eq!(scheme.color(Element::Nitrogen), rgb(0x2233FF));
+
+        let scheme = ColorScheme::from_toml("name = \"Plain\"").unwrap();
+        assert_eq!(scheme.colors, ColorScheme::jmol().colors);
+
+        let err = |source| ColorScheme::from_toml(source).unwrap_err();
+        assert!(matches!(err("colors = 3"), ColorSchemeError::Toml(_)));
+        assert!(matches!(
+            err("name = \"A\"\nbase = \"Pastel\""),
+            ColorSchemeError::UnknownBase(_)
+        ));
+        assert!(matches!(
+            err("name = \"A\"\n[colors]\nXx = \"#000000\""),
+            ColorSchemeError::Element(_)
+        ));
+        assert!(matches!(
+            err("name = \"A\"\n[colors]\nC = \"red\""),
+            ColorSchemeError::Color(_)
+        ));
+    }
+}
+
+// End of File
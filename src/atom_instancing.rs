@@ -0,0 +1,277 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Draws all of the atoms of a molecule with a single instanced draw call.
+// Spawning an entity per atom stops scaling at a few thousand atoms, so
+// instead the molecule entity carries one `AtomInstance` per particle, which
+// is uploaded to the GPU each frame and drawn as an instance of the sphere
+// mesh on the molecule entity.  This follows Bevy's `shader_instancing`
+// example.
+
+use bevy::{
+    core_pipeline::core_3d::Opaque3d,
+    ecs::{
+        query::QueryItem,
+        system::{lifetimeless::*, SystemParamItem},
+    },
+    pbr::{MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        mesh::{GpuBufferInfo, MeshVertexBufferLayout},
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, PhaseItem, RenderCommand, RenderCommandResult,
+            RenderPhase, SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::*,
+        renderer::RenderDevice,
+        view::ExtractedView,
+        RenderApp, RenderSet,
+    },
+};
+use bytemuck::{Pod, Zeroable};
+use periodic_table::ElementRepr;
+
+const ATOM_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x6a71_c0de_a70b_5e11);
+
+/// One particle of a molecule as it is laid out in the instance buffer: its
+/// position in the molecule, and its color and radius in the same layout as
+/// the periodic table's element data.
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct AtomInstance {
+    pub position: [f32; 3],
+    // Pads the position out to a `vec4` in the shader
+    pub padding: f32,
+    pub repr: ElementRepr,
+}
+
+impl AtomInstance {
+    pub fn new(position: Vec3, repr: ElementRepr) -> Self {
+        Self {
+            position: position.to_array(),
+            padding: 0.0,
+            repr,
+        }
+    }
+}
+
+/// The particles of a molecule, which are drawn as instances of the entity's
+/// mesh (a sphere of unit radius).  Molecule entities also need a
+/// `NoFrustumCulling`, as the bounds of the mesh say nothing about where the
+/// instances are.
+#[derive(Component, Clone, Default, Deref, DerefMut)]
+pub struct AtomInstances(pub Vec<AtomInstance>);
+
+impl ExtractComponent for AtomInstances {
+    type Query = &'static AtomInstances;
+    type Filter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::Query>) -> Option<Self> {
+        Some(item.clone())
+    }
+}
+
+pub struct AtomInstancingPlugin;
+
+impl Plugin for AtomInstancingPlugin {
+    fn build(&self, app: &mut App) {
+        bevy::asset::load_internal_asset!(
+            app,
+            ATOM_SHADER_HANDLE,
+            "atom_instancing.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugin(ExtractComponentPlugin::<AtomInstances>::default());
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Opaque3d, DrawAtoms>()
+            .init_resource::<AtomPipeline>()
+            .init_resource::<SpecializedMeshPipelines<AtomPipeline>>()
+            .add_system(queue_atoms.in_set(RenderSet::Queue))
+            .add_system(prepare_atom_buffers.in_set(RenderSet::Prepare));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_atoms(
+    opaque_3d_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    atom_pipeline: Res<AtomPipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<AtomPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    molecules: Query<(Entity, &MeshUniform, &Handle<Mesh>), With<AtomInstances>>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<Opaque3d>)>,
+) {
+    let draw_atoms = opaque_3d_draw_functions.read().id::<DrawAtoms>();
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+
+    for (view, mut opaque_phase) in &mut views {
+        let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+        let rangefinder = view.rangefinder3d();
+        for (entity, mesh_uniform, mesh_handle) in &molecules {
+            let Some(mesh) = meshes.get(mesh_handle) else {
+                continue;
+            };
+            let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+            let pipeline =
+                match pipelines.specialize(&pipeline_cache, &atom_pipeline, key, &mesh.layout) {
+                    Ok(pipeline) => pipeline,
+                    Err(err) => {
+                        error!("Could not specialize the atom pipeline: {}", err);
+                        continue;
+                    }
+                };
+            opaque_phase.add(Opaque3d {
+                entity,
+                pipeline,
+                draw_function: draw_atoms,
+                distance: rangefinder.distance(&mesh_uniform.transform),
+            });
+        }
+    }
+}
+
+/// The instances of an entity's mesh, uploaded to the GPU for
+/// `DrawMeshInstanced`.  The bonds of a molecule are drawn the same way (see
+/// `bond_instancing.rs`).
+#[derive(Component)]
+pub struct InstanceBuffer {
+    buffer: Buffer,
+    length: usize,
+}
+
+impl InstanceBuffer {
+    pub fn new<T: Pod>(render_device: &RenderDevice, label: &str, instances: &[T]) -> Self {
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(instances),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+        Self {
+            buffer,
+            length: instances.len(),
+        }
+    }
+}
+
+fn prepare_atom_buffers(
+    mut commands: Commands,
+    query: Query<(Entity, &AtomInstances)>,
+    render_device: Res<RenderDevice>,
+) {
+    for (entity, instances) in &query {
+        commands.entity(entity).insert(InstanceBuffer::new(
+            &render_device,
+            "atom instance buffer",
+            instances.as_slice(),
+        ));
+    }
+}
+
+#[derive(Resource)]
+pub struct AtomPipeline {
+    mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for AtomPipeline {
+    fn from_world(world: &mut World) -> Self {
+        AtomPipeline {
+            mesh_pipeline: world.resource::<MeshPipeline>().clone(),
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for AtomPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+        descriptor.vertex.shader = ATOM_SHADER_HANDLE.typed();
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: std::mem::size_of::<AtomInstance>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![
+                // Shader locations 0-2 are taken by the mesh's position,
+                // normal and UV attributes
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 3,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: VertexFormat::Float32x4.size(),
+                    shader_location: 4,
+                },
+            ],
+        });
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader = ATOM_SHADER_HANDLE.typed();
+        }
+        Ok(descriptor)
+    }
+}
+
+type DrawAtoms = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawMeshInstanced,
+);
+
+pub struct DrawMeshInstanced;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
+    type Param = SRes<RenderAssets<Mesh>>;
+    type ViewWorldQuery = ();
+    type ItemWorldQuery = (Read<Handle<Mesh>>, Read<InstanceBuffer>);
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        (mesh_handle, instance_buffer): (&'w Handle<Mesh>, &'w InstanceBuffer),
+        meshes: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(gpu_mesh) = meshes.into_inner().get(mesh_handle) else {
+            return RenderCommandResult::Failure;
+        };
+        // An empty molecule has nothing to draw, and an empty buffer cannot
+        // be bound
+        if instance_buffer.length == 0 {
+            return RenderCommandResult::Success;
+        }
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+
+        let instances = 0..instance_buffer.length as u32;
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, instances);
+            }
+            GpuBufferInfo::NonIndexed { vertex_count } => {
+                pass.draw(0..*vertex_count, instances);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
+
+// End of File
@@ -0,0 +1,65 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Draws every atom of a molecule as an instance of a unit sphere.  See
+// `atom_instancing.rs` for the layout of the instance buffer.
+
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_types
+
+@group(1) @binding(0)
+var<uniform> mesh: Mesh;
+
+// Bindings must come before the functions that use them
+#import bevy_pbr::mesh_functions
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+
+    // The position of the atom in its molecule, in xyz
+    @location(3) i_position: vec4<f32>,
+    // The atom's `ElementRepr`: its sRGB color in rgb, and its radius in w
+    @location(4) i_repr: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) color: vec3<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let position = vertex.position * vertex.i_repr.w + vertex.i_position.xyz;
+    let world_position = mesh_position_local_to_world(mesh.model, vec4<f32>(position, 1.0));
+
+    var out: VertexOutput;
+    out.clip_position = mesh_position_world_to_clip(world_position);
+    out.world_position = world_position.xyz;
+    out.world_normal = mesh_normal_local_to_world(vertex.normal);
+    out.color = vertex.i_repr.rgb;
+    return out;
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The atoms are lit from the camera, so that they are shaded as spheres
+    // from whichever direction they are seen.
+    let normal = normalize(in.world_normal);
+    let to_camera = normalize(view.world_position - in.world_position);
+    let diffuse = max(dot(normal, to_camera), 0.0);
+    let color = srgb_to_linear(in.color) * (0.3 + 0.7 * diffuse);
+    return vec4<f32>(color, 1.0);
+}
+
+// End of File
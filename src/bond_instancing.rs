@@ -0,0 +1,201 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Draws all of the bonds of a molecule with a single instanced draw call, in
+// the same way as `atom_instancing.rs` draws its atoms.  The instances are
+// carried by a child entity of the molecule, whose mesh is a cylinder of unit
+// radius and height; each instance stretches the cylinder from one end of a
+// half strand of a bond to the other.
+
+use crate::atom_instancing::{DrawMeshInstanced, InstanceBuffer};
+use bevy::{
+    core_pipeline::core_3d::Opaque3d,
+    ecs::query::QueryItem,
+    pbr::{MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        mesh::MeshVertexBufferLayout,
+        render_asset::RenderAssets,
+        render_phase::{AddRenderCommand, DrawFunctions, RenderPhase, SetItemPipeline},
+        render_resource::*,
+        renderer::RenderDevice,
+        view::ExtractedView,
+        RenderApp, RenderSet,
+    },
+};
+use bytemuck::{Pod, Zeroable};
+use periodic_table::ElementRepr;
+
+const BOND_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x6a71_c0de_b0d5_5e11);
+
+/// One half of one strand of a bond as it is laid out in the instance buffer:
+/// the centers of the ends of its cylinder, and its color and radius in the
+/// same layout as the periodic table's element data.
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct BondInstance {
+    pub start: [f32; 3],
+    // Pad the ends out to `vec4`s in the shader
+    pub start_padding: f32,
+    pub end: [f32; 3],
+    pub end_padding: f32,
+    pub repr: ElementRepr,
+}
+
+impl BondInstance {
+    pub fn new(start: Vec3, end: Vec3, repr: ElementRepr) -> Self {
+        Self {
+            start: start.to_array(),
+            start_padding: 0.0,
+            end: end.to_array(),
+            end_padding: 0.0,
+            repr,
+        }
+    }
+}
+
+/// The bond strands of a molecule, which are drawn as instances of the
+/// entity's mesh (a cylinder of unit radius and height along the y axis).
+/// Like molecule entities, the entity also needs a `NoFrustumCulling`.
+#[derive(Component, Clone, Default, Deref, DerefMut)]
+pub struct BondInstances(pub Vec<BondInstance>);
+
+impl ExtractComponent for BondInstances {
+    type Query = &'static BondInstances;
+    type Filter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::Query>) -> Option<Self> {
+        Some(item.clone())
+    }
+}
+
+pub struct BondInstancingPlugin;
+
+impl Plugin for BondInstancingPlugin {
+    fn build(&self, app: &mut App) {
+        bevy::asset::load_internal_asset!(
+            app,
+            BOND_SHADER_HANDLE,
+            "bond_instancing.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugin(ExtractComponentPlugin::<BondInstances>::default());
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Opaque3d, DrawBonds>()
+            .init_resource::<BondPipeline>()
+            .init_resource::<SpecializedMeshPipelines<BondPipeline>>()
+            .add_system(queue_bonds.in_set(RenderSet::Queue))
+            .add_system(prepare_bond_buffers.in_set(RenderSet::Prepare));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_bonds(
+    opaque_3d_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    bond_pipeline: Res<BondPipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<BondPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    bonds: Query<(Entity, &MeshUniform, &Handle<Mesh>), With<BondInstances>>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<Opaque3d>)>,
+) {
+    let draw_bonds = opaque_3d_draw_functions.read().id::<DrawBonds>();
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+
+    for (view, mut opaque_phase) in &mut views {
+        let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+        let rangefinder = view.rangefinder3d();
+        for (entity, mesh_uniform, mesh_handle) in &bonds {
+            let Some(mesh) = meshes.get(mesh_handle) else {
+                continue;
+            };
+            let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+            let pipeline =
+                match pipelines.specialize(&pipeline_cache, &bond_pipeline, key, &mesh.layout) {
+                    Ok(pipeline) => pipeline,
+                    Err(err) => {
+                        error!("Could not specialize the bond pipeline: {}", err);
+                        continue;
+                    }
+                };
+            opaque_phase.add(Opaque3d {
+                entity,
+                pipeline,
+                draw_function: draw_bonds,
+                distance: rangefinder.distance(&mesh_uniform.transform),
+            });
+        }
+    }
+}
+
+fn prepare_bond_buffers(
+    mut commands: Commands,
+    query: Query<(Entity, &BondInstances)>,
+    render_device: Res<RenderDevice>,
+) {
+    for (entity, instances) in &query {
+        commands.entity(entity).insert(InstanceBuffer::new(
+            &render_device,
+            "bond instance buffer",
+            instances.as_slice(),
+        ));
+    }
+}
+
+#[derive(Resource)]
+pub struct BondPipeline {
+    mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for BondPipeline {
+    fn from_world(world: &mut World) -> Self {
+        BondPipeline {
+            mesh_pipeline: world.resource::<MeshPipeline>().clone(),
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for BondPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+        descriptor.vertex.shader = BOND_SHADER_HANDLE.typed();
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: std::mem::size_of::<BondInstance>() as u64,
+            step_mode: VertexStepMode::Instance,
+            // Shader locations 0-2 are taken by the mesh's position, normal
+            // and UV attributes; the start, end and repr follow
+            attributes: (0..3)
+                .map(|index| VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: index * VertexFormat::Float32x4.size(),
+                    shader_location: 3 + index as u32,
+                })
+                .collect(),
+        });
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader = BOND_SHADER_HANDLE.typed();
+        }
+        Ok(descriptor)
+    }
+}
+
+type DrawBonds = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawMeshInstanced,
+);
+
+// End of File
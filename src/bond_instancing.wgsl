@@ -0,0 +1,83 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Draws every bond strand of a molecule as an instance of a cylinder of unit
+// radius and height along the y axis.  See `bond_instancing.rs` for the
+// layout of the instance buffer.
+
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_types
+
+@group(1) @binding(0)
+var<uniform> mesh: Mesh;
+
+// Bindings must come before the functions that use them
+#import bevy_pbr::mesh_functions
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+
+    // The centers of the ends of the strand in its molecule, in xyz
+    @location(3) i_start: vec4<f32>,
+    @location(4) i_end: vec4<f32>,
+    // The `ElementRepr` of the atom at this end of the bond: its sRGB color
+    // in rgb, and the radius of the strand in w
+    @location(5) i_repr: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) color: vec3<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    // The cylinder's y axis runs along the strand.  It is round, so any pair
+    // of directions across the strand will do for x and z, as long as they
+    // keep the triangles wound the same way.
+    let axis = vertex.i_end.xyz - vertex.i_start.xyz;
+    let along = normalize(axis);
+    var other = vec3<f32>(1.0, 0.0, 0.0);
+    if abs(along.x) > 0.9 {
+        other = vec3<f32>(0.0, 1.0, 0.0);
+    }
+    let x = normalize(cross(along, other));
+    let z = cross(x, along);
+
+    let radius = vertex.i_repr.w;
+    let position = vertex.i_start.xyz
+        + axis * (vertex.position.y + 0.5)
+        + (x * vertex.position.x + z * vertex.position.z) * radius;
+    let normal = x * vertex.normal.x + along * vertex.normal.y + z * vertex.normal.z;
+    let world_position = mesh_position_local_to_world(mesh.model, vec4<f32>(position, 1.0));
+
+    var out: VertexOutput;
+    out.clip_position = mesh_position_world_to_clip(world_position);
+    out.world_position = world_position.xyz;
+    out.world_normal = mesh_normal_local_to_world(normal);
+    out.color = vertex.i_repr.rgb;
+    return out;
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Lit from the camera, like the atoms
+    let normal = normalize(in.world_normal);
+    let to_camera = normalize(view.world_position - in.world_position);
+    let diffuse = max(dot(normal, to_camera), 0.0);
+    let color = srgb_to_linear(in.color) * (0.3 + 0.7 * diffuse);
+    return vec4<f32>(color, 1.0);
+}
+
+// End of File
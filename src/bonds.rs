@@ -0,0 +1,110 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::bond_instancing::{BondInstance, BondInstances};
+use crate::molecule_builder::{highlight, EditHistory, Molecule, PbrCache, Representation};
+use bevy::prelude::*;
+use molecule::edit::Edit;
+use molecule::Particle;
+use periodic_table::ElementRepr;
+use petgraph::stable_graph::NodeIndex;
+use std::collections::HashSet;
+
+/// The selected bonds of a molecule, with the lower-numbered atom of each
+/// first.  Like `SelectedAtoms`, this is cleared whenever atoms may have been
+/// removed or replaced.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct SelectedBonds(pub HashSet<(NodeIndex, NodeIndex)>);
+
+/// Copies the bonds between the atoms of every molecule into the instance
+/// buffer of the molecule's bond entity, whenever the molecule, the way it is
+/// drawn or its selected bonds change.  Each strand of a bond is drawn in two
+/// halves, colored like the atom at either end.  Bonds to bonding sites are
+/// not drawn, and neither are any bonds in representations without them.
+pub fn update_bond_instances(
+    q_molecule: Query<(
+        Ref<Molecule>,
+        Ref<Representation>,
+        Ref<SelectedBonds>,
+        &Children,
+    )>,
+    mut q_bonds: Query<&mut BondInstances>,
+    pbr_cache: Res<PbrCache>,
+) {
+    for (molecule, representation, selected, children) in q_molecule.iter() {
+        if !molecule.is_changed()
+            && !representation.is_changed()
+            && !selected.is_changed()
+            && !pbr_cache.is_changed()
+        {
+            continue;
+        }
+        let Some(&bonds) = children.iter().find(|&&child| q_bonds.contains(child)) else {
+            continue;
+        };
+        let Ok(mut instances) = q_bonds.get_mut(bonds) else {
+            continue;
+        };
+        instances.clear();
+        let Some(style) = representation.bond_style() else {
+            continue;
+        };
+
+        let graph = &molecule.graph;
+        for edge in graph.edge_indices() {
+            let Some((a, b)) = graph.edge_endpoints(edge) else {
+                continue;
+            };
+            let (Particle::Atom(atom_a), Particle::Atom(atom_b)) =
+                (&graph[a].particle, &graph[b].particle)
+            else {
+                continue;
+            };
+            let highlighted = selected.contains(&(a.min(b), a.max(b)));
+            let colors = [atom_a, atom_b].map(|atom| {
+                let color = pbr_cache.element_color(atom.element);
+                if highlighted {
+                    highlight(color)
+                } else {
+                    color
+                }
+            });
+            for (start, end, radius) in style.strand_ends(&molecule, a, b) {
+                let middle = start.lerp(end, 0.5);
+                for (color, (from, to)) in colors.into_iter().zip([(start, middle), (middle, end)])
+                {
+                    instances.push(BondInstance::new(from, to, ElementRepr { color, radius }));
+                }
+            }
+        }
+    }
+}
+
+/// Cycles the order of a bond from single to double to triple and back.  If
+/// an atom has no bonding site left for a higher order bond, the bond goes
+/// back to being single.
+pub fn cycle_bond_order(
+    history: &mut EditHistory,
+    entity: Entity,
+    molecule: &mut molecule::Molecule,
+    (a, b): (NodeIndex, NodeIndex),
+) {
+    let Some(edge) = molecule.graph.find_edge(a, b) else {
+        return;
+    };
+    let order = molecule.graph[edge];
+    let raised = Edit::SetBondOrder {
+        a,
+        b,
+        order: order % 3 + 1,
+    };
+    if history.perform(entity, molecule, raised).is_err() && order > 1 {
+        let single = Edit::SetBondOrder { a, b, order: 1 };
+        if let Err(err) = history.perform(entity, molecule, single) {
+            error!("Could not change the bond order: {}", err);
+        }
+    }
+}
+
+// End of File
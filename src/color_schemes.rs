@@ -0,0 +1,91 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use bevy::{prelude::*, winit::WinitWindows};
+use bevy_egui::{egui, EguiContexts};
+use periodic_table::ColorScheme;
+
+/// The color schemes which atoms can be drawn in: the built-in schemes, then
+/// any loaded by the user.
+#[derive(Resource)]
+pub struct ColorSchemes {
+    pub schemes: Vec<ColorScheme>,
+    pub current: usize,
+}
+
+impl Default for ColorSchemes {
+    fn default() -> Self {
+        ColorSchemes {
+            schemes: ColorScheme::builtin(),
+            current: 0,
+        }
+    }
+}
+
+impl ColorSchemes {
+    pub fn current(&self) -> &ColorScheme {
+        &self.schemes[self.current]
+    }
+}
+
+// Asks for a color scheme file and reads it, returning `None` if the dialog
+// was cancelled or the file couldn't be read.
+#[cfg(not(target_family = "wasm"))]
+fn load_dialog() -> Option<ColorScheme> {
+    let path = rfd::FileDialog::new()
+        .add_filter("Color scheme", &["toml"])
+        .pick_file()?;
+    let scheme = std::fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|source| ColorScheme::from_toml(&source).map_err(|err| err.to_string()));
+    match scheme {
+        Ok(scheme) => Some(scheme),
+        Err(err) => {
+            error!("Could not load {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// A window for choosing the color scheme, or loading a new one from a TOML
+/// file.
+pub fn color_scheme_picker(
+    mut contexts: EguiContexts,
+    mut schemes: ResMut<ColorSchemes>,
+    // Unused, but forces this system to run on the main thread, from which
+    // native file dialogs have to be shown on some platforms.
+    _windows: NonSend<WinitWindows>,
+) {
+    let mut selected = schemes.current;
+    egui::Window::new("Appearance")
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Color scheme")
+                    .selected_text(&schemes.current().name)
+                    .show_ui(ui, |ui| {
+                        for (index, scheme) in schemes.schemes.iter().enumerate() {
+                            ui.selectable_value(&mut selected, index, &scheme.name);
+                        }
+                    });
+
+                // There is no filesystem to load schemes from on the web.
+                #[cfg(not(target_family = "wasm"))]
+                if ui.button("Load...").clicked() {
+                    if let Some(scheme) = load_dialog() {
+                        schemes.schemes.push(scheme);
+                        selected = schemes.schemes.len() - 1;
+                    }
+                }
+            });
+        });
+
+    // Only touch the resource when the selection changes, so that atoms are
+    // only re-skinned when they need to be
+    if selected != schemes.current {
+        schemes.current = selected;
+    }
+}
+
+// End of File
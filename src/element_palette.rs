@@ -0,0 +1,82 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use periodic_table::Element;
+
+/// The element of the atoms placed by the molecule builder.
+#[derive(Resource)]
+pub struct CurrentElement(pub Element);
+
+impl Default for CurrentElement {
+    fn default() -> Self {
+        CurrentElement(Element::Carbon)
+    }
+}
+
+// The periodic table is laid out in 18 columns, with the lanthanides and
+// actinides split off into two rows below the main table (after a blank
+// spacer row).
+const COLUMNS: usize = 18;
+const ROWS: usize = 10;
+
+// Returns the (row, column) of an element in the palette.
+fn palette_position(element: Element) -> (usize, usize) {
+    let row = element.period() as usize - 1;
+    match element.group() {
+        Some(group) => (row, group as usize - 1),
+        // The f-block is moved below the table, lined up under groups 3-16.
+        None => {
+            let first = if row == 5 {
+                Element::Lanthanum
+            } else {
+                Element::Actinium
+            };
+            (row + 3, (element as usize - first as usize) + 2)
+        }
+    }
+}
+
+pub fn element_palette(mut contexts: EguiContexts, mut current: ResMut<CurrentElement>) {
+    let mut cells = [[None; COLUMNS]; ROWS];
+    for element in Element::all() {
+        let (row, column) = palette_position(element);
+        cells[row][column] = Some(element);
+    }
+
+    egui::Window::new("Elements")
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("periodic_table")
+                .spacing([2.0, 2.0])
+                .show(ui, |ui| {
+                    for row in cells.iter() {
+                        for cell in row.iter() {
+                            match cell {
+                                Some(element) => {
+                                    let selected = current.0 == *element;
+                                    let label =
+                                        egui::SelectableLabel::new(selected, element.symbol());
+                                    if ui
+                                        .add_sized([24.0, 24.0], label)
+                                        .on_hover_text(element.name())
+                                        .clicked()
+                                        && !selected
+                                    {
+                                        current.0 = *element;
+                                    }
+                                }
+                                None => {
+                                    ui.label("");
+                                }
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+// End of File
@@ -2,12 +2,20 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this file,
 // You can obtain one at http://mozilla.org/MPL/2.0/.
 
+pub mod atom_instancing;
+pub mod bond_instancing;
+pub mod bonds;
 pub mod camera;
+pub mod color_schemes;
+pub mod element_palette;
 pub mod menubar;
 pub mod molecule_builder;
 pub mod platform;
 pub mod platform_impl;
-pub mod vsepr;
+pub mod representation_picker;
+pub mod simulation;
+pub mod unit_cell;
+pub mod workspace;
 
 pub const APP_NAME: &str = "atomCAD";
 
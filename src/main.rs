@@ -10,16 +10,27 @@ use bevy::{
 use bevy_egui::{egui, EguiContexts, EguiPlugin};
 use bevy_infinite_grid::{InfiniteGrid, InfiniteGridBundle, InfiniteGridPlugin};
 use bevy_mod_picking::prelude::*;
-use bevy_prototype_debug_lines::*;
 
+use atomcad::atom_instancing::AtomInstancingPlugin;
+use atomcad::bond_instancing::BondInstancingPlugin;
+use atomcad::bonds::update_bond_instances;
 use atomcad::camera::{pan_orbit_camera, PanOrbitCamera};
-use atomcad::menubar::winit_menu_bar;
-use atomcad::molecule_builder::{init_molecule, relax, track_particles};
+use atomcad::color_schemes::{color_scheme_picker, ColorSchemes};
+use atomcad::element_palette::{element_palette, CurrentElement};
+use atomcad::menubar::{winit_menu_bar, AppActionEvent};
+use atomcad::molecule_builder::{
+    apply_color_scheme, handle_edit_actions, init_molecule, pick_molecules, play_trajectories,
+    relax, update_atom_instances, ActiveForceField, EditHistory,
+};
+use atomcad::representation_picker::representation_picker;
+use atomcad::simulation::{dynamics_stopped, run_dynamics, simulation_panel, MolecularDynamics};
+use atomcad::unit_cell::{draw_unit_cells, unit_cell_editor};
+use atomcad::workspace::{handle_file_actions, WorkspacePath};
 use atomcad::APP_NAME;
 
 fn main() {
-    App::new()
-        .insert_resource(WinitSettings::desktop_app())
+    let mut app = App::new();
+    app.insert_resource(WinitSettings::desktop_app())
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: APP_NAME.into(),
@@ -32,15 +43,47 @@ fn main() {
         .add_plugins(DefaultPickingPlugins)
         .add_plugin(EguiPlugin)
         .add_plugin(InfiniteGridPlugin)
-        .add_plugin(DebugLinesPlugin::with_depth_test(true))
+        .add_plugin(AtomInstancingPlugin)
+        .add_plugin(BondInstancingPlugin)
+        .add_event::<AppActionEvent>()
+        .init_resource::<CurrentElement>()
+        .init_resource::<ColorSchemes>()
+        .init_resource::<WorkspacePath>()
+        .init_resource::<EditHistory>()
+        .init_resource::<ActiveForceField>()
+        .init_resource::<MolecularDynamics>()
         .add_startup_system(winit_menu_bar)
         .add_startup_system(setup)
         .add_startup_system(init_molecule)
         .add_system(ui_hello_world)
+        .add_system(element_palette)
+        .add_system(color_scheme_picker)
+        .add_system(representation_picker)
+        .add_system(simulation_panel)
+        .add_system(unit_cell_editor)
+        .add_system(apply_color_scheme)
         .add_system(pan_orbit_camera)
-        .add_system(track_particles)
-        .add_system(relax)
-        .run();
+        .add_system(play_trajectories)
+        .add_system(pick_molecules)
+        .add_system(update_atom_instances)
+        .add_system(update_bond_instances)
+        .add_system(draw_unit_cells)
+        .add_system(relax.run_if(dynamics_stopped))
+        .add_system(run_dynamics)
+        .add_system(handle_file_actions)
+        .add_system(handle_edit_actions);
+
+    // On Linux the menubar is drawn by egui inside the main window, and has to
+    // be redrawn each frame.
+    #[cfg(target_os = "linux")]
+    app.add_system(atomcad::platform::menubar::show_menu_bar);
+
+    // On macOS atomCAD's own menu actions are queued by Cocoa, and forwarded
+    // to the ECS each frame.
+    #[cfg(target_os = "macos")]
+    app.add_system(atomcad::platform::menubar::forward_menu_actions);
+
+    app.run();
 }
 
 fn setup(mut commands: Commands) {
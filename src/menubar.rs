@@ -12,6 +12,7 @@ use bevy::{prelude::*, winit::WinitWindows};
 //
 // Menus can also be contextual (e.g. a popup right-click menu) or accessed
 // from the system tray.
+#[derive(Clone)]
 pub struct Menu {
     pub title: String,
     pub items: Vec<MenuItem>,
@@ -34,6 +35,7 @@ impl Menu {
 // A menu item is either an action (with an optional keyboard shortcut) or a
 // submenu.  The Separator is a visual divider between groups of related menu
 // items.
+#[derive(Clone)]
 pub enum MenuItem {
     Separator,
     Entry(String, MenuShortcut, MenuAction),
@@ -63,6 +65,12 @@ pub enum SystemShortcut {
     HideApp,
     HideOthers,
     QuitApp,
+    Open,
+    Save,
+    SaveAs,
+    Undo,
+    Redo,
+    Delete,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -95,10 +103,13 @@ impl std::ops::BitOr for ModifierKeys {
 // A menu action is a callback that is invoked when the menu item is selected.
 // There are also a number of important platform-specific actions that can be
 // invoked.
+#[derive(Clone)]
 pub enum MenuAction {
     System(SystemAction),
+    App(AppAction),
 }
 
+#[derive(Clone, Copy)]
 pub enum SystemAction {
     LaunchAboutWindow,
     LaunchPreferences,
@@ -109,59 +120,151 @@ pub enum SystemAction {
     Terminate,
 }
 
+// Actions implemented by atomCAD itself rather than the platform.  Selecting
+// one sends an `AppActionEvent`, which is handled by whichever systems
+// implement the action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppAction {
+    Open,
+    Save,
+    SaveAs,
+    Undo,
+    Redo,
+    Delete,
+    DeleteBonds,
+    RaiseBondOrder,
+    LowerBondOrder,
+}
+
+impl AppAction {
+    // Every action, for platforms which identify menu items by number.
+    pub const ALL: &'static [AppAction] = &[
+        AppAction::Open,
+        AppAction::Save,
+        AppAction::SaveAs,
+        AppAction::Undo,
+        AppAction::Redo,
+        AppAction::Delete,
+        AppAction::DeleteBonds,
+        AppAction::RaiseBondOrder,
+        AppAction::LowerBondOrder,
+    ];
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AppActionEvent(pub AppAction);
+
 pub fn winit_menu_bar(
+    // Platforms which draw the menubar themselves (Linux) keep it in a
+    // resource, which is inserted through `commands`.
+    mut commands: Commands,
     // We have to use `NonSend` here.  This forces this function to be called
     // from the winit thread (which is the main thread on macOS), after the
     // window has been created.  We don't actually use it on macOS, but this
     // does control when and from where we will be called.
     windows: NonSend<WinitWindows>,
 ) {
-    let menubar = Menu::new(APP_NAME).add(MenuItem::SubMenu(
-        Menu::new("")
-            .add(MenuItem::new(
-                &format!("About {}", APP_NAME),
-                MenuShortcut::None,
-                MenuAction::System(SystemAction::LaunchAboutWindow),
-            ))
-            .add(MenuItem::Separator)
-            .add(MenuItem::new(
-                "Settings...",
-                MenuShortcut::System(SystemShortcut::Preferences),
-                MenuAction::System(SystemAction::LaunchPreferences),
-            ))
-            .add(MenuItem::Separator)
-            .add(MenuItem::new(
-                "Services",
-                MenuShortcut::None,
-                MenuAction::System(SystemAction::ServicesMenu),
-            ))
-            .add(MenuItem::Separator)
-            .add(MenuItem::new(
-                &format!("Hide {}", APP_NAME),
-                MenuShortcut::System(SystemShortcut::HideApp),
-                MenuAction::System(SystemAction::HideApp),
-            ))
-            .add(MenuItem::new(
-                "Hide Others",
-                MenuShortcut::System(SystemShortcut::HideOthers),
-                MenuAction::System(SystemAction::HideOthers),
-            ))
-            .add(MenuItem::new(
-                "Show All",
-                MenuShortcut::None,
-                MenuAction::System(SystemAction::ShowAll),
-            ))
-            .add(MenuItem::Separator)
-            .add(MenuItem::new(
-                &format!("Quit {}", APP_NAME),
-                MenuShortcut::System(SystemShortcut::QuitApp),
-                MenuAction::System(SystemAction::Terminate),
-            )),
-    ));
+    let menubar = Menu::new(APP_NAME)
+        .add(MenuItem::SubMenu(
+            Menu::new("")
+                .add(MenuItem::new(
+                    &format!("About {}", APP_NAME),
+                    MenuShortcut::None,
+                    MenuAction::System(SystemAction::LaunchAboutWindow),
+                ))
+                .add(MenuItem::Separator)
+                .add(MenuItem::new(
+                    "Settings...",
+                    MenuShortcut::System(SystemShortcut::Preferences),
+                    MenuAction::System(SystemAction::LaunchPreferences),
+                ))
+                .add(MenuItem::Separator)
+                .add(MenuItem::new(
+                    "Services",
+                    MenuShortcut::None,
+                    MenuAction::System(SystemAction::ServicesMenu),
+                ))
+                .add(MenuItem::Separator)
+                .add(MenuItem::new(
+                    &format!("Hide {}", APP_NAME),
+                    MenuShortcut::System(SystemShortcut::HideApp),
+                    MenuAction::System(SystemAction::HideApp),
+                ))
+                .add(MenuItem::new(
+                    "Hide Others",
+                    MenuShortcut::System(SystemShortcut::HideOthers),
+                    MenuAction::System(SystemAction::HideOthers),
+                ))
+                .add(MenuItem::new(
+                    "Show All",
+                    MenuShortcut::None,
+                    MenuAction::System(SystemAction::ShowAll),
+                ))
+                .add(MenuItem::Separator)
+                .add(MenuItem::new(
+                    &format!("Quit {}", APP_NAME),
+                    MenuShortcut::System(SystemShortcut::QuitApp),
+                    MenuAction::System(SystemAction::Terminate),
+                )),
+        ))
+        .add(MenuItem::SubMenu(
+            Menu::new("File")
+                .add(MenuItem::new(
+                    "Open...",
+                    MenuShortcut::System(SystemShortcut::Open),
+                    MenuAction::App(AppAction::Open),
+                ))
+                .add(MenuItem::Separator)
+                .add(MenuItem::new(
+                    "Save",
+                    MenuShortcut::System(SystemShortcut::Save),
+                    MenuAction::App(AppAction::Save),
+                ))
+                .add(MenuItem::new(
+                    "Save As...",
+                    MenuShortcut::System(SystemShortcut::SaveAs),
+                    MenuAction::App(AppAction::SaveAs),
+                )),
+        ))
+        .add(MenuItem::SubMenu(
+            Menu::new("Edit")
+                .add(MenuItem::new(
+                    "Undo",
+                    MenuShortcut::System(SystemShortcut::Undo),
+                    MenuAction::App(AppAction::Undo),
+                ))
+                .add(MenuItem::new(
+                    "Redo",
+                    MenuShortcut::System(SystemShortcut::Redo),
+                    MenuAction::App(AppAction::Redo),
+                ))
+                .add(MenuItem::Separator)
+                .add(MenuItem::new(
+                    "Delete",
+                    MenuShortcut::System(SystemShortcut::Delete),
+                    MenuAction::App(AppAction::Delete),
+                ))
+                .add(MenuItem::new(
+                    "Delete Bonds",
+                    MenuShortcut::None,
+                    MenuAction::App(AppAction::DeleteBonds),
+                ))
+                .add(MenuItem::Separator)
+                .add(MenuItem::new(
+                    "Raise Bond Order",
+                    MenuShortcut::None,
+                    MenuAction::App(AppAction::RaiseBondOrder),
+                ))
+                .add(MenuItem::new(
+                    "Lower Bond Order",
+                    MenuShortcut::None,
+                    MenuAction::App(AppAction::LowerBondOrder),
+                )),
+        ));
 
     // Do the platform-dependent work of constructing the menubar and
     // attaching it to the application object or main window.
-    attach_menu(&(*windows), &menubar);
+    attach_menu(&(*windows), &mut commands, &menubar);
 }
 
 // End of File
@@ -2,374 +2,613 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this file,
 // You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use crate::vsepr::BOND_SHAPES;
-use bevy::prelude::*;
-use bevy_mod_picking::prelude::*;
-use bevy_prototype_debug_lines::DebugLines;
-use periodic_table::Element;
+use crate::atom_instancing::{AtomInstance, AtomInstances};
+use crate::bond_instancing::BondInstances;
+use crate::bonds::{cycle_bond_order, SelectedBonds};
+use crate::color_schemes::ColorSchemes;
+use crate::element_palette::CurrentElement;
+use crate::menubar::{AppAction, AppActionEvent};
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        view::NoFrustumCulling,
+    },
+    window::PrimaryWindow,
+};
+use bevy_egui::EguiContexts;
+use molecule::edit::{Edit, History};
+use molecule::forcefield::{uff::Uff, ForceField, Parameterized};
+use molecule::representation::MeshData;
+use molecule::trajectory::Trajectory;
+use molecule::{vsepr, Particle};
+use periodic_table::{Element, ElementRepr, PeriodicTable};
 use petgraph::stable_graph::NodeIndex;
-use petgraph::visit::IntoNeighbors;
-use std::collections::HashMap;
-
-type BondOrder = u8;
-
-/// Describes how different particles in a spawned molecule (including unbonded
-/// electrons and atoms) are connected using a stable undirected graph. The
-/// molecule must be spawned, because the graph's nodes are bevy `Entity`
-/// instances, and all of their data (element, position, etc) is stored in the
-/// entity's components. The edge weights represent integer bond order (1
-/// indicates a single bond and so on). If a node of the molecule graph is a
-/// `BondingSite` Entity, it must have exactly one bond, and that bond must be to
-/// an `Atom` Entity.
-type MolGraph = petgraph::stable_graph::StableUnGraph<MolNode, BondOrder>;
-
-#[derive(Debug)]
-struct MolNode {
-    pos: Vec3,
-    vel: Vec3,
-    particle: Particle,
-    id: Entity,
-}
-#[derive(Debug)]
-enum Particle {
-    Atom(Atom),
-    BondingSite,
-}
-
-// A particle entity whose position should track a node from the molecule graph.
-#[derive(Component)]
-pub struct TrackedParticle {
-    node_index: NodeIndex,
-}
-
-/// Stores a molecule graph as a component so that molecules can be stored in
-/// ECS. This effectively allows us to use the ECS as a molecule workspace.
-#[derive(Component)]
-pub struct Molecule {
-    graph: MolGraph,
+use std::collections::{HashMap, HashSet};
+
+/// Stores a molecule as a component so that molecules can be stored in ECS.
+/// This effectively allows us to use the ECS as a molecule workspace.  The
+/// molecule itself is plain data; `update_atom_instances` copies its particles
+/// into the molecule entity's `AtomInstances` to be drawn.
+#[derive(Component, Deref, DerefMut)]
+pub struct Molecule(pub molecule::Molecule);
+
+/// The undo history of every molecule in the workspace.  Each edit is recorded
+/// against the entity of the molecule it was made to.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct EditHistory(pub History<Entity>);
+
+/// The selected atoms of a molecule.  The graph reuses the indices of removed
+/// nodes, so the selection is cleared whenever atoms may have been removed or
+/// replaced (by deleting them, or by undo and redo).
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct SelectedAtoms(pub HashSet<NodeIndex>);
+
+/// How a molecule is drawn.  Every molecule entity has one, which starts out
+/// as ball and stick.
+#[derive(Component, Clone, Copy, Default, Deref, DerefMut)]
+pub struct Representation(pub molecule::representation::Representation);
+
+const BONDING_SITE_RADIUS: f32 = 0.3;
+const BONDING_SITE_COLOR: [f32; 3] = [0.8, 0.8, 0.8];
+// Selected atoms and bonds are drawn halfway between their own color and
+// this one
+const SELECTION_COLOR: [f32; 3] = [0.3, 0.7, 1.0];
+
+/// The color a selected atom or bond of the given color is drawn in.
+pub fn highlight(color: [f32; 3]) -> [f32; 3] {
+    let mut color = color;
+    for (channel, selection) in color.iter_mut().zip(SELECTION_COLOR) {
+        *channel = (*channel + selection) * 0.5;
+    }
+    color
 }
 
-/// The presence of this component means that an `Entity` models an atom in a
-/// molecule.
-#[derive(Debug)]
-pub struct Atom {
-    element: Element,
-    // The NodeIndex of the atom that this Atom points towards. If None,
-    // this atom's +z axis is aligned with the molecule's +z axis. If Some, the
-    // +z axis of this atom points from the atom's center to the center of the
-    // atom it is facing.
-    facing: Option<NodeIndex>,
+// Converts a mesh from the molecule crate into a mesh for the renderer.
+fn mesh_from_data(data: MeshData) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, data.positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, data.normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, data.uvs);
+    mesh.set_indices(Some(Indices::U32(data.indices)));
+    mesh
 }
 
-/// Stores PbrBundles that are often duplicated, namely for things like atoms
-/// and bonding sites. Note that cloning a PbrBundle only clones a `Handle` of the
-/// Mesh and Material, so it is very cheap to clone this struct's members when
-/// you need ownership.
+/// Stores the meshes shared by every molecule: the sphere that atoms and
+/// bonding sites are drawn as instances of, and the cylinder that bond strands
+/// are drawn as instances of.  Note that cloning a `Handle` is cheap, so these
+/// are handed out by value.
 /// TODO: this may be redundant - I think `Assets` serves a very similar purpose
 #[derive(Resource)]
 pub struct PbrCache {
-    bonding_site: PbrBundle,
-    atoms: HashMap<Element, PbrBundle>,
+    // A sphere of unit radius, which is scaled to the size of each particle
+    // in the instance buffer
+    sphere: Handle<Mesh>,
+    // A cylinder of unit radius and height, centered on the origin along the
+    // y axis, which is stretched along each strand in the instance buffer
+    bond: Handle<Mesh>,
+    // The colors and radii of the elements, kept in step with the current
+    // color scheme
+    periodic_table: PeriodicTable,
+}
+
+impl PbrCache {
+    // The color and radius a particle is drawn with in the given
+    // representation
+    fn particle_repr(&self, particle: &Particle, representation: Representation) -> ElementRepr {
+        match particle {
+            Particle::Atom(atom) => {
+                let repr = self.periodic_table.element_repr(atom.element);
+                ElementRepr {
+                    color: repr.color,
+                    radius: representation.atom_radius(repr.radius),
+                }
+            }
+            Particle::BondingSite => ElementRepr {
+                color: BONDING_SITE_COLOR,
+                radius: BONDING_SITE_RADIUS,
+            },
+        }
+    }
+
+    /// The color that atoms of an element, and their ends of bonds, are drawn
+    /// in.
+    pub fn element_color(&self, element: Element) -> [f32; 3] {
+        self.periodic_table.element_repr(element).color
+    }
 }
 
 pub fn init_molecule(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    color_schemes: Res<ColorSchemes>,
 ) {
-    let mut pbr_cache = PbrCache {
-        atoms: HashMap::new(),
-        bonding_site: PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::UVSphere {
-                radius: 0.3,
-                sectors: 14,
-                stacks: 14,
-            })),
-            material: materials.add(Color::rgb(0.8, 0.8, 0.8).into()),
-            transform: Transform::from_xyz(0.0, 0.0, 0.0),
-            ..default()
-        },
+    let periodic_table = PeriodicTable::with_color_scheme(color_schemes.current());
+    let pbr_cache = PbrCache {
+        sphere: meshes.add(mesh_from_data(MeshData::sphere(14, 14))),
+        bond: meshes.add(mesh_from_data(MeshData::cylinder(14))),
+        periodic_table,
     };
 
-    pbr_cache.atoms.insert(
+    // Build the test molecule
+    let mut molecule = molecule::Molecule::new();
+    molecule.add_atom(
         Element::Carbon,
-        PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::UVSphere {
-                radius: 0.5,
-                sectors: 14,
-                stacks: 14,
-            })),
-            material: materials.add(Color::rgb(0.2, 0.2, 0.2).into()),
-            transform: Transform::from_xyz(0.0, 0.0, 0.0),
-            ..default()
-        },
-    );
-
-    // Build the test molecule's graph
-    let mut molgraph = MolGraph::default();
-
-    // Create a molecule entity backed by the molecule graph - this
-    // will allow us to use the ECS as a molecule database and give us
-    // unique identifiers for each molecule
-    let mut molecule = commands.spawn((
-        // Molecule { graph: molgraph },
-        // A Visibility and ComputedVisibility are needed to make
-        // the children of the molecule (the atoms) render. A transform
-        // and global transform are needed for the child entities to
-        // position themselves. There might be a name for this bundle -
-        // in Godot, it would be something like `Spatial`
-        Visibility::default(),
-        ComputedVisibility::default(),
-        GlobalTransform::default(),
-        Transform::default(),
-    ));
-
-    let molecule_id = molecule.id();
-    spawn_atom(
-        molecule.commands(),
-        molecule_id,
-        &mut molgraph,
-        &pbr_cache,
-        false,
         Vec3::default(),
         Vec3::new(0.0, 0.0, 1.0),
         None,
+        &vsepr::bond_directions(Element::Carbon, &[]),
+        false,
     );
 
-    molecule.insert(Molecule { graph: molgraph });
+    spawn_molecule(&mut commands, &pbr_cache, molecule);
 
     // Give ownership of the pbr cache to the ECS
     commands.insert_resource(pbr_cache);
 }
-use std::time::{Duration, SystemTime};
 
-pub fn track_particles(
-    mut q_particle: Query<(&TrackedParticle, &mut Transform, &Parent)>,
-    q_molecule: Query<&Molecule>,
+/// Re-skins every atom and bond when the color scheme changes.  Both take
+/// their colors from the periodic table in the `PbrCache`, so only that needs
+/// to be updated.
+pub fn apply_color_scheme(color_schemes: Res<ColorSchemes>, mut pbr_cache: ResMut<PbrCache>) {
+    if !color_schemes.is_changed() {
+        return;
+    }
+    pbr_cache
+        .periodic_table
+        .set_color_scheme(color_schemes.current());
+}
+
+/// Creates a molecule entity backed by the molecule graph - this will allow
+/// us to use the ECS as a molecule database and give us unique identifiers for
+/// each molecule.  Its particles are drawn by instancing the sphere mesh, and
+/// its bonds by a child entity instancing the cylinder mesh.
+pub fn spawn_molecule(
+    commands: &mut Commands,
+    pbr_cache: &PbrCache,
+    molecule: molecule::Molecule,
+) -> Entity {
+    commands
+        .spawn((
+            Molecule(molecule),
+            Representation::default(),
+            SelectedAtoms::default(),
+            SelectedBonds::default(),
+            AtomInstances::default(),
+            Relaxation::default(),
+            pbr_cache.sphere.clone(),
+            // The mesh's bounds are those of a single particle at the origin
+            NoFrustumCulling,
+            // A Visibility and ComputedVisibility are needed to make the
+            // molecule and its children (the bonds) render. A transform and
+            // global transform are needed for the child entities to position
+            // themselves. There might be a name for this bundle - in Godot, it
+            // would be something like `Spatial`
+            Visibility::default(),
+            ComputedVisibility::default(),
+            GlobalTransform::default(),
+            Transform::default(),
+        ))
+        .with_children(|molecule| {
+            // The bonds, filled in by `update_bond_instances`
+            molecule.spawn((
+                BondInstances::default(),
+                pbr_cache.bond.clone(),
+                NoFrustumCulling,
+                SpatialBundle::default(),
+            ));
+        })
+        .id()
+}
+
+/// Copies the particles of every molecule into its instance buffer, each
+/// frame, so that they are drawn where the molecule graph has them.
+pub fn update_atom_instances(
+    mut q_molecule: Query<(
+        &Molecule,
+        &Representation,
+        &SelectedAtoms,
+        &mut AtomInstances,
+    )>,
+    pbr_cache: Res<PbrCache>,
 ) {
-    for (particle, mut transform, parent) in q_particle.iter_mut() {
-        let molecule = match q_molecule.get(parent.get()) {
-            Ok(molecule) => molecule,
-            Err(_) => {
-                println!("An entity ({:?}) whose direct child uses a TrackedParticle component was not assigned to a Molecule!", parent.get());
-                continue;
+    for (molecule, &representation, selected, mut instances) in q_molecule.iter_mut() {
+        instances.clear();
+        for index in molecule.graph.node_indices() {
+            let node = &molecule.graph[index];
+            let mut repr = pbr_cache.particle_repr(&node.particle, representation);
+            if selected.contains(&index) {
+                repr.color = highlight(repr.color);
             }
-        };
-
-        if let Some(mol_node) = molecule.graph.node_weight(particle.node_index) {
-            transform.translation = mol_node.pos;
-        } else {
-            println!("A TrackedParticle belonging to parent Entity {:?} had a nonexistent node index ({:?})", parent.get(), particle.node_index);
+            instances.push(AtomInstance::new(node.pos, repr));
         }
     }
 }
 
-pub fn relax(
-    mut commands: Commands,
-    mut q_molecule: Query<&mut Molecule>,
-    mut lines: ResMut<DebugLines>,
+// How far, in logical pixels, the cursor may move between pressing and
+// releasing the mouse button for it to count as a click rather than a drag of
+// the camera.
+const CLICK_TOLERANCE: f32 = 4.0;
+
+// What a click landed on in a molecule
+#[derive(Clone, Copy)]
+enum Picked {
+    Particle(NodeIndex),
+    Bond(NodeIndex, NodeIndex),
+}
+
+/// Picks the particle or bond under the cursor when the left mouse button is
+/// clicked.  Clicking a bonding site bonds an atom of the current element to
+/// it.  Clicking an atom or bond selects it instead of everything else, unless
+/// Shift or Control is held, in which case it is added to or removed from the
+/// selection.  A plain click on a bond also cycles its order.  Clicking empty
+/// space clears the selection.
+#[allow(clippy::too_many_arguments)]
+pub fn pick_molecules(
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut egui_contexts: EguiContexts,
+    input_mouse: Res<Input<MouseButton>>,
+    input_keys: Res<Input<KeyCode>>,
+    mut pressed_at: Local<Option<Vec2>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut q_molecule: Query<(
+        Entity,
+        &mut Molecule,
+        &Representation,
+        &GlobalTransform,
+        &mut SelectedAtoms,
+        &mut SelectedBonds,
+    )>,
+    mut history: ResMut<EditHistory>,
+    current_element: Res<CurrentElement>,
+    pbr_cache: Res<PbrCache>,
 ) {
-    for mut molecule in q_molecule.iter_mut() {
-        let mut graph = &mut molecule.graph;
-
-        let mut forces = HashMap::<NodeIndex, Vec3>::new();
-
-        for node_index in graph.node_indices() {
-            let node = graph.node_weight(node_index).unwrap();
-            if true {
-                let mut force = Vec3::ZERO;
-                // for neighbour_index in graph.neighbors(node_index) {
-                //     let neighbour = graph.node_weight(neighbour_index).unwrap();
-                //     if let Particle::Atom(_) = neighbour.particle {
-                //         let displacement = neighbour.pos - node.pos;
-                //         let force_str = displacement.length() - 1;
-                //         force += displacement.normalize() * force_str;
-                //     }
-                // }
-
-                for other_index in graph.node_indices() {
-                    if other_index == node_index {
-                        continue;
-                    }
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    if input_mouse.just_pressed(MouseButton::Left) {
+        // Clicks on egui windows are not meant for the molecules behind them
+        *pressed_at = if egui_contexts.ctx_mut().is_pointer_over_area() {
+            None
+        } else {
+            window.cursor_position()
+        };
+    }
+    if !input_mouse.just_released(MouseButton::Left) {
+        return;
+    }
+    let (Some(start), Some(cursor)) = (pressed_at.take(), window.cursor_position()) else {
+        return;
+    };
+    if start.distance(cursor) > CLICK_TOLERANCE {
+        return;
+    }
+    let Some(ray) = q_camera
+        .iter()
+        .find_map(|(camera, transform)| camera.viewport_to_world(transform, cursor))
+    else {
+        return;
+    };
 
-                    let other = graph.node_weight(other_index).unwrap();
-                    if true {
-                        let displacement = other.pos - node.pos;
-                        if graph.contains_edge(node_index, other_index) {
-                            let force_str = 2.0 * (displacement.length() - 1.0);
-                            force += displacement.normalize() * force_str;
-                        } else {
-                            let force_str = displacement.length_recip().powi(2);
-                            force += -displacement.normalize() * force_str;
-                        }
+    // The nearest particle or bond under the cursor in any molecule.
+    // Molecules are only ever moved and rotated, so distances can be compared
+    // between them.
+    let mut nearest: Option<(Entity, Picked, f32)> = None;
+    for (entity, molecule, &representation, transform, _, _) in q_molecule.iter() {
+        let to_molecule = transform.compute_matrix().inverse();
+        let origin = to_molecule.transform_point3(ray.origin);
+        let direction = to_molecule.transform_vector3(ray.direction).normalize();
+        let particle = molecule
+            .pick(origin, direction, |particle| {
+                pbr_cache.particle_repr(particle, representation).radius
+            })
+            .map(|(node, distance)| (Picked::Particle(node), distance));
+        let bond = representation
+            .bond_style()
+            .and_then(|style| molecule.pick_bond(origin, direction, &style))
+            .map(|((a, b), distance)| (Picked::Bond(a, b), distance));
+        for (picked, distance) in particle.into_iter().chain(bond) {
+            if nearest.map_or(true, |(_, _, nearest)| distance < nearest) {
+                nearest = Some((entity, picked, distance));
+            }
+        }
+    }
+
+    let multiselect = input_keys.any_pressed([
+        KeyCode::LShift,
+        KeyCode::RShift,
+        KeyCode::LControl,
+        KeyCode::RControl,
+    ]);
+    let clicked = nearest.and_then(|(entity, picked, _)| {
+        let (_, molecule, _, _, _, _) = q_molecule.get(entity).ok()?;
+        let particle = match picked {
+            Picked::Particle(node) => Some(molecule.graph[node].particle.clone()),
+            Picked::Bond(..) => None,
+        };
+        Some((entity, picked, particle))
+    });
+
+    match clicked {
+        Some((entity, Picked::Particle(site), Some(Particle::BondingSite))) => {
+            let Ok((_, mut molecule, _, _, _, _)) = q_molecule.get_mut(entity) else {
+                return;
+            };
+            let edit = Edit::AddAtom {
+                site,
+                element: current_element.0,
+            };
+            if let Err(err) = history.perform(entity, &mut molecule, edit) {
+                error!("Could not add an atom: {}", err);
+            }
+        }
+        Some((entity, Picked::Particle(atom), _)) if multiselect => {
+            if let Ok((_, _, _, _, mut selected, _)) = q_molecule.get_mut(entity) {
+                if !selected.remove(&atom) {
+                    selected.insert(atom);
+                }
+            }
+        }
+        Some((entity, Picked::Bond(a, b), _)) if multiselect => {
+            if let Ok((_, _, _, _, _, mut selected)) = q_molecule.get_mut(entity) {
+                if !selected.remove(&(a, b)) {
+                    selected.insert((a, b));
+                }
+            }
+        }
+        Some((entity, picked, _)) => {
+            for (other, _, _, _, mut atoms, mut bonds) in q_molecule.iter_mut() {
+                atoms.clear();
+                bonds.clear();
+                if other != entity {
+                    continue;
+                }
+                match picked {
+                    Picked::Particle(atom) => {
+                        atoms.insert(atom);
+                    }
+                    Picked::Bond(a, b) => {
+                        bonds.insert((a, b));
                     }
                 }
-
-                forces.insert(node_index, force);
+            }
+            if let (Picked::Bond(a, b), Ok((_, mut molecule, _, _, _, _))) =
+                (picked, q_molecule.get_mut(entity))
+            {
+                cycle_bond_order(&mut history, entity, &mut molecule, (a, b));
             }
         }
-
-        for (node_index, force) in forces {
-            graph.node_weight_mut(node_index).unwrap().vel += force * 0.1;
+        None if !multiselect => {
+            for (_, _, _, _, mut atoms, mut bonds) in q_molecule.iter_mut() {
+                atoms.clear();
+                bonds.clear();
+            }
         }
+        None => {}
+    }
+}
+
+/// The force field that molecules are relaxed in, which starts out as UFF.
+#[derive(Resource)]
+pub struct ActiveForceField(pub Box<dyn ForceField + Send + Sync>);
+
+impl Default for ActiveForceField {
+    fn default() -> Self {
+        Self(Box::<Uff>::default())
+    }
+}
 
-        for mut node in graph.node_weights_mut() {
-            node.pos += node.vel * 0.01;
-            node.vel *= 0.9;
+/// The force field parameterized for a molecule by `relax`, which is kept
+/// from frame to frame until the molecule's bonds or unit cell change.  Every
+/// molecule entity has one.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct Relaxation(pub Option<Parameterized>);
+
+pub fn relax(
+    mut q_molecule: Query<(&mut Molecule, &mut Relaxation, Option<&TrajectoryPlayback>)>,
+    force_field: Res<ActiveForceField>,
+) {
+    for (mut molecule, mut relaxation, playback) in q_molecule.iter_mut() {
+        // The parameters of another force field no longer apply
+        if force_field.is_changed() {
+            relaxation.0 = None;
+        }
+        // Molecules playing back a trajectory are positioned by the trajectory
+        if playback.is_none() {
+            molecule.relax_step(force_field.0.as_ref(), &mut relaxation.0);
         }
+    }
+}
 
-        for edge in graph.edge_indices() {
-            if let Some((a, b)) = graph.edge_endpoints(edge) {
-                lines.line(
-                    graph.node_weight(a).unwrap().pos,
-                    graph.node_weight(b).unwrap().pos,
-                    0.0,
-                );
-            }
+/// Plays a trajectory back on the molecule of the same entity, looping once
+/// the last frame has been shown.
+#[derive(Component)]
+pub struct TrajectoryPlayback {
+    pub trajectory: Trajectory,
+    pub frame: usize,
+    pub timer: Timer,
+}
+
+impl TrajectoryPlayback {
+    pub fn new(trajectory: Trajectory, frames_per_second: f32) -> Self {
+        Self {
+            trajectory,
+            frame: 0,
+            timer: Timer::from_seconds(frames_per_second.recip(), TimerMode::Repeating),
         }
     }
 }
 
-fn on_bonding_site_clicked(
-    In(click): In<ListenedEvent<Click>>,
-    mut commands: Commands,
-    mut q_clicked: Query<(&Parent, &Transform, &TrackedParticle)>,
-    mut q_molecule: Query<&mut Molecule>,
-    q_transform: Query<&Transform>,
-    pbr_cache: Res<PbrCache>,
-) -> Bubble {
-    if let Ok((parent, transform, clicked_bonding_site)) = q_clicked.get(click.target) {
-        // let pbr_cache = pbr_cache.into_inner();
-        // Retrieve the parent of the clicked particle - i.e. its molecule
-        let molecule: &mut Molecule = q_molecule.get_mut(parent.get()).unwrap().into_inner();
-
-        let clicked_index = clicked_bonding_site.node_index;
-
-        // Get the atom this bonding site was connected to before removing it
-        // (recall that we demand that all bonding sites have exactly one
-        // neighbor)
-        let bond_target = molecule.graph.neighbors(clicked_index).next().unwrap();
-        molecule.graph.remove_node(clicked_index);
-        commands.entity(click.target).despawn();
-
-        // The bonding sites are displayed quite close to the atom - because the
-        // atoms are larger, we extend this displacement and spawn the new atom further
-        // than the bonding site was located from its parent
-        let parent_tf = q_transform
-            .get(molecule.graph.node_weight(bond_target).unwrap().id)
-            .unwrap();
-        let displacement = transform.translation - parent_tf.translation;
-        let new_atom_pos = transform.translation + displacement.normalize() * 0.5;
-
-        // We want the +z axis of this new atom to point from its center towards the atom it's bonded
-        // to
-        let up = -displacement.normalize();
-
-        let atom_node = spawn_atom(
-            &mut commands,
-            parent.get(),
-            &mut molecule.graph,
-            &pbr_cache.into_inner(),
-            true,
-            new_atom_pos,
-            up,
-            Some(bond_target),
-        );
-
-        // Add a single bond between the old atom and this atom:
-        molecule.graph.add_edge(atom_node, bond_target, 1);
-        println!("{:?}", molecule.graph);
-
-        return Bubble::Burst;
+pub fn play_trajectories(
+    time: Res<Time>,
+    mut q_molecule: Query<(&mut Molecule, &mut TrajectoryPlayback)>,
+) {
+    for (mut molecule, mut playback) in q_molecule.iter_mut() {
+        if playback.trajectory.is_empty() || !playback.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        playback.frame = (playback.frame + 1) % playback.trajectory.len();
+        playback
+            .trajectory
+            .apply_frame(&mut molecule, playback.frame);
     }
+}
 
-    Bubble::Up
+// The selected atoms of a molecule, and the bonds which are either selected
+// themselves or are between two selected atoms.  Bonds are given with the
+// lower-numbered atom first.
+#[derive(Default)]
+struct Selection {
+    atoms: HashSet<NodeIndex>,
+    bonds: HashSet<(NodeIndex, NodeIndex)>,
 }
 
-// End of File
-fn spawn_atom(
-    commands: &mut Commands,
-    molecule: Entity,
-    molgraph: &mut MolGraph,
-    pbr_cache: &PbrCache,
-    skip_first_bonding_site: bool,
-    position: Vec3,
-    up: Vec3,
-    facing: Option<NodeIndex>,
-) -> NodeIndex {
-    // Create a quaternion that will rotate from the global +z vector to the
-    // up vector
-    let up_rotation = Quat::from_rotation_arc(Vec3::new(0.0, 0.0, 1.0), up);
-
-    // Create an initial carbon atom
-    let mut carbon_pbr = pbr_cache.atoms[&Element::Carbon].clone();
-    carbon_pbr.transform.translation = position;
-    let mut initial_carbon = commands.spawn((carbon_pbr));
-    let carbon_node = molgraph.add_node(MolNode {
-        pos: position,
-        vel: Vec3::ZERO,
-        particle: Particle::Atom(Atom {
-            element: Element::Carbon,
-            facing,
-        }),
-        id: initial_carbon.id(),
-    });
-    initial_carbon.insert(TrackedParticle {
-        node_index: carbon_node,
-    });
-    let initial_carbon = initial_carbon.id();
-
-    // Make the displayed particles gameobjects children of the molecule, allowing
-    // the molecule to be recovered when a particle is picked
-    commands.entity(molecule).add_child(initial_carbon);
-
-    let num = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_millis()
-        % (BOND_SHAPES.len() - 1) as u128
-        + 1;
-    // Create bonding sites
-    let mut angle_iter = BOND_SHAPES[num as usize].unwrap().into_iter();
-    if skip_first_bonding_site {
-        angle_iter.next();
+// The selection in each molecule, keyed by the molecule's entity.
+fn selection(
+    q_molecule: &Query<(
+        Entity,
+        &mut Molecule,
+        &mut SelectedAtoms,
+        &mut SelectedBonds,
+    )>,
+) -> HashMap<Entity, Selection> {
+    let mut selected = HashMap::<Entity, Selection>::new();
+    for (entity, _, atoms, bonds) in q_molecule.iter() {
+        if !atoms.is_empty() || !bonds.is_empty() {
+            selected.insert(
+                entity,
+                Selection {
+                    atoms: atoms.0.clone(),
+                    bonds: bonds.0.clone(),
+                },
+            );
+        }
     }
-
-    for angles in angle_iter {
-        let mut bonding_site_pbr = pbr_cache.bonding_site.clone();
-        let mut displacement = 1.0
-            * Vec3 {
-                x: angles.azimuthal.cos() * angles.polar.sin(),
-                y: angles.azimuthal.sin() * angles.polar.sin(),
-                z: angles.polar.cos(),
-            };
-        displacement = up_rotation * displacement;
-        bonding_site_pbr.transform.translation = position + displacement;
-
-        let bonding_site = commands
-            .spawn((
-                bonding_site_pbr,
-                RaycastPickTarget::default(),
-                OnPointer::<Click>::run_callback(on_bonding_site_clicked),
-            ))
-            .id();
-
-        // Store the graph indexes needed
-        let bonding_site_node = molgraph.add_node(MolNode {
-            pos: position + displacement,
-            vel: Vec3::ZERO,
-            particle: Particle::BondingSite,
-            id: bonding_site,
-        });
-        molgraph.add_edge(carbon_node, bonding_site_node, 1);
-
-        // Add a BondingSite component to the entity so that it can track this
-        commands.entity(bonding_site).insert(TrackedParticle {
-            node_index: bonding_site_node,
-        });
-
-        commands.entity(molecule).add_child(bonding_site);
+    for (&target, selection) in selected.iter_mut() {
+        let Ok((_, molecule, _, _)) = q_molecule.get(target) else {
+            continue;
+        };
+        for &a in selection.atoms.iter() {
+            for (b, _) in molecule.bonds(a) {
+                if a < b && selection.atoms.contains(&b) {
+                    selection.bonds.insert((a, b));
+                }
+            }
+        }
     }
+    selected
+}
 
-    carbon_node
+/// Handles the Edit menu: Undo, Redo, deleting the selected atoms and bonds,
+/// and deleting or changing the order of the selected bonds (including those
+/// between selected atoms).  The bonds of the changed molecules are redrawn
+/// by `update_bond_instances`.
+pub fn handle_edit_actions(
+    mut events: EventReader<AppActionEvent>,
+    mut history: ResMut<EditHistory>,
+    mut q_molecule: Query<(
+        Entity,
+        &mut Molecule,
+        &mut SelectedAtoms,
+        &mut SelectedBonds,
+    )>,
+) {
+    for AppActionEvent(action) in events.iter() {
+        match action {
+            AppAction::Undo => {
+                let Some(&target) = history.undo_target() else {
+                    continue;
+                };
+                match q_molecule.get_mut(target) {
+                    Ok((_, mut molecule, mut atoms, mut bonds)) => {
+                        history.undo(&mut molecule);
+                        atoms.clear();
+                        bonds.clear();
+                    }
+                    // The molecule no longer exists, so neither do the
+                    // molecules of any earlier edits
+                    Err(_) => history.clear(),
+                }
+            }
+            AppAction::Redo => {
+                let Some(&target) = history.redo_target() else {
+                    continue;
+                };
+                match q_molecule.get_mut(target) {
+                    Ok((_, mut molecule, mut atoms, mut bonds)) => {
+                        history.redo(&mut molecule);
+                        atoms.clear();
+                        bonds.clear();
+                    }
+                    Err(_) => history.clear(),
+                }
+            }
+            AppAction::Delete
+            | AppAction::DeleteBonds
+            | AppAction::RaiseBondOrder
+            | AppAction::LowerBondOrder => {
+                for (target, selection) in selection(&q_molecule) {
+                    let Ok((_, mut molecule, mut atoms, mut bonds)) = q_molecule.get_mut(target)
+                    else {
+                        continue;
+                    };
+                    let mut edits = Vec::new();
+                    for &(a, b) in selection.bonds.iter() {
+                        let Some(edge) = molecule.graph.find_edge(a, b) else {
+                            continue;
+                        };
+                        let order = molecule.graph[edge];
+                        match action {
+                            // Bonds to deleted atoms go with them
+                            AppAction::Delete
+                                if !selection.atoms.contains(&a)
+                                    && !selection.atoms.contains(&b) =>
+                            {
+                                edits.push(Edit::DeleteBond { a, b })
+                            }
+                            AppAction::DeleteBonds => edits.push(Edit::DeleteBond { a, b }),
+                            AppAction::RaiseBondOrder if order < 3 => {
+                                edits.push(Edit::SetBondOrder {
+                                    a,
+                                    b,
+                                    order: order + 1,
+                                })
+                            }
+                            AppAction::LowerBondOrder if order > 1 => {
+                                edits.push(Edit::SetBondOrder {
+                                    a,
+                                    b,
+                                    order: order - 1,
+                                })
+                            }
+                            _ => {}
+                        }
+                    }
+                    if *action == AppAction::Delete {
+                        edits.extend(
+                            selection
+                                .atoms
+                                .iter()
+                                .map(|&atom| Edit::DeleteAtom { atom }),
+                        );
+                        atoms.clear();
+                    }
+                    if matches!(action, AppAction::Delete | AppAction::DeleteBonds) {
+                        bonds.clear();
+                    }
+                    if edits.is_empty() {
+                        continue;
+                    }
+                    // Changing several atoms or bonds is a single step to undo
+                    if let Err(err) = history.perform(target, &mut molecule, Edit::Batch(edits)) {
+                        error!("Could not edit the selection: {}", err);
+                    }
+                }
+            }
+            // The File menu is handled by `handle_file_actions`
+            _ => {}
+        }
+    }
 }
+
+// End of File
@@ -12,6 +12,11 @@ pub mod windows;
 #[cfg(target_os = "windows")]
 pub use self::windows::*;
 
+#[cfg(target_os = "linux")]
+pub mod linux;
+#[cfg(target_os = "linux")]
+pub use self::linux::*;
+
 #[cfg(target_arch = "wasm32")]
 pub mod web;
 #[cfg(target_arch = "wasm32")]
@@ -0,0 +1,290 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::menubar::{
+    AppActionEvent, Menu, MenuAction, MenuItem, MenuShortcut, ModifierKeys, SystemAction,
+    SystemShortcut,
+};
+use crate::APP_NAME;
+
+use bevy::{app::AppExit, prelude::*, window::PrimaryWindow, winit::WinitWindows};
+use bevy_egui::{egui, EguiContexts};
+
+// There is no single native menubar API on Linux (GTK, Qt and the various
+// desktop environments all disagree), so the menubar is drawn by egui as a
+// panel along the top edge of the main window.  The menu description is kept
+// in this resource so that it can be redrawn every frame.
+#[derive(Resource)]
+pub struct WindowMenuBar {
+    menu: Menu,
+    show_about: bool,
+}
+
+// Some of the standard macOS application menu entries have no equivalent on
+// Linux desktops, and are left out of the in-window menubar.
+fn is_supported(action: &MenuAction) -> bool {
+    match action {
+        MenuAction::System(action) => match action {
+            SystemAction::LaunchAboutWindow => true,
+            SystemAction::LaunchPreferences => true,
+            SystemAction::ServicesMenu => false,
+            SystemAction::HideApp => true,
+            SystemAction::HideOthers => false,
+            SystemAction::ShowAll => false,
+            SystemAction::Terminate => true,
+        },
+        MenuAction::App(_) => true,
+    }
+}
+
+// The key combination bound to a shortcut on Linux, following the GNOME and
+// KDE conventions.  Returns `None` if the shortcut has no Linux equivalent.
+fn shortcut_keys(shortcut: MenuShortcut) -> Option<(ModifierKeys, KeyCode, &'static str)> {
+    match shortcut {
+        MenuShortcut::None => None,
+        MenuShortcut::System(shortcut) => match shortcut {
+            SystemShortcut::Preferences => Some((ModifierKeys::CONTROL, KeyCode::Comma, ",")),
+            SystemShortcut::HideApp => Some((ModifierKeys::CONTROL, KeyCode::H, "H")),
+            SystemShortcut::HideOthers => None,
+            SystemShortcut::QuitApp => Some((ModifierKeys::CONTROL, KeyCode::Q, "Q")),
+            SystemShortcut::Open => Some((ModifierKeys::CONTROL, KeyCode::O, "O")),
+            SystemShortcut::Save => Some((ModifierKeys::CONTROL, KeyCode::S, "S")),
+            SystemShortcut::SaveAs => {
+                Some((ModifierKeys::CONTROL | ModifierKeys::SHIFT, KeyCode::S, "S"))
+            }
+            SystemShortcut::Undo => Some((ModifierKeys::CONTROL, KeyCode::Z, "Z")),
+            SystemShortcut::Redo => {
+                Some((ModifierKeys::CONTROL | ModifierKeys::SHIFT, KeyCode::Z, "Z"))
+            }
+            SystemShortcut::Delete => Some((ModifierKeys::NONE, KeyCode::Delete, "Del")),
+        },
+    }
+}
+
+// The human-readable form of a shortcut, e.g. "Ctrl+Shift+Q".
+fn shortcut_text(shortcut: MenuShortcut) -> Option<String> {
+    let (modifiers, _, key) = shortcut_keys(shortcut)?;
+    let mut text = String::new();
+    if modifiers.contains(ModifierKeys::CONTROL) {
+        text.push_str("Ctrl+");
+    }
+    if modifiers.contains(ModifierKeys::OPTION) {
+        text.push_str("Alt+");
+    }
+    if modifiers.contains(ModifierKeys::SHIFT) {
+        text.push_str("Shift+");
+    }
+    if modifiers.contains(ModifierKeys::COMMAND) {
+        text.push_str("Super+");
+    }
+    text.push_str(key);
+    Some(text)
+}
+
+// The modifier keys currently held down.  On Linux the Option modifier is the
+// Alt key and the Command modifier is the Super (Windows) key.
+fn pressed_modifiers(keys: &Input<KeyCode>) -> ModifierKeys {
+    let mut modifiers = ModifierKeys::NONE;
+    if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        modifiers = modifiers | ModifierKeys::SHIFT;
+    }
+    if keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        modifiers = modifiers | ModifierKeys::CONTROL;
+    }
+    if keys.any_pressed([KeyCode::LAlt, KeyCode::RAlt]) {
+        modifiers = modifiers | ModifierKeys::OPTION;
+    }
+    if keys.any_pressed([KeyCode::LWin, KeyCode::RWin]) {
+        modifiers = modifiers | ModifierKeys::COMMAND;
+    }
+    modifiers
+}
+
+// The items of a menu which should be displayed on Linux.  Unsupported entries
+// are removed, along with any separators left dangling by their removal.
+fn visible_items(menu: &Menu) -> Vec<&MenuItem> {
+    let mut items: Vec<&MenuItem> = Vec::new();
+    for item in menu.items.iter() {
+        match item {
+            MenuItem::Separator => {
+                if let Some(MenuItem::Separator) | None = items.last() {
+                    continue;
+                }
+            }
+            MenuItem::Entry(_, _, action) => {
+                if !is_supported(action) {
+                    continue;
+                }
+            }
+            MenuItem::SubMenu(_) => {}
+        }
+        items.push(item);
+    }
+    if let Some(MenuItem::Separator) = items.last() {
+        items.pop();
+    }
+    items
+}
+
+// Walk the menu tree looking for an entry whose shortcut was just pressed.
+fn find_shortcut(
+    menu: &Menu,
+    keys: &Input<KeyCode>,
+    modifiers: ModifierKeys,
+) -> Option<MenuAction> {
+    for item in visible_items(menu) {
+        match item {
+            MenuItem::Separator => {}
+            MenuItem::Entry(_, shortcut, action) => {
+                if let Some((shortcut_modifiers, key, _)) = shortcut_keys(*shortcut) {
+                    if shortcut_modifiers == modifiers && keys.just_pressed(key) {
+                        return Some(action.clone());
+                    }
+                }
+            }
+            MenuItem::SubMenu(submenu) => {
+                if let Some(action) = find_shortcut(submenu, keys, modifiers) {
+                    return Some(action);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn build_menu(ui: &mut egui::Ui, menu: &Menu, selected: &mut Option<MenuAction>) {
+    for item in visible_items(menu) {
+        match item {
+            MenuItem::Separator => {
+                ui.separator();
+            }
+            MenuItem::Entry(title, shortcut, action) => {
+                let mut button = egui::Button::new(title);
+                if let Some(text) = shortcut_text(*shortcut) {
+                    button = button.shortcut_text(text);
+                }
+                if ui.add(button).clicked() {
+                    *selected = Some(action.clone());
+                    ui.close_menu();
+                }
+            }
+            MenuItem::SubMenu(submenu) => {
+                ui.menu_button(&submenu.title, |ui| build_menu(ui, submenu, selected));
+            }
+        }
+    }
+}
+
+fn perform_action(
+    action: MenuAction,
+    menubar: &mut WindowMenuBar,
+    window: Option<&mut Window>,
+    exit: &mut EventWriter<AppExit>,
+    app_actions: &mut EventWriter<AppActionEvent>,
+) {
+    match action {
+        MenuAction::System(action) => match action {
+            SystemAction::LaunchAboutWindow => menubar.show_about = true,
+            // There is no preferences panel yet.  On macOS the equivalent
+            // menu item is likewise inert until one is implemented.
+            SystemAction::LaunchPreferences => {}
+            SystemAction::HideApp => {
+                if let Some(window) = window {
+                    window.set_minimized(true);
+                }
+            }
+            SystemAction::Terminate => exit.send(AppExit),
+            SystemAction::ServicesMenu | SystemAction::HideOthers | SystemAction::ShowAll => {}
+        },
+        MenuAction::App(action) => app_actions.send(AppActionEvent(action)),
+    }
+}
+
+pub fn show_menu_bar(
+    mut contexts: EguiContexts,
+    menubar: Option<ResMut<WindowMenuBar>>,
+    keys: Res<Input<KeyCode>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut exit: EventWriter<AppExit>,
+    mut app_actions: EventWriter<AppActionEvent>,
+) {
+    // The menubar is only available once `attach_menu` has been called.
+    let Some(mut menubar) = menubar else {
+        return;
+    };
+    let menubar = &mut *menubar;
+    let ctx = contexts.ctx_mut();
+
+    // Keyboard shortcuts are ignored while egui is using the keyboard, e.g.
+    // when a text field has focus.
+    let mut selected = None;
+    if !ctx.wants_keyboard_input() {
+        selected = find_shortcut(&menubar.menu, &keys, pressed_modifiers(&keys));
+    }
+
+    egui::TopBottomPanel::top("menubar").show(ctx, |ui| {
+        egui::menu::bar(ui, |ui| {
+            for item in visible_items(&menubar.menu) {
+                match item {
+                    MenuItem::Separator => {
+                        ui.separator();
+                    }
+                    MenuItem::Entry(title, _, action) => {
+                        if ui.button(title).clicked() {
+                            selected = Some(action.clone());
+                        }
+                    }
+                    MenuItem::SubMenu(submenu) => {
+                        // macOS titles the application menu with the name of
+                        // the application, regardless of the title given.
+                        let title = if submenu.title.is_empty() {
+                            &menubar.menu.title
+                        } else {
+                            &submenu.title
+                        };
+                        ui.menu_button(title, |ui| build_menu(ui, submenu, &mut selected));
+                    }
+                }
+            }
+        });
+    });
+
+    egui::Window::new(format!("About {}", APP_NAME))
+        .open(&mut menubar.show_about)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.heading(APP_NAME);
+            ui.label(format!("Version {}", env!("CARGO_PKG_VERSION")));
+            ui.label(
+                "A CAD environment for designing atomically-precise molecular nanotechnology.",
+            );
+        });
+
+    if let Some(action) = selected {
+        let window = windows.get_single_mut().ok();
+        perform_action(
+            action,
+            menubar,
+            window.map(|w| w.into_inner()),
+            &mut exit,
+            &mut app_actions,
+        );
+    }
+}
+
+pub fn attach_menu(
+    // The menubar is drawn inside the primary window by `show_menu_bar`, so
+    // the native window handles are not needed.
+    _windows: &WinitWindows,
+    commands: &mut Commands,
+    menu: &Menu,
+) {
+    commands.insert_resource(WindowMenuBar {
+        menu: menu.clone(),
+        show_about: false,
+    });
+}
+
+// End of File
@@ -2,15 +2,61 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this file,
 // You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use objc::declare::ClassDecl;
 use objc::rc::autoreleasepool;
-use objc::runtime::Object;
+use objc::runtime::{Class, Object, Sel};
 use objc::{class, msg_send, sel, sel_impl};
+use std::sync::{Mutex, Once};
 
 use crate::menubar::{
-    Menu, MenuAction, MenuItem, MenuShortcut, ModifierKeys, SystemAction, SystemShortcut,
+    AppAction, AppActionEvent, Menu, MenuAction, MenuItem, MenuShortcut, ModifierKeys,
+    SystemAction, SystemShortcut,
 };
 
-use bevy::winit::WinitWindows;
+use bevy::{
+    prelude::{Commands, EventWriter},
+    winit::WinitWindows,
+};
+
+// atomCAD's own menu actions, as selected by the user but not yet forwarded
+// to the ECS.  Cocoa invokes menu actions from the main thread while it is
+// processing events, outside of any system, so they are queued here until
+// `forward_menu_actions` runs.
+static PENDING_ACTIONS: Mutex<Vec<AppAction>> = Mutex::new(Vec::new());
+
+extern "C" fn perform_app_action(_this: &Object, _cmd: Sel, sender: *mut Object) {
+    // The tag of the menu item is the index of its action in `AppAction::ALL`
+    let tag: isize = unsafe { msg_send![sender, tag] };
+    if let Some(&action) = usize::try_from(tag)
+        .ok()
+        .and_then(|index| AppAction::ALL.get(index))
+    {
+        PENDING_ACTIONS.lock().unwrap().push(action);
+    }
+}
+
+// Cocoa menu items send their action to a target object, so atomCAD's own
+// actions are sent to an instance of this class.
+fn action_target_class() -> &'static Class {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        let mut decl = ClassDecl::new("AtomCADMenuTarget", class!(NSObject)).unwrap();
+        unsafe {
+            decl.add_method(
+                sel!(performAppAction:),
+                perform_app_action as extern "C" fn(&Object, Sel, *mut Object),
+            );
+        }
+        decl.register();
+    });
+    class!(AtomCADMenuTarget)
+}
+
+pub fn forward_menu_actions(mut events: EventWriter<AppActionEvent>) {
+    for action in PENDING_ACTIONS.lock().unwrap().drain(..) {
+        events.send(AppActionEvent(action));
+    }
+}
 
 fn nsstring(s: &str) -> *mut Object {
     unsafe {
@@ -25,7 +71,12 @@ fn nsstring(s: &str) -> *mut Object {
     }
 }
 
-unsafe fn build_menu(app: *mut Object, services_menu: *mut Object, menu: &Menu) -> *mut Object {
+unsafe fn build_menu(
+    app: *mut Object,
+    services_menu: *mut Object,
+    target: *mut Object,
+    menu: &Menu,
+) -> *mut Object {
     // Create root menu bar.
     let menuobj: *mut Object = msg_send![class![NSMenu], alloc];
     let menuobj: *mut Object = msg_send![menuobj, initWithTitle: nsstring(&menu.title)];
@@ -40,6 +91,7 @@ unsafe fn build_menu(app: *mut Object, services_menu: *mut Object, menu: &Menu)
             MenuItem::Entry(title, shortcut, action) => {
                 let title = nsstring(&title);
                 let mut is_service_menu = false;
+                let mut app_action = None;
                 let action = match action {
                     MenuAction::System(action) => match action {
                         SystemAction::LaunchAboutWindow => {
@@ -55,6 +107,10 @@ unsafe fn build_menu(app: *mut Object, services_menu: *mut Object, menu: &Menu)
                         SystemAction::ShowAll => Some(sel!(unhideAllApplications:)),
                         SystemAction::Terminate => Some(sel!(terminate:)),
                     },
+                    MenuAction::App(action) => {
+                        app_action = Some(*action);
+                        Some(sel!(performAppAction:))
+                    }
                 };
                 let shortcutkey = match shortcut {
                     MenuShortcut::None => nsstring(""),
@@ -63,6 +119,13 @@ unsafe fn build_menu(app: *mut Object, services_menu: *mut Object, menu: &Menu)
                         SystemShortcut::HideApp => nsstring("h"),
                         SystemShortcut::HideOthers => nsstring("h"),
                         SystemShortcut::QuitApp => nsstring("q"),
+                        SystemShortcut::Open => nsstring("o"),
+                        SystemShortcut::Save => nsstring("s"),
+                        SystemShortcut::SaveAs => nsstring("s"),
+                        SystemShortcut::Undo => nsstring("z"),
+                        SystemShortcut::Redo => nsstring("z"),
+                        // NSBackspaceCharacter, i.e. the Delete key
+                        SystemShortcut::Delete => nsstring("\u{8}"),
                     },
                 };
                 let shotcutmodifiers = match shortcut {
@@ -72,6 +135,12 @@ unsafe fn build_menu(app: *mut Object, services_menu: *mut Object, menu: &Menu)
                         SystemShortcut::HideApp => ModifierKeys::COMMAND,
                         SystemShortcut::HideOthers => ModifierKeys::COMMAND | ModifierKeys::OPTION,
                         SystemShortcut::QuitApp => ModifierKeys::COMMAND,
+                        SystemShortcut::Open => ModifierKeys::COMMAND,
+                        SystemShortcut::Save => ModifierKeys::COMMAND,
+                        SystemShortcut::SaveAs => ModifierKeys::COMMAND | ModifierKeys::SHIFT,
+                        SystemShortcut::Undo => ModifierKeys::COMMAND,
+                        SystemShortcut::Redo => ModifierKeys::COMMAND | ModifierKeys::SHIFT,
+                        SystemShortcut::Delete => ModifierKeys::COMMAND,
                     },
                 };
                 let mut item: *mut Object = msg_send![class![NSMenuItem], alloc];
@@ -115,6 +184,11 @@ unsafe fn build_menu(app: *mut Object, services_menu: *mut Object, menu: &Menu)
                     let _: () = msg_send![item, setKeyEquivalentModifierMask: modifiermask];
                 }
                 item = msg_send![item, autorelease];
+                if let Some(action) = app_action {
+                    let tag = AppAction::ALL.iter().position(|&a| a == action).unwrap() as isize;
+                    let _: () = msg_send![item, setTarget: target];
+                    let _: () = msg_send![item, setTag: tag];
+                }
                 if is_service_menu {
                     let _: () = msg_send![item, setSubmenu: services_menu];
                 }
@@ -124,7 +198,7 @@ unsafe fn build_menu(app: *mut Object, services_menu: *mut Object, menu: &Menu)
                 let item: *mut Object = msg_send![class![NSMenuItem], alloc];
                 let item: *mut Object = msg_send![item, init];
                 let item: *mut Object = msg_send![item, autorelease];
-                let submenu = build_menu(app, services_menu, &submenu);
+                let submenu = build_menu(app, services_menu, target, &submenu);
                 let _: () = msg_send![item, setSubmenu: submenu];
                 let _: () = msg_send![menuobj, addItem: item];
             }
@@ -142,6 +216,9 @@ pub fn attach_menu(
     // shared by the entire process, so we only need to set it once and don't
     // use the `WinitWindows` parameter.
     _windows: &WinitWindows,
+    // Likewise, the menu is owned by Cocoa once attached, so nothing needs to
+    // be stored in the ECS.
+    _commands: &mut Commands,
     menu: &Menu,
 ) {
     // Create the menu on macOS using Cocoa APIs.
@@ -156,8 +233,13 @@ pub fn attach_menu(
         let services_menu: *mut Object = msg_send![services_menu, autorelease];
         let _: () = msg_send![app, setServicesMenu: services_menu];
 
+        // Create the object which receives atomCAD's own menu actions.  Menu
+        // items do not retain their target, so it is deliberately never
+        // released.
+        let target: *mut Object = msg_send![action_target_class(), new];
+
         // Turn the menubar description into a Cocoa menu.
-        let obj = build_menu(app, services_menu, &menu);
+        let obj = build_menu(app, services_menu, target, &menu);
 
         // Register the menu with the NSApplication object.
         let _: () = msg_send![app, setMainMenu: obj];
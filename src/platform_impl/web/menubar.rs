@@ -3,10 +3,10 @@
 // You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use crate::menubar::Menu;
-use bevy::winit::WinitWindows;
+use bevy::{prelude::Commands, winit::WinitWindows};
 
 // Currently does nothing, and is present merely to ensure we compile on
 // web backends.
-pub fn attach_menu(_windows: &WinitWindows, _menu: &Menu) {}
+pub fn attach_menu(_windows: &WinitWindows, _commands: &mut Commands, _menu: &Menu) {}
 
 // End of File
@@ -3,11 +3,11 @@
 // You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use crate::menubar::Menu;
-use bevy::winit::WinitWindows;
+use bevy::{prelude::Commands, winit::WinitWindows};
 
 // Currently does nothing, and is present merely to ensure we compile on
 // Windows.  Should use the win32 APIs to setup the menubar for the main
 // window(s).
-pub fn attach_menu(_windows: &WinitWindows, _menu: &Menu) {}
+pub fn attach_menu(_windows: &WinitWindows, _commands: &mut Commands, _menu: &Menu) {}
 
 // End of File
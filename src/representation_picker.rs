@@ -0,0 +1,38 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::molecule_builder::Representation;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+/// A window for choosing how each molecule in the workspace is drawn.
+pub fn representation_picker(
+    mut contexts: EguiContexts,
+    mut q_molecule: Query<(Entity, &mut Representation)>,
+) {
+    egui::Window::new("Display")
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for (number, (entity, mut representation)) in q_molecule.iter_mut().enumerate() {
+                let mut selected = representation.0;
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source(entity)
+                        .selected_text(selected.name())
+                        .show_ui(ui, |ui| {
+                            for choice in molecule::representation::Representation::ALL {
+                                ui.selectable_value(&mut selected, choice, choice.name());
+                            }
+                        });
+                    ui.label(format!("Molecule {}", number + 1));
+                });
+
+                // Only touch the component when the selection changes
+                if selected != representation.0 {
+                    representation.0 = selected;
+                }
+            }
+        });
+}
+
+// End of File
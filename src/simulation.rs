@@ -0,0 +1,264 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::molecule_builder::{ActiveForceField, Molecule, TrajectoryPlayback};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use molecule::dynamics::{Dynamics, DynamicsOptions, DynamicsReport, Thermostat};
+use molecule::minimize::{minimize, MinimizeOptions, MinimizeReport};
+use std::collections::HashMap;
+
+/// The molecular dynamics run of the molecules in the workspace.  While it
+/// is running the molecules are no longer relaxed, but move under the force
+/// field at the temperature of the thermostat.
+#[derive(Resource)]
+pub struct MolecularDynamics {
+    pub running: bool,
+    pub options: DynamicsOptions,
+    pub steps_per_frame: usize,
+    /// The state of each molecule after the last frame.
+    pub reports: Vec<DynamicsReport>,
+    // The run of each molecule, keyed by the molecule's entity
+    runs: HashMap<Entity, Dynamics>,
+    // Advanced whenever a run starts, so that the random numbers of each run
+    // differ
+    seed: u64,
+}
+
+impl Default for MolecularDynamics {
+    fn default() -> Self {
+        Self {
+            running: false,
+            options: DynamicsOptions::default(),
+            steps_per_frame: 10,
+            reports: Vec::new(),
+            runs: HashMap::new(),
+            seed: 0,
+        }
+    }
+}
+
+impl MolecularDynamics {
+    // Starts the run of a molecule afresh from its positions and velocities
+    fn start(
+        &mut self,
+        entity: Entity,
+        molecule: &molecule::Molecule,
+        force_field: &ActiveForceField,
+    ) -> &mut Dynamics {
+        self.seed += 1;
+        let run = Dynamics::new(molecule, force_field.0.as_ref(), self.options, self.seed);
+        self.runs.insert(entity, run);
+        self.runs.get_mut(&entity).unwrap()
+    }
+}
+
+/// A run condition for the systems that move molecules when dynamics is not
+/// running.
+pub fn dynamics_stopped(dynamics: Res<MolecularDynamics>) -> bool {
+    !dynamics.running
+}
+
+/// Advances the dynamics of every molecule by a few timesteps each frame.
+/// Each molecule's run carries on from frame to frame, and is only started
+/// again (from the molecule's positions and velocities) once the molecule has
+/// been edited, or the force field changed.
+pub fn run_dynamics(
+    mut dynamics: ResMut<MolecularDynamics>,
+    // Molecules playing back a trajectory are positioned by the trajectory
+    mut q_molecule: Query<(Entity, &mut Molecule), Without<TrajectoryPlayback>>,
+    force_field: Res<ActiveForceField>,
+) {
+    if !dynamics.running {
+        return;
+    }
+    let dynamics = &mut *dynamics;
+    dynamics.reports.clear();
+    // Forget the runs of molecules that are gone
+    dynamics
+        .runs
+        .retain(|&entity, _| q_molecule.contains(entity));
+    for (entity, mut molecule) in q_molecule.iter_mut() {
+        let edited = !dynamics
+            .runs
+            .get(&entity)
+            .is_some_and(|run| run.follows(&molecule));
+        if edited || force_field.is_changed() {
+            dynamics.start(entity, &molecule, &force_field);
+        }
+        let run = dynamics.runs.get_mut(&entity).unwrap();
+        run.options = dynamics.options;
+        dynamics.reports.push(run.run(dynamics.steps_per_frame));
+        run.write_to(&mut molecule);
+    }
+}
+
+fn thermostat_name(thermostat: Thermostat) -> &'static str {
+    match thermostat {
+        Thermostat::None => "None",
+        Thermostat::Berendsen { .. } => "Berendsen",
+        Thermostat::Langevin { .. } => "Langevin",
+    }
+}
+
+/// A window for minimizing the energy of every molecule in the workspace, or
+/// running molecular dynamics on them, which shows how the last minimization
+/// and the dynamics of each molecule are going.
+pub fn simulation_panel(
+    mut contexts: EguiContexts,
+    mut options: Local<MinimizeOptions>,
+    mut reports: Local<Vec<MinimizeReport>>,
+    mut dynamics: ResMut<MolecularDynamics>,
+    // Molecules playing back a trajectory are positioned by the trajectory
+    mut q_molecule: Query<(Entity, &mut Molecule), Without<TrajectoryPlayback>>,
+    force_field: Res<ActiveForceField>,
+) {
+    egui::Window::new("Simulation")
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("RMS force tolerance");
+                ui.add(
+                    egui::DragValue::new(&mut options.rms_force_tolerance)
+                        .speed(0.01)
+                        .clamp_range(0.001..=10.0)
+                        .suffix(" kcal/mol/Å"),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Iteration limit");
+                ui.add(egui::DragValue::new(&mut options.max_iterations).clamp_range(1..=100_000));
+            });
+
+            if ui.button("Minimize").clicked() {
+                *reports = q_molecule
+                    .iter_mut()
+                    .map(|(_, mut molecule)| {
+                        minimize(&mut molecule, force_field.0.as_ref(), &options)
+                    })
+                    .collect();
+            }
+
+            for (number, report) in reports.iter().enumerate() {
+                ui.separator();
+                ui.label(format!("Molecule {}", number + 1));
+                ui.label(format!("Energy: {:.3} kcal/mol", report.energy.total()));
+                ui.label(format!("Max force: {:.3} kcal/mol/Å", report.max_force));
+                ui.label(format!("RMS force: {:.3} kcal/mol/Å", report.rms_force));
+                ui.label(if report.converged {
+                    format!("Converged after {} steps", report.iterations)
+                } else {
+                    format!(
+                        "Stopped after {} steps without converging",
+                        report.iterations
+                    )
+                });
+            }
+
+            ui.separator();
+            ui.heading("Dynamics");
+            ui.horizontal(|ui| {
+                ui.label("Temperature");
+                ui.add(
+                    egui::DragValue::new(&mut dynamics.options.temperature)
+                        .clamp_range(0.0..=5000.0)
+                        .suffix(" K"),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Timestep");
+                ui.add(
+                    egui::DragValue::new(&mut dynamics.options.timestep)
+                        .speed(0.01)
+                        .clamp_range(0.01..=2.0)
+                        .suffix(" fs"),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Steps per frame");
+                ui.add(egui::DragValue::new(&mut dynamics.steps_per_frame).clamp_range(1..=1000));
+            });
+
+            let thermostat = &mut dynamics.options.thermostat;
+            egui::ComboBox::from_label("Thermostat")
+                .selected_text(thermostat_name(*thermostat))
+                .show_ui(ui, |ui| {
+                    for choice in [
+                        Thermostat::None,
+                        Thermostat::Berendsen {
+                            coupling_time: 100.0,
+                        },
+                        Thermostat::Langevin { friction: 0.01 },
+                    ] {
+                        let name = thermostat_name(choice);
+                        // Keep the parameter of the current thermostat
+                        if ui
+                            .selectable_label(thermostat_name(*thermostat) == name, name)
+                            .clicked()
+                            && thermostat_name(*thermostat) != name
+                        {
+                            *thermostat = choice;
+                        }
+                    }
+                });
+            match thermostat {
+                Thermostat::None => {}
+                Thermostat::Berendsen { coupling_time } => {
+                    ui.horizontal(|ui| {
+                        ui.label("Coupling time");
+                        ui.add(
+                            egui::DragValue::new(coupling_time)
+                                .clamp_range(1.0..=10_000.0)
+                                .suffix(" fs"),
+                        );
+                    });
+                }
+                Thermostat::Langevin { friction } => {
+                    ui.horizontal(|ui| {
+                        ui.label("Friction");
+                        ui.add(
+                            egui::DragValue::new(friction)
+                                .speed(0.001)
+                                .clamp_range(0.0001..=1.0)
+                                .suffix(" /fs"),
+                        );
+                    });
+                }
+            }
+
+            let label = if dynamics.running { "Stop" } else { "Run" };
+            if ui.button(label).clicked() {
+                dynamics.running = !dynamics.running;
+                dynamics.runs.clear();
+                for (entity, mut molecule) in q_molecule.iter_mut() {
+                    if dynamics.running {
+                        // Start from velocities at the target temperature
+                        let run = dynamics.start(entity, &molecule, &force_field);
+                        run.thermalize();
+                        run.write_to(&mut molecule);
+                    } else {
+                        // Relaxing takes over from a standstill
+                        for node in molecule.graph.node_weights_mut() {
+                            node.vel = Vec3::ZERO;
+                        }
+                    }
+                }
+                dynamics.reports.clear();
+            }
+
+            for (number, report) in dynamics.reports.iter().enumerate() {
+                ui.separator();
+                ui.label(format!("Molecule {}", number + 1));
+                ui.label(format!("Kinetic energy: {:.3} kcal/mol", report.kinetic));
+                ui.label(format!(
+                    "Potential energy: {:.3} kcal/mol",
+                    report.potential.total()
+                ));
+                ui.label(format!("Total energy: {:.3} kcal/mol", report.total()));
+                ui.label(format!("Temperature: {:.1} K", report.temperature));
+            }
+        });
+}
+
+// End of File
@@ -0,0 +1,244 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::molecule_builder::{ActiveForceField, EditHistory, Molecule};
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::render::mesh::PrimitiveTopology;
+use bevy_egui::{egui, EguiContexts};
+use molecule::cell::UnitCell;
+use molecule::edit::Edit;
+
+const OUTLINE_COLOR: Color = Color::rgb(0.8, 0.8, 0.8);
+
+// The space left around a molecule when a unit cell is first given to it, in
+// angstroms.
+const CELL_MARGIN: f32 = 5.0;
+
+/// The outline of a molecule's unit cell, which is a child of the molecule's
+/// entity.  The outline is replaced when the cell changes.
+#[derive(Component)]
+pub struct CellOutline(UnitCell);
+
+fn outline_mesh(cell: &UnitCell) -> Mesh {
+    let positions: Vec<[f32; 3]> = cell
+        .edges()
+        .iter()
+        .flat_map(|(start, end)| [start.to_array(), end.to_array()])
+        .collect();
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh
+}
+
+/// Draws the edges of the unit cell of each periodic molecule.
+pub fn draw_unit_cells(
+    mut commands: Commands,
+    q_molecule: Query<(Entity, &Molecule, Option<&Children>), Changed<Molecule>>,
+    q_outline: Query<&CellOutline>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut material: Local<Option<Handle<StandardMaterial>>>,
+) {
+    for (molecule_id, molecule, children) in q_molecule.iter() {
+        let mut drawn = false;
+        for &child in children.into_iter().flatten() {
+            let Ok(outline) = q_outline.get(child) else {
+                continue;
+            };
+            if Some(outline.0) == molecule.cell {
+                drawn = true;
+            } else {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+
+        let Some(cell) = molecule.cell else {
+            continue;
+        };
+        if drawn {
+            continue;
+        }
+        let material = material
+            .get_or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: OUTLINE_COLOR,
+                    unlit: true,
+                    ..default()
+                })
+            })
+            .clone();
+        let outline = commands
+            .spawn((
+                PbrBundle {
+                    mesh: meshes.add(outline_mesh(&cell)),
+                    material,
+                    ..default()
+                },
+                NotShadowCaster,
+                CellOutline(cell),
+            ))
+            .id();
+        commands.entity(molecule_id).add_child(outline);
+    }
+}
+
+// A rectangular cell with room for the molecule, and the offset that moves
+// the molecule to the middle of it.  The cell is at least twice `range` wide,
+// so that the molecule interacts with no more than one copy of each of its
+// particles.
+fn enclosing_cell(molecule: &molecule::Molecule, range: f32) -> (Option<UnitCell>, Vec3) {
+    let (min, max) = molecule.graph.node_weights().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), node| (min.min(node.pos), max.max(node.pos)),
+    );
+    let (min, max) = if min.cmple(max).all() {
+        (min, max)
+    } else {
+        (Vec3::ZERO, Vec3::ZERO)
+    };
+    let size = (max - min + Vec3::splat(2.0 * CELL_MARGIN)).max(Vec3::splat(2.0 * range));
+    let offset = size / 2.0 - (min + max) / 2.0;
+    (UnitCell::new(Mat3::from_diagonal(size)), offset)
+}
+
+fn set_cell(
+    history: &mut EditHistory,
+    entity: Entity,
+    molecule: &mut molecule::Molecule,
+    cell: Option<UnitCell>,
+    offset: Vec3,
+) {
+    if let Err(err) = history.perform(entity, molecule, Edit::SetCell { cell, offset }) {
+        error!("Could not change the unit cell: {}", err);
+    }
+}
+
+/// A window for making each molecule in the workspace periodic, and choosing
+/// the shape of its unit cell.  Cells are kept at least twice as wide as the
+/// range of the force field, and cells that are narrower than that (such as
+/// those read from files) are pointed out.
+///
+/// Each change of cell is an undoable edit.  While a parameter is being
+/// dragged the cell follows it directly, and the whole drag is recorded as
+/// one edit once it ends.
+pub fn unit_cell_editor(
+    mut contexts: EguiContexts,
+    mut q_molecule: Query<(Entity, &mut Molecule)>,
+    force_field: Res<ActiveForceField>,
+    mut history: ResMut<EditHistory>,
+    // The molecule whose cell is being dragged, and its cell before the drag
+    mut dragging: Local<Option<(Entity, Option<UnitCell>)>>,
+) {
+    let range = force_field.0.range();
+    egui::Window::new("Unit Cell")
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for (number, (entity, mut molecule)) in q_molecule.iter_mut().enumerate() {
+                if number > 0 {
+                    ui.separator();
+                }
+                let mut periodic = molecule.cell.is_some();
+                ui.checkbox(
+                    &mut periodic,
+                    format!("Molecule {} is periodic", number + 1),
+                );
+                if periodic != molecule.cell.is_some() {
+                    let (cell, offset) = if periodic {
+                        enclosing_cell(&molecule, range)
+                    } else {
+                        (None, Vec3::ZERO)
+                    };
+                    set_cell(&mut history, entity, &mut molecule, cell, offset);
+                }
+
+                let Some(cell) = molecule.cell else {
+                    continue;
+                };
+                let (mut lengths, mut angles) = (cell.lengths(), cell.angles());
+                let mut dragged = false;
+                ui.horizontal(|ui| {
+                    for (label, length) in ["a", "b", "c"].into_iter().zip(lengths.as_mut()) {
+                        ui.label(label);
+                        dragged |= ui
+                            .add(
+                                egui::DragValue::new(length)
+                                    .speed(0.1)
+                                    .clamp_range(1.0..=1000.0)
+                                    .suffix(" Å"),
+                            )
+                            .dragged();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    for (label, angle) in ["α", "β", "γ"].into_iter().zip(angles.as_mut()) {
+                        ui.label(label);
+                        dragged |= ui
+                            .add(
+                                egui::DragValue::new(angle)
+                                    .speed(0.5)
+                                    .clamp_range(30.0..=150.0)
+                                    .suffix("°"),
+                            )
+                            .dragged();
+                    }
+                });
+
+                if !cell.is_wide_enough(range) {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!(
+                            "The cell is narrower than {:.1} Å, so some periodic \
+                             interactions are missed",
+                            2.0 * range
+                        ),
+                    );
+                }
+
+                // A drag of this molecule's cell has ended: put the cell back
+                // as it was, and record the change from there
+                let drag_start = match *dragging {
+                    Some((dragged_entity, start)) if dragged_entity == entity => Some(start),
+                    _ => None,
+                };
+                if let (Some(start), false) = (drag_start, dragged) {
+                    *dragging = None;
+                    let end = molecule.cell;
+                    molecule.cell = start;
+                    if end != start {
+                        set_cell(&mut history, entity, &mut molecule, end, Vec3::ZERO);
+                    }
+                }
+
+                // Only touch the molecule when the cell changes.  The new cell
+                // keeps the orientation of the old one, so that it does not
+                // turn under the atoms.  It may not be made narrower than the
+                // force field needs, but a narrow cell may still be widened.
+                if lengths != cell.lengths() || angles != cell.angles() {
+                    let new_cell = cell.with_parameters(lengths, angles).filter(|new_cell| {
+                        new_cell.is_wide_enough(range)
+                            || new_cell.widths().min_element() >= cell.widths().min_element()
+                    });
+                    match new_cell {
+                        Some(new_cell) if dragged => {
+                            if drag_start.is_none() {
+                                *dragging = Some((entity, molecule.cell));
+                            }
+                            molecule.cell = Some(new_cell);
+                        }
+                        Some(new_cell) => set_cell(
+                            &mut history,
+                            entity,
+                            &mut molecule,
+                            Some(new_cell),
+                            Vec3::ZERO,
+                        ),
+                        None => {}
+                    }
+                }
+            }
+        });
+}
+
+// End of File
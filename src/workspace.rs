@@ -0,0 +1,200 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this file,
+// You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::camera::PanOrbitCamera;
+use crate::menubar::{AppAction, AppActionEvent};
+use crate::molecule_builder::{
+    spawn_molecule, EditHistory, Molecule, PbrCache, TrajectoryPlayback,
+};
+use bevy::{prelude::*, winit::WinitWindows};
+use document::{CameraState, Document, DocumentError, Format};
+use molecule::io::xyz;
+use molecule::trajectory::Trajectory;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// The file that the workspace was last opened from or saved to, which
+/// File > Save writes back to.
+#[derive(Resource, Default)]
+pub struct WorkspacePath(pub Option<PathBuf>);
+
+#[cfg(not(target_family = "wasm"))]
+fn file_dialog() -> rfd::FileDialog {
+    rfd::FileDialog::new()
+        .add_filter("atomCAD document", &[document::EXTENSION])
+        .add_filter("atomCAD document (JSON)", &["json"])
+}
+
+// The extensions of XYZ files, which File > Open imports as a molecule and its
+// trajectory.
+const XYZ_EXTENSIONS: &[&str] = &["xyz", "extxyz"];
+
+// How quickly the frames of an imported trajectory are played back.
+const TRAJECTORY_FRAMES_PER_SECOND: f32 = 10.0;
+
+#[cfg(not(target_family = "wasm"))]
+fn open_dialog() -> Option<PathBuf> {
+    file_dialog().add_filter("XYZ", XYZ_EXTENSIONS).pick_file()
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn save_dialog() -> Option<PathBuf> {
+    file_dialog()
+        .set_file_name(&format!("Untitled.{}", document::EXTENSION))
+        .save_file()
+}
+
+// There is no filesystem to open or save files in on the web.
+#[cfg(target_family = "wasm")]
+fn open_dialog() -> Option<PathBuf> {
+    None
+}
+
+#[cfg(target_family = "wasm")]
+fn save_dialog() -> Option<PathBuf> {
+    None
+}
+
+// What File > Open found in a file
+enum Opened {
+    Document(Document),
+    Xyz(molecule::Molecule, Trajectory),
+}
+
+fn read_file(path: &Path) -> Result<Opened, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let is_xyz = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            XYZ_EXTENSIONS
+                .iter()
+                .any(|xyz| extension.eq_ignore_ascii_case(xyz))
+        });
+    Ok(if is_xyz {
+        let (molecule, trajectory) = xyz::read(reader)?;
+        Opened::Xyz(molecule, trajectory)
+    } else {
+        Opened::Document(Document::read(reader)?)
+    })
+}
+
+fn write_document(document: &Document, path: &Path) -> Result<(), DocumentError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    document.write(Format::from_path(path), &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Handles File > Open, Save and Save As.  Opening a document replaces every
+/// molecule in the workspace, along with their undo history, and restores the
+/// camera.  Opening an XYZ file replaces the workspace with its molecule,
+/// which plays back the file's frames if there is more than one.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_file_actions(
+    mut commands: Commands,
+    mut events: EventReader<AppActionEvent>,
+    mut path: ResMut<WorkspacePath>,
+    mut history: ResMut<EditHistory>,
+    pbr_cache: Res<PbrCache>,
+    q_molecule: Query<(Entity, &Molecule)>,
+    mut q_camera: Query<(&mut PanOrbitCamera, &mut Transform)>,
+    // Unused, but forces this system to run on the main thread, from which
+    // native file dialogs have to be shown on some platforms.
+    _windows: NonSend<WinitWindows>,
+) {
+    for AppActionEvent(action) in events.iter() {
+        match action {
+            AppAction::Open => {
+                let Some(file) = open_dialog() else {
+                    continue;
+                };
+                let opened = match read_file(&file) {
+                    Ok(opened) => opened,
+                    Err(err) => {
+                        error!("Could not open {}: {}", file.display(), err);
+                        continue;
+                    }
+                };
+
+                for (entity, _) in q_molecule.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                history.clear();
+
+                match opened {
+                    Opened::Document(document) => {
+                        for molecule in document.molecules {
+                            spawn_molecule(&mut commands, &pbr_cache, molecule);
+                        }
+
+                        if let (Some(camera), Ok((mut pan_orbit, mut transform))) =
+                            (document.camera, q_camera.get_single_mut())
+                        {
+                            pan_orbit.focus = camera.focus;
+                            pan_orbit.radius = camera.radius;
+                            pan_orbit.upside_down = camera.upside_down;
+                            transform.rotation = camera.rotation;
+                            transform.translation =
+                                camera.focus + camera.rotation * Vec3::new(0.0, 0.0, camera.radius);
+                        }
+
+                        path.0 = Some(file);
+                    }
+                    Opened::Xyz(molecule, trajectory) => {
+                        let entity = spawn_molecule(&mut commands, &pbr_cache, molecule);
+                        // A single frame is just the molecule itself
+                        if trajectory.len() > 1 {
+                            commands.entity(entity).insert(TrajectoryPlayback::new(
+                                trajectory,
+                                TRAJECTORY_FRAMES_PER_SECOND,
+                            ));
+                        }
+
+                        // Saving writes a new document, rather than over the
+                        // imported file
+                        path.0 = None;
+                    }
+                }
+            }
+            AppAction::Save | AppAction::SaveAs => {
+                let file = match (&path.0, action) {
+                    (Some(file), AppAction::Save) => file.clone(),
+                    _ => {
+                        let Some(file) = save_dialog() else {
+                            continue;
+                        };
+                        file
+                    }
+                };
+
+                let document = Document {
+                    molecules: q_molecule
+                        .iter()
+                        .map(|(_, molecule)| molecule.0.clone())
+                        .collect(),
+                    camera: q_camera
+                        .get_single()
+                        .ok()
+                        .map(|(pan_orbit, transform)| CameraState {
+                            focus: pan_orbit.focus,
+                            radius: pan_orbit.radius,
+                            rotation: transform.rotation,
+                            upside_down: pan_orbit.upside_down,
+                        }),
+                };
+                match write_document(&document, &file) {
+                    Ok(()) => path.0 = Some(file),
+                    Err(err) => error!("Could not save {}: {}", file.display(), err),
+                }
+            }
+            // The Edit menu is handled by `handle_edit_actions`
+            _ => {}
+        }
+    }
+}
+
+// End of File